@@ -40,6 +40,23 @@ pub enum IdentType {
   },
   Array {
     ident: Box<IdentType>,
+    /// The element count of `T[N]`, a constant expression.
+    size: Option<Box<Expr>>,
+    span: Option<Span>,
+  },
+  /// `T?`, either a `T` or `none`.
+  Option {
+    ident: Box<IdentType>,
+    span: Option<Span>,
+  },
+  /// `T!E` or `Result<T, E>`, either a `T` or an error `E`.
+  Result {
+    ok: Box<IdentType>,
+    err: Box<IdentType>,
+    span: Option<Span>,
+  },
+  Tuple {
+    types: Vec<IdentType>,
     span: Option<Span>,
   },
   Default,
@@ -64,6 +81,11 @@ impl IdentType {
         ret_type: Box::new(ret_type.to_type()),
       },
       IdentType::Array { ident, .. } => Type::Array(Box::new(ident.to_type())),
+      IdentType::Option { ident, .. } => Type::Option(Box::new(ident.to_type())),
+      IdentType::Result { ok, err, .. } => {
+        Type::Result(Box::new(ok.to_type()), Box::new(err.to_type()))
+      }
+      IdentType::Tuple { types, .. } => Type::Tuple(IdentType::vec_to_type(types)),
       IdentType::Default => Type::Default,
       IdentType::Error => Type::Error,
       _ => unreachable!(),
@@ -83,11 +105,22 @@ pub struct IdentFunction {
   pub span: Span,
 }
 
+/// A generic parameter `T: Bound + Other`, which the types it is instantiated
+/// with must implement the interfaces of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentGeneric {
+  pub ident: String,
+  pub bounds: Vec<String>,
+  pub span: Span,
+}
+
 /// https://whistle.js.org/docs/specification/grammar#identifiers
 #[derive(Debug, Clone, PartialEq)]
 pub struct IdentTyped {
   pub ident: String,
   pub type_ident: IdentType,
+  /// The value of a function parameter when a call leaves it out.
+  pub default: Option<Expr>,
   pub span: Option<Span>,
 }
 
@@ -139,6 +172,23 @@ pub enum Expr {
     else_expr: Box<Expr>,
     span: Span,
   },
+  /// `expr as T`, a conversion between primitive types.
+  Cast {
+    expr: Box<Expr>,
+    types: IdentType,
+    span: Span,
+  },
+  /// `expr?`, the value of a result, returning its error from the function.
+  Try {
+    expr: Box<Expr>,
+    span: Span,
+  },
+  /// `err(expr)`, a result holding the error `expr`.
+  Err {
+    expr: Box<Expr>,
+    meta_id: usize,
+    span: Span,
+  },
 }
 
 impl Expr {
@@ -147,6 +197,9 @@ impl Expr {
       Expr::Unary { span, .. } => span.clone(),
       Expr::Binary { span, .. } => span.clone(),
       Expr::Cond { span, .. } => span.clone(),
+      Expr::Cast { span, .. } => *span,
+      Expr::Try { span, .. } => *span,
+      Expr::Err { span, .. } => *span,
     }
   }
 }
@@ -198,6 +251,12 @@ pub enum IdentVal {
   },
   Arguments {
     args: Vec<Expr>,
+    /// Arguments passed as `name: expr`, after the positional ones. The
+    /// checker moves them into `args`.
+    named: Vec<(String, Expr)>,
+    /// The first of the type variables a call of a generic function binds its
+    /// generic parameters to.
+    meta_id: usize,
     span: Span,
   },
   Index {
@@ -257,6 +316,12 @@ pub enum Stmt {
     val: Expr,
     span: Span,
   },
+  TupleDecl {
+    mutable: bool,
+    idents: Vec<IdentTyped>,
+    val: Expr,
+    span: Span,
+  },
   Block {
     stmts: Vec<Stmt>,
     span: Span,
@@ -270,7 +335,9 @@ pub enum Stmt {
     span: Span,
   },
   Assign {
-    ident: String,
+    /// The assigned place, a variable followed by any field selectors and
+    /// indices.
+    lhs: Expr,
     rhs: Expr,
     span: Span,
   },
@@ -286,6 +353,7 @@ impl Stmt {
       Stmt::Return { span, .. } => span.clone(),
       Stmt::VarDecl { span, .. } => span.clone(),
       Stmt::ValDecl { span, .. } => span.clone(),
+      Stmt::TupleDecl { span, .. } => span.clone(),
       Stmt::Block { span, .. } => span.clone(),
       Stmt::Tip { span, .. } => span.clone(),
       Stmt::Expr { span, .. } => span.clone(),
@@ -305,12 +373,20 @@ pub enum ProgramStmt {
   Extern {
     idents: Vec<IdentExternFn>,
     namespace: String,
+    tips: Vec<Tip>,
+    span: Span,
+  },
+  Builtin {
+    module: String,
+    idents: Vec<IdentImport>,
     span: Span,
   },
   FunctionDecl {
+    tips: Vec<Tip>,
     export: bool,
     inline: bool,
     ident: String,
+    generics: Vec<IdentGeneric>,
     params: Vec<IdentTyped>,
     ret_type: IdentType,
     stmt: Vec<Stmt>,
@@ -327,11 +403,28 @@ pub enum ProgramStmt {
     span: Span,
   },
   StructDecl {
+    tips: Vec<Tip>,
     export: bool,
     ident: String,
     params: Vec<IdentTyped>,
     span: Span,
   },
+  /// `interface ident { fn method(self): T }`, the methods of a type
+  /// implementing it.
+  InterfaceDecl {
+    ident: String,
+    methods: Vec<IdentExternFn>,
+    span: Span,
+  },
+  /// `impl interface for types { fn method(self): T { .. } }`, or
+  /// `impl types { .. }` for methods outside of an interface.
+  ImplDecl {
+    interface: Option<String>,
+    types: IdentType,
+    /// The methods, as function declarations.
+    fns: Vec<ProgramStmt>,
+    span: Span,
+  },
   TypeDecl {
     export: bool,
     ident: String,
@@ -353,10 +446,13 @@ impl ProgramStmt {
     match &self {
       ProgramStmt::Import { span, .. } => span.clone(),
       ProgramStmt::Extern { span, .. } => span.clone(),
+      ProgramStmt::Builtin { span, .. } => span.clone(),
       ProgramStmt::FunctionDecl { span, .. } => span.clone(),
       ProgramStmt::VarDecl { span, .. } => span.clone(),
       ProgramStmt::ValDecl { span, .. } => span.clone(),
       ProgramStmt::StructDecl { span, .. } => span.clone(),
+      ProgramStmt::InterfaceDecl { span, .. } => *span,
+      ProgramStmt::ImplDecl { span, .. } => *span,
       ProgramStmt::TypeDecl { span, .. } => span.clone(),
       ProgramStmt::Stmt { span, .. } => span.clone(),
       ProgramStmt::Tip { span, .. } => span.clone(),
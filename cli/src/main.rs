@@ -1,5 +1,6 @@
 use clap::{Parser, Subcommand};
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Instant;
 use std::{fs, sync::Arc};
 use tokio::sync::RwLock;
@@ -7,7 +8,6 @@ use tokio::sync::RwLock;
 mod lsp;
 mod util;
 
-
 use lsp::WhistleBackend;
 
 use tower_lsp::{LspService, Server};
@@ -41,6 +41,15 @@ enum Commands {
   #[command(arg_required_else_help = true)]
   Run {
     path: String,
+    /// skip validating the generated module
+    #[arg(long = "no-validate")]
+    no_validate: bool,
+    /// trap with a message on integer overflow and division by zero
+    #[arg(long = "checked")]
+    checked: bool,
+    /// fail instead of only printing warnings
+    #[arg(long = "deny-warnings")]
+    deny_warnings: bool,
   },
 
   /// compiles the file
@@ -51,6 +60,37 @@ enum Commands {
     /// output file
     #[arg(short = 'o', long = "output", value_name = "OUTPUT")]
     output: Option<String>,
+    /// skip validating the generated module
+    #[arg(long = "no-validate")]
+    no_validate: bool,
+    /// keep functions, globals and data unreachable from the exports
+    #[arg(long = "no-tree-shake")]
+    no_tree_shake: bool,
+    /// trap with a message on integer overflow and division by zero
+    #[arg(long = "checked")]
+    checked: bool,
+    /// fail instead of only printing warnings
+    #[arg(long = "deny-warnings")]
+    deny_warnings: bool,
+    /// also write a source map next to the output
+    #[arg(long = "source-map")]
+    source_map: bool,
+    /// optimization level (0-3)
+    #[arg(
+      short = 'O',
+      value_name = "LEVEL",
+      default_value_t = 0,
+      value_parser = clap::value_parser!(u8).range(0..=3)
+    )]
+    opt_level: u8,
+    /// what to write: wasm, wat or ir
+    #[arg(
+      long = "emit",
+      value_name = "KIND",
+      default_value = "wasm",
+      value_parser = ["wasm", "wat", "ir"]
+    )]
+    emit: String,
   },
 
   /// launches the language Server
@@ -84,32 +124,105 @@ async fn main() {
       );
     }
 
-    Commands::Run { path } => {
+    Commands::Run {
+      path,
+      no_validate,
+      checked,
+      deny_warnings,
+    } => {
       let text = fs::read_to_string(path).expect("Something went wrong, we can't read this file.");
-      let bytes = util::compile(&text);
+      let options = util::CompileOptions {
+        validate: !no_validate,
+        checked,
+        deny_warnings,
+        ..Default::default()
+      };
+      let (_, bytes) = util::compile(&text, &options);
       let engine = wasmtime::Engine::default();
       let mut linker = wasmtime::Linker::new(&engine);
       wasmtime_wasi::add_to_linker(&mut linker, |s| s).unwrap();
+      linker
+        .func_wrap("sys", "printInt", |val: i32| println!("{}", val))
+        .unwrap();
+      linker
+        .func_wrap("sys", "printFloat", |val: f64| println!("{}", val))
+        .unwrap();
+      linker
+        .func_wrap(
+          "sys",
+          "panic",
+          |mut caller: wasmtime::Caller<'_, wasmtime_wasi::WasiCtx>, ptr: i32, len: i32| {
+            let memory = caller
+              .get_export("memory")
+              .and_then(|export| export.into_memory());
+            if let Some(memory) = memory {
+              let bytes = memory.data(&caller);
+              let message = bytes
+                .get(ptr as usize..ptr as usize + len as usize)
+                .unwrap_or_default();
+              eprintln!("panicked: {}", String::from_utf8_lossy(message));
+            }
+          },
+        )
+        .unwrap();
       let wasi = wasmtime_wasi::WasiCtxBuilder::new()
-          .inherit_stdio()
-          .inherit_args().unwrap()
-          .build();
+        .inherit_stdio()
+        .inherit_args()
+        .unwrap()
+        .build();
       let mut store = wasmtime::Store::new(&engine, wasi);
-  
+
       let module = wasmtime::Module::new(&engine, &bytes).unwrap();
       linker.module(&mut store, "", &module).unwrap();
       linker
-          .get_default(&mut store, "").unwrap()
-          .typed::<(), ()>(&store).unwrap()
-          .call(&mut store, ()).unwrap();  
+        .get_default(&mut store, "")
+        .unwrap()
+        .typed::<(), ()>(&store)
+        .unwrap()
+        .call(&mut store, ())
+        .unwrap();
     }
 
-    Commands::Compile { path, output } => {
+    Commands::Compile {
+      path,
+      output,
+      no_validate,
+      no_tree_shake,
+      checked,
+      deny_warnings,
+      source_map,
+      opt_level,
+      emit,
+    } => {
       let now = Instant::now();
-      let output = output.unwrap_or(path.replace(".whi", ".wasm"));
-      let text = fs::read_to_string(path).expect("Something went wrong, we can't read this file.");
-      let bytes = util::compile(&text);
-      if output.ends_with(".wat") {
+      let output = output.unwrap_or(path.replace(".whi", &format!(".{}", emit)));
+      let text = fs::read_to_string(&path).expect("Something went wrong, we can't read this file.");
+      let map_path = format!("{}.map", output);
+      let options = util::CompileOptions {
+        validate: !no_validate,
+        source_map: source_map.then(|| {
+          Path::new(&map_path)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string()
+        }),
+        opt_level,
+        emit_ir: emit == "ir",
+        tree_shake: !no_tree_shake,
+        checked,
+        deny_warnings,
+      };
+      let (compiler, bytes) = util::compile(&text, &options);
+      if source_map {
+        let map =
+          whistle_compiler::source_map(&bytes, &compiler.module.debug, &compiler.sources, &path);
+        fs::write(&map_path, map).expect("Something went wrong, we can't write this file.");
+      }
+      if emit == "ir" {
+        fs::write(output, whistle_compiler::print_ir(&compiler))
+          .expect("Something went wrong, we can't write this file.");
+      } else if emit == "wat" || output.ends_with(".wat") {
         let wasm_text = wasmprinter::print_bytes(&bytes).unwrap();
         fs::write(output, wasm_text.as_bytes())
           .expect("Something went wrong, we can't write this file.");
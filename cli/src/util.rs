@@ -40,13 +40,49 @@ pub fn check(text: &str) -> (Checker, Grammar) {
   (checker, grammar)
 }
 
-pub fn compile(text: &str) -> Vec<u8> {
+pub struct CompileOptions {
+  pub validate: bool,
+  /// Where the module's `sourceMappingURL` points, if a source map is emitted.
+  pub source_map: Option<String>,
+  /// Optimization level, from 0 to 3.
+  pub opt_level: u8,
+  /// Keep the IR of each function, see [`whistle_compiler::print_ir`].
+  pub emit_ir: bool,
+  pub tree_shake: bool,
+  /// Trap with a message on integer overflow and division by zero.
+  pub checked: bool,
+  /// Exit with an error if there are any warnings.
+  pub deny_warnings: bool,
+}
+
+impl Default for CompileOptions {
+  fn default() -> Self {
+    Self {
+      validate: true,
+      source_map: None,
+      opt_level: 0,
+      emit_ir: false,
+      tree_shake: true,
+      checked: false,
+      deny_warnings: false,
+    }
+  }
+}
+
+pub fn compile(text: &str, options: &CompileOptions) -> (Compiler, Vec<u8>) {
   let (checker, grammar) = check(text);
   let mut compiler = Compiler::new(checker);
+  compiler.validate = options.validate;
+  compiler.opt_level = options.opt_level;
+  compiler.emit_ir = options.emit_ir;
+  compiler.module.source_map_url = options.source_map.clone();
+  compiler.module.tree_shake = options.tree_shake;
+  compiler.checked = options.checked;
   let res = compile_all(&mut compiler, grammar);
   handle_errors(&mut compiler.handler);
+  handle_warnings(&mut compiler.handler, options.deny_warnings);
 
-  res
+  (compiler, res)
 }
 
 pub fn handle_errors(handler: &mut DiagnosticHandler) {
@@ -55,4 +91,13 @@ pub fn handle_errors(handler: &mut DiagnosticHandler) {
     println!("{:#?}", handler.errors);
     std::process::exit(1);
   };
-}
\ No newline at end of file
+}
+
+pub fn handle_warnings(handler: &mut DiagnosticHandler, deny: bool) {
+  for warning in &handler.warnings {
+    println!("{}", warning);
+  }
+  if deny && !handler.warnings.is_empty() {
+    std::process::exit(1);
+  }
+}
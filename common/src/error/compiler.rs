@@ -9,20 +9,88 @@ pub enum CompilerErrorKind {
   ScopeNotGlobal,
   ScopeNotFunction,
   ScopeNotInFunction,
+  ScopeNotInLoop,
   SymbolRedifinition,
   SymbolUndefined,
+  UnknownBuiltin,
 
+  ArraySizeMismatch {
+    expected: usize,
+    found: usize,
+  },
+  ConstDivisionByZero,
+  ConstOverflow,
+  DuplicateArgument {
+    ident: String,
+  },
   ExpectedBooleanExpr,
+  ExpectedConstExpr,
   ImmutableAssign,
-  MissingParameters,
+  IncompatibleTry {
+    types: Type,
+  },
+  InfiniteType {
+    types: Type,
+  },
+  InvalidCast {
+    from: Type,
+    to: Type,
+  },
+  InvalidModule {
+    message: String,
+    function: Option<String>,
+  },
+  InvalidStartFunction,
+  LiteralOutOfRange {
+    types: Type,
+  },
+  MissingParameters {
+    idents: Vec<String>,
+  },
+  MissingReturn,
+  MissingSelf {
+    ident: String,
+  },
+  MissingTipValue {
+    ident: String,
+  },
   MissingCallSignature,
+  MissingMethod {
+    ident: String,
+  },
   MissingProperty,
   NoImplicitAny,
   NoProperties,
   Unassignable,
+  UncalledIntrinsic {
+    ident: String,
+  },
+  UnsatisfiedBound {
+    types: Type,
+    interface: String,
+  },
+  UnstorableType {
+    types: Type,
+  },
+  UnexpectedArgument,
+  UnknownInterface {
+    ident: String,
+  },
+  UnknownMethod {
+    ident: String,
+  },
   UnknownOperator,
+  UnknownParameter {
+    ident: String,
+  },
+  MalformedInstruction {
+    message: String,
+  },
 
-  TypeMismatch { type1: Type, type2: Type },
+  TypeMismatch {
+    type1: Type,
+    type2: Type,
+  },
 
   Unimplemented,
 }
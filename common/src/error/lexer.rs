@@ -33,6 +33,7 @@ pub enum LexerErrorKind {
   UnexpectedEof,
   NoMatch,
   CouldNotParseFloat,
+  UnknownStdModule,
   Eof,
 }
 
@@ -5,6 +5,9 @@ pub use parser::*;
 mod compiler;
 pub use compiler::*;
 
+use crate::Span;
+use std::fmt;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
   LexerError(LexerError),
@@ -13,7 +16,86 @@ pub enum Error {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Warning {}
+pub enum WarningKind {
+  UnknownTip(String),
+  UnknownLint(String),
+  UnreachableCode,
+  UnusedVariable(String),
+  UnusedParameter(String),
+  UnusedImport(String),
+  UnusedFunction(String),
+  ShadowedBinding(String),
+  DeadStore(String),
+  Deprecated { ident: String, note: Option<String> },
+}
+
+/// The names `#(allow)` accepts, one per kind of warning.
+pub const LINTS: &[&str] = &[
+  "unknown_tip",
+  "unknown_lint",
+  "unreachable_code",
+  "unused_variable",
+  "unused_parameter",
+  "unused_import",
+  "unused_function",
+  "shadowed_binding",
+  "dead_store",
+  "deprecated",
+];
+
+impl WarningKind {
+  pub fn lint(&self) -> &'static str {
+    match self {
+      WarningKind::UnknownTip(_) => "unknown_tip",
+      WarningKind::UnknownLint(_) => "unknown_lint",
+      WarningKind::UnreachableCode => "unreachable_code",
+      WarningKind::UnusedVariable(_) => "unused_variable",
+      WarningKind::UnusedParameter(_) => "unused_parameter",
+      WarningKind::UnusedImport(_) => "unused_import",
+      WarningKind::UnusedFunction(_) => "unused_function",
+      WarningKind::ShadowedBinding(_) => "shadowed_binding",
+      WarningKind::DeadStore(_) => "dead_store",
+      WarningKind::Deprecated { .. } => "deprecated",
+    }
+  }
+
+  pub fn message(&self) -> String {
+    match self {
+      WarningKind::UnknownTip(ident) => format!("unknown tip `{}`", ident),
+      WarningKind::UnknownLint(lint) => format!("unknown lint `{}`", lint),
+      WarningKind::UnreachableCode => "unreachable code".to_string(),
+      WarningKind::UnusedVariable(ident) => format!("unused variable `{}`", ident),
+      WarningKind::UnusedParameter(ident) => format!("unused parameter `{}`", ident),
+      WarningKind::UnusedImport(path) => format!("unused import `{}`", path),
+      WarningKind::UnusedFunction(ident) => format!("unused function `{}`", ident),
+      WarningKind::ShadowedBinding(ident) => format!("`{}` shadows an earlier binding", ident),
+      WarningKind::DeadStore(ident) => format!("value assigned to `{}` is never read", ident),
+      WarningKind::Deprecated { ident, note } => match note {
+        Some(note) => format!("`{}` is deprecated: {}", ident, note),
+        None => format!("`{}` is deprecated", ident),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+  pub kind: WarningKind,
+  pub span: Span,
+}
+
+impl fmt::Display for Warning {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "warning[{}]: {} at {}..{}",
+      self.kind.lint(),
+      self.kind.message(),
+      self.span.start,
+      self.span.end
+    )
+  }
+}
 
 #[derive(Debug, Clone)]
 pub struct DiagnosticHandler {
@@ -28,4 +110,8 @@ impl DiagnosticHandler {
       warnings: Vec::new(),
     }
   }
+
+  pub fn warn(&mut self, kind: WarningKind, span: Span) {
+    self.warnings.push(Warning { kind, span })
+  }
 }
@@ -37,6 +37,7 @@ pub enum ParserErrorKind {
   ExpectedPrimaryExpression,
   ExpectedProgramStmt,
   ExpectedExpression,
+  ExpectedNamedArgument,
   ExpectedOperand,
   ExpectedKeyword(Keyword),
   ExpectedToken(Token),
@@ -7,6 +7,7 @@ pub enum Keyword {
   From,
   Export,
   Extern,
+  Builtin,
   Inline,
   Fn,
   Return,
@@ -25,6 +26,9 @@ pub enum Keyword {
   Match,
   Type,
   Struct,
+  Interface,
+  Impl,
+  Err,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -54,6 +58,7 @@ impl TryFrom<&str> for Keyword {
       "from" => Ok(Keyword::From),
       "export" => Ok(Keyword::Export),
       "extern" => Ok(Keyword::Extern),
+      "builtin" => Ok(Keyword::Builtin),
       "inline" => Ok(Keyword::Inline),
       "fn" => Ok(Keyword::Fn),
       "return" => Ok(Keyword::Return),
@@ -84,6 +89,9 @@ impl TryFrom<&str> for Keyword {
       "match" => Ok(Keyword::Match),
       "type" => Ok(Keyword::Type),
       "struct" => Ok(Keyword::Struct),
+      "interface" => Ok(Keyword::Interface),
+      "impl" => Ok(Keyword::Impl),
+      "err" => Ok(Keyword::Err),
 
       _ => Err(()),
     }
@@ -98,6 +106,7 @@ impl From<Keyword> for &str {
       Keyword::From => "from",
       Keyword::Export => "export",
       Keyword::Extern => "extern",
+      Keyword::Builtin => "builtin",
       Keyword::Inline => "inline",
       Keyword::Fn => "fn",
       Keyword::Return => "return",
@@ -128,6 +137,9 @@ impl From<Keyword> for &str {
       Keyword::Match => "match",
       Keyword::Type => "type",
       Keyword::Struct => "struct",
+      Keyword::Interface => "interface",
+      Keyword::Impl => "impl",
+      Keyword::Err => "err",
     }
   }
 }
@@ -24,6 +24,23 @@ pub struct Span {
 
 pub const DUMMY: Span = Span { start: 0, end: 0 };
 
+/// A file of the program. The spans of its tokens start at `offset`, after
+/// the files processed before it, so a span identifies the file it is in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+  /// The import path, empty for the file being compiled.
+  pub name: String,
+  pub text: String,
+  pub offset: usize,
+}
+
+impl Source {
+  /// The index in `sources` of the file containing `idx`.
+  pub fn find(sources: &[Source], idx: usize) -> Option<usize> {
+    sources.iter().rposition(|source| source.offset <= idx)
+  }
+}
+
 impl From<usize> for Span {
   fn from(index: usize) -> Self {
     Span {
@@ -45,6 +45,7 @@ pub enum Operator {
 
   Assign,
   Pipe,
+  Coalesce,
 }
 
 impl TryFrom<&str> for Operator {
@@ -92,6 +93,7 @@ impl TryFrom<&str> for Operator {
 
       "=" => Ok(Operator::Assign),
       "|>" => Ok(Operator::Pipe),
+      "??" => Ok(Operator::Coalesce),
 
       _ => Err(()),
     }
@@ -102,6 +104,7 @@ impl Operator {
   pub fn operators() -> Vec<String> {
     let mut ops = vec![
       String::from("|>"),
+      String::from("??"),
       String::from("&&="),
       String::from("**="),
       String::from("<<="),
@@ -183,6 +186,7 @@ impl Operator {
         | Operator::GreaterThan
         | Operator::Assign
         | Operator::Pipe
+        | Operator::Coalesce
     )
   }
 
@@ -223,6 +227,7 @@ impl Operator {
 
       Operator::LogAnd => 10,
       Operator::LogOr => 11,
+      Operator::Coalesce => 11,
 
       Operator::Cond => 12,
 
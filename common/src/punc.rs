@@ -15,6 +15,7 @@ pub enum Punc {
   RightParen,
   LeftAngleBracket,
   RightAngleBracket,
+  QuestionMark,
 }
 
 impl TryFrom<char> for Punc {
@@ -34,6 +35,7 @@ impl TryFrom<char> for Punc {
       '<' => Ok(Punc::LeftAngleBracket),
       '>' => Ok(Punc::RightAngleBracket),
       '@' => Ok(Punc::Snabel),
+      '?' => Ok(Punc::QuestionMark),
 
       _ => Err(()),
     }
@@ -1,5 +1,8 @@
+use crate::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tip {
   pub ident: String,
   pub value: String,
+  pub value_span: Span,
 }
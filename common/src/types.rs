@@ -22,6 +22,10 @@ pub enum Type {
     ret_type: Box<Type>,
   },
   Array(Box<Type>),
+  Option(Box<Type>),
+  /// A value of the first type or an error of the second.
+  Result(Box<Type>, Box<Type>),
+  Tuple(Vec<Type>),
   Default,
   Error,
 }
@@ -0,0 +1,133 @@
+use crate::Inst;
+use std::collections::HashMap;
+use whistle_ast::Primitive;
+use whistle_ast::Type;
+use whistle_common::TypedIdent;
+
+#[derive(Debug, Clone)]
+pub enum IntrinsicKind {
+  /// A function imported from the host.
+  Import { namespace: String, ident: String },
+  /// Instructions emitted in place of a call, after the arguments are pushed.
+  Inline(Vec<Inst>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Intrinsic {
+  pub params: Vec<TypedIdent>,
+  pub ret_type: Type,
+  pub kind: IntrinsicKind,
+}
+
+impl Intrinsic {
+  pub fn types(&self) -> Type {
+    Type::Function {
+      params: self.params.clone(),
+      ret_type: Box::new(self.ret_type.clone()),
+    }
+  }
+}
+
+/// Intrinsics available through `builtin @module { ... }`.
+#[derive(Debug, Clone, Default)]
+pub struct Builtins {
+  modules: HashMap<String, HashMap<String, Intrinsic>>,
+}
+
+impl Builtins {
+  pub fn new() -> Self {
+    Self {
+      modules: HashMap::new(),
+    }
+  }
+
+  pub fn builtin() -> Self {
+    let mut builtins = Builtins::new();
+
+    builtins.register(
+      "core",
+      "memory_size",
+      Intrinsic {
+        params: vec![],
+        ret_type: Type::Primitive(Primitive::I32),
+        kind: IntrinsicKind::Inline(vec![Inst::MemorySize]),
+      },
+    );
+    builtins.register(
+      "core",
+      "memory_grow",
+      Intrinsic {
+        params: vec![param("pages", Primitive::I32)],
+        ret_type: Type::Primitive(Primitive::I32),
+        kind: IntrinsicKind::Inline(vec![Inst::MemoryGrow]),
+      },
+    );
+    builtins.register(
+      "core",
+      "unreachable",
+      Intrinsic {
+        params: vec![],
+        ret_type: Type::Primitive(Primitive::None),
+        kind: IntrinsicKind::Inline(vec![Inst::Unreachable]),
+      },
+    );
+    builtins.register(
+      "core",
+      "proc_exit",
+      Intrinsic {
+        params: vec![param("code", Primitive::I32)],
+        ret_type: Type::Primitive(Primitive::None),
+        kind: IntrinsicKind::Import {
+          namespace: String::from("wasi_snapshot_preview1"),
+          ident: String::from("proc_exit"),
+        },
+      },
+    );
+
+    builtins.register(
+      "sys",
+      "printInt",
+      Intrinsic {
+        params: vec![param("val", Primitive::I32)],
+        ret_type: Type::Primitive(Primitive::None),
+        kind: IntrinsicKind::Import {
+          namespace: String::from("sys"),
+          ident: String::from("printInt"),
+        },
+      },
+    );
+    builtins.register(
+      "sys",
+      "printFloat",
+      Intrinsic {
+        params: vec![param("val", Primitive::F64)],
+        ret_type: Type::Primitive(Primitive::None),
+        kind: IntrinsicKind::Import {
+          namespace: String::from("sys"),
+          ident: String::from("printFloat"),
+        },
+      },
+    );
+
+    builtins
+  }
+
+  pub fn register(&mut self, module: &str, ident: &str, intrinsic: Intrinsic) {
+    self
+      .modules
+      .entry(module.to_string())
+      .or_default()
+      .insert(ident.to_string(), intrinsic);
+  }
+
+  pub fn get(&self, module: &str, ident: &str) -> Option<&Intrinsic> {
+    self.modules.get(module)?.get(ident)
+  }
+}
+
+fn param(ident: &str, prim: Primitive) -> TypedIdent {
+  TypedIdent {
+    ident: ident.to_string(),
+    type_ident: Type::Primitive(prim),
+  }
+}
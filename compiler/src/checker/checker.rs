@@ -1,26 +1,81 @@
+use crate::Builtins;
+use crate::GenericCall;
+use crate::GenericFn;
+use crate::Inst;
+use crate::MethodCall;
 use crate::ScopeContainer;
+use crate::TipHandler;
+use crate::TipHandlers;
 use whistle_common::CompilerErrorKind;
 use whistle_common::CompilerHandler;
 use whistle_common::DiagnosticHandler;
+use whistle_common::Source;
 use whistle_parser::Parser;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use whistle_ast::Expr;
 use whistle_ast::Operator;
 use whistle_ast::Primitive;
 use whistle_ast::Type;
 use whistle_common::Span;
+use whistle_common::TypedIdent;
+use whistle_common::DUMMY;
 
 #[derive(Debug, Clone)]
 pub struct Constraint {
   pub type1: Type,
   pub type2: Type,
   pub span: Option<Span>,
+  /// Whether `type1` is a value stored in a `type2`, which wraps it when
+  /// `type2` is an option or result and `type1` is not.
+  pub wrap: bool,
 }
 
 pub struct Checker {
   pub scope: ScopeContainer,
   pub substitutions: Vec<Type>,
+  /// Where each type variable stands for the type of something in the source.
+  pub var_spans: Vec<Option<Span>>,
   pub constraints: Vec<Constraint>,
+  /// The operand and target types of every `as` cast, validated once the
+  /// operand types are known.
+  pub casts: Vec<(Type, Type, Span)>,
+  /// The type of every named value, which must fit in a local or global,
+  /// validated once types are known.
+  pub bindings: Vec<(Type, Span)>,
+  /// The default parameter values of each function, by name.
+  pub defaults: HashMap<String, Vec<Option<Expr>>>,
+  /// The declared return type of the function being checked.
+  pub ret_type: Option<Type>,
+  /// How many loops enclose the statement being checked.
+  pub loops: usize,
+  /// The properties of each struct, by name.
+  pub structs: HashMap<String, Vec<TypedIdent>>,
+  /// The method signatures of each interface, in which `Self` is generic.
+  pub interfaces: HashMap<String, Vec<TypedIdent>>,
+  /// Each type and an interface it implements.
+  pub impls: Vec<(Type, String)>,
+  /// The generic functions, by name.
+  pub generics: HashMap<String, GenericFn>,
+  /// The generic function being checked.
+  pub generic_fn: Option<String>,
+  pub methods: Vec<MethodCall>,
+  pub generic_calls: Vec<GenericCall>,
+  /// The types and function index of each instance of a generic function.
+  pub instances: HashMap<String, Vec<(Vec<Type>, u32)>>,
   pub handler: DiagnosticHandler,
+  /// The files of the program, see [`whistle_common::Source`].
+  pub sources: Vec<Source>,
+  pub tips: TipHandlers,
+  pub builtins: Builtins,
+  /// The instructions of each inline intrinsic, by the index of its symbol.
+  pub intrinsics: Vec<Vec<Inst>>,
+  /// The function whose tips are being checked.
+  pub decl: Option<String>,
+  /// The functions marked `#(deprecated)` and the note given, by name.
+  pub deprecated: HashMap<String, Option<String>>,
 }
 
 impl Checker {
@@ -28,73 +83,266 @@ impl Checker {
     Self {
       scope: ScopeContainer::new(),
       substitutions: Vec::new(),
+      var_spans: Vec::new(),
       constraints: Vec::new(),
+      casts: Vec::new(),
+      bindings: Vec::new(),
+      defaults: HashMap::new(),
+      ret_type: None,
+      loops: 0,
+      structs: HashMap::new(),
+      interfaces: HashMap::new(),
+      impls: Vec::new(),
+      generics: HashMap::new(),
+      generic_fn: None,
+      methods: Vec::new(),
+      generic_calls: Vec::new(),
+      instances: HashMap::new(),
       handler: parser.handler,
+      sources: parser.sources,
+      tips: TipHandlers::builtin(),
+      builtins: Builtins::builtin(),
+      intrinsics: Vec::new(),
+      decl: None,
+      deprecated: HashMap::new(),
+    }
+  }
+
+  pub fn register_tip<T: TipHandler + 'static>(&mut self, ident: &str, handler: T) {
+    self.tips.register(ident, handler)
+  }
+
+  /// The properties of `types`, if it is a struct or names one.
+  pub fn props(&self, types: Type) -> Option<Vec<TypedIdent>> {
+    match self.substitute(types) {
+      Type::Struct(props) => Some(props),
+      Type::Ident(ident) => self.structs.get(&ident).cloned(),
+      _ => None,
     }
   }
 
   pub fn constraint(&mut self, type1: Type, type2: Type, span: Option<Span>) {
-    self.constraints.push(Constraint { type1, type2, span })
+    self.constraints.push(Constraint {
+      type1,
+      type2,
+      span,
+      wrap: false,
+    })
+  }
+
+  /// Constrains a value of type `value` to be stored in a `slot`.
+  pub fn flow(&mut self, value: Type, slot: Type, span: Option<Span>) {
+    self.constraints.push(Constraint {
+      type1: value,
+      type2: slot,
+      span,
+      wrap: true,
+    })
   }
 
   pub fn unify(&mut self, constraint: Constraint) {
-    let Constraint { type1, type2, .. } = constraint;
-    // println!("Constraint {:?}, {:?}", type1, type2);
-    let base1 = self.base_type(type1);
-    let base2 = self.base_type(type2);
-    // println!("Base {:?}, {:?}", base1, base2);
-    if let Type::Var(i) = base1 {
-      match (self.substitutions[i].clone(), base2.clone()) {
-        (Type::Array(arr1), Type::Array(arr2)) => {
-          if let Type::Var(j) = *arr1 {
-            self.unify_base(j, *arr2, constraint.span)
-          }
+    let Constraint {
+      type1,
+      type2,
+      span,
+      wrap,
+    } = constraint;
+    let type2 = match (
+      wrap,
+      self.substitute(type2.clone()),
+      self.substitute(type1.clone()),
+    ) {
+      (true, Type::Option(inner), value) if !matches!(value, Type::Option(_)) => *inner,
+      (true, Type::Result(ok, _), value) if !matches!(value, Type::Result(..)) => *ok,
+      _ => type2,
+    };
+    if let Err(err) = self.unify_types(type1, type2) {
+      self.handler.throw(err, span.unwrap_or(DUMMY))
+    }
+  }
+
+  /// Unifies two types, refining type variables to the more specific of the
+  /// two. Returns the unified type, or the innermost pair of types that differ.
+  pub fn unify_types(&mut self, type1: Type, type2: Type) -> Result<Type, CompilerErrorKind> {
+    match (self.resolve(type1), self.resolve(type2)) {
+      (Type::Var(i), Type::Var(j)) if i == j => Ok(Type::Var(i)),
+      (Type::Var(i), Type::Var(j)) => {
+        let bound = match (self.bound(i), self.bound(j)) {
+          (Some(bound1), Some(bound2)) => Some(self.unify_types(bound1, bound2)?),
+          (bound1, bound2) => bound1.or(bound2),
+        };
+        self.substitutions[i] = Type::Var(j);
+        if let Some(bound) = bound {
+          self.bind(j, bound)?;
         }
-        _ => self.unify_base(i, base2, constraint.span),
+        Ok(Type::Var(j))
       }
-    } else if Checker::is_subtype(base2.clone(), base1.clone()) == None {
-      let err = CompilerErrorKind::TypeMismatch {
-        type1: self.substitute(base1),
-        type2: self.substitute(base2),
-      };
-      self.handler.throw(err, constraint.span.unwrap())
+      (Type::Var(i), types) | (types, Type::Var(i)) => {
+        let types = match self.bound(i) {
+          Some(bound) => self.unify_types(bound, types)?,
+          None => types,
+        };
+        self.bind(i, types)?;
+        Ok(Type::Var(i))
+      }
+      (type1, type2) => self.unify_structural(type1, type2),
     }
-    // println!("{:?}\n", self.substitutions);
   }
 
-  pub fn unify_base(&mut self, i: usize, base2: Type, span: Option<Span>) {
-    if let Type::Var(j) = base2 {
-      match Checker::is_subtype(self.substitutions[j].clone(), self.substitutions[i].clone()) {
-        Some(is_subtype) => {
-          if is_subtype {
-            self.substitutions[i] = base2
-          } else {
-            self.substitutions[j] = self.substitutions[i].clone()
-          }
-        }
-        None => {
-          let err = CompilerErrorKind::TypeMismatch {
-            type1: self.substitute(self.substitutions[j].clone()),
-            type2: self.substitute(self.substitutions[i].clone()),
-          };
-          self.handler.throw(err, span.unwrap())
-        }
+  fn unify_structural(&mut self, type1: Type, type2: Type) -> Result<Type, CompilerErrorKind> {
+    let mismatch =
+      |checker: &Checker, type1: &Type, type2: &Type| CompilerErrorKind::TypeMismatch {
+        type1: checker.substitute(type1.clone()),
+        type2: checker.substitute(type2.clone()),
+      };
+    match (&type1, &type2) {
+      (Type::Error, _) | (Type::Default, _) => Ok(type2),
+      (_, Type::Error) | (_, Type::Default) => Ok(type1),
+      (Type::Primitive(prim1), Type::Primitive(prim2)) => match Checker::meet(prim1, prim2) {
+        Some(prim) => Ok(Type::Primitive(prim)),
+        None => Err(mismatch(self, &type1, &type2)),
+      },
+      (Type::Array(elem1), Type::Array(elem2)) => Ok(Type::Array(Box::new(
+        self.unify_types(*elem1.clone(), *elem2.clone())?,
+      ))),
+      (Type::Option(inner1), Type::Option(inner2)) => Ok(Type::Option(Box::new(
+        self.unify_types(*inner1.clone(), *inner2.clone())?,
+      ))),
+      (Type::Result(ok1, err1), Type::Result(ok2, err2)) => {
+        let ok = self.unify_types(*ok1.clone(), *ok2.clone())?;
+        let err = self.unify_types(*err1.clone(), *err2.clone())?;
+        Ok(Type::Result(Box::new(ok), Box::new(err)))
       }
-    } else {
-      match Checker::is_subtype(base2.clone(), self.substitutions[i].clone()) {
-        Some(is_subtype) => {
-          if is_subtype {
-            self.substitutions[i] = base2
-          }
+      (Type::Tuple(types1), Type::Tuple(types2)) if types1.len() == types2.len() => {
+        let types = self.unify_all(types1.clone(), types2.clone())?;
+        Ok(Type::Tuple(types))
+      }
+      (
+        Type::Function {
+          params: params1,
+          ret_type: ret1,
+        },
+        Type::Function {
+          params: params2,
+          ret_type: ret2,
+        },
+      ) if params1.len() == params2.len() => {
+        let mut params = Vec::new();
+        for (param1, param2) in params1.iter().zip(params2) {
+          params.push(TypedIdent {
+            ident: param1.ident.clone(),
+            type_ident: self.unify_types(param1.type_ident.clone(), param2.type_ident.clone())?,
+          });
         }
-        None => {
-          let err = CompilerErrorKind::TypeMismatch {
-            type1: self.substitute(self.substitutions[i].clone()),
-            type2: self.substitute(base2.clone()),
+        let ret_type = Box::new(self.unify_types(*ret1.clone(), *ret2.clone())?);
+        Ok(Type::Function { params, ret_type })
+      }
+      (Type::Struct(props1), Type::Struct(props2)) if props1.len() == props2.len() => {
+        let mut props = Vec::new();
+        for prop1 in props1 {
+          let prop2 = match props2.iter().find(|prop2| prop2.ident == prop1.ident) {
+            Some(prop2) => prop2,
+            None => return Err(mismatch(self, &type1, &type2)),
           };
-          self.handler.throw(err, span.unwrap())
+          props.push(TypedIdent {
+            ident: prop1.ident.clone(),
+            type_ident: self.unify_types(prop1.type_ident.clone(), prop2.type_ident.clone())?,
+          });
         }
+        Ok(Type::Struct(props))
       }
+      (
+        Type::IdentType {
+          ident: ident1,
+          prim: prim1,
+        },
+        Type::IdentType {
+          ident: ident2,
+          prim: prim2,
+        },
+      ) if ident1 == ident2 && prim1.len() == prim2.len() => {
+        let prim = self.unify_all(prim1.clone(), prim2.clone())?;
+        Ok(Type::IdentType {
+          ident: ident1.clone(),
+          prim,
+        })
+      }
+      (Type::Ident(ident1), Type::Ident(ident2))
+      | (Type::Generic(ident1), Type::Generic(ident2))
+        if ident1 == ident2 =>
+      {
+        Ok(type1)
+      }
+      _ => Err(mismatch(self, &type1, &type2)),
+    }
+  }
+
+  fn unify_all(
+    &mut self,
+    types1: Vec<Type>,
+    types2: Vec<Type>,
+  ) -> Result<Vec<Type>, CompilerErrorKind> {
+    types1
+      .into_iter()
+      .zip(types2)
+      .map(|(type1, type2)| self.unify_types(type1, type2))
+      .collect()
+  }
+
+  /// Sets the bound of the unresolved variable `i`, unless it would contain
+  /// itself.
+  fn bind(&mut self, i: usize, types: Type) -> Result<(), CompilerErrorKind> {
+    if self.occurs(i, &types) {
+      return Err(CompilerErrorKind::InfiniteType {
+        types: self.substitute(types),
+      });
+    }
+    self.substitutions[i] = types;
+    Ok(())
+  }
+
+  fn occurs(&self, i: usize, types: &Type) -> bool {
+    match types {
+      Type::Var(j) => {
+        let j = self.root(*j);
+        j == i || self.bound(j).is_some_and(|bound| self.occurs(i, &bound))
+      }
+      Type::Array(elem) | Type::Option(elem) => self.occurs(i, elem),
+      Type::Result(ok, err) => self.occurs(i, ok) || self.occurs(i, err),
+      Type::Tuple(types) | Type::IdentType { prim: types, .. } => {
+        types.iter().any(|types| self.occurs(i, types))
+      }
+      Type::Function { params, ret_type } => {
+        params.iter().any(|param| self.occurs(i, &param.type_ident)) || self.occurs(i, ret_type)
+      }
+      Type::Struct(props) => props.iter().any(|prop| self.occurs(i, &prop.type_ident)),
+      _ => false,
+    }
+  }
+
+  /// The more specific of two primitives, if one is a subtype of the other.
+  /// `number` is above `int` and `float`, which are above the sized types.
+  pub fn meet(prim1: &Primitive, prim2: &Primitive) -> Option<Primitive> {
+    let within = |prim: &Primitive, bound: &Primitive| {
+      prim == bound
+        || match bound {
+          Primitive::Number => Checker::meet(prim, &Primitive::Int)
+            .or_else(|| Checker::meet(prim, &Primitive::Float))
+            .is_some(),
+          Primitive::Int => matches!(
+            prim,
+            Primitive::I32 | Primitive::I64 | Primitive::U32 | Primitive::U64
+          ),
+          Primitive::Float => matches!(prim, Primitive::F32 | Primitive::F64),
+          _ => false,
+        }
+    };
+    if within(prim1, prim2) {
+      Some(prim1.clone())
+    } else if within(prim2, prim1) {
+      Some(prim2.clone())
+    } else {
+      None
     }
   }
 
@@ -103,113 +351,154 @@ impl Checker {
       Type::Primitive(Primitive::Int) => Type::Primitive(Primitive::I32),
       Type::Primitive(Primitive::Float) => Type::Primitive(Primitive::F64),
       Type::Primitive(Primitive::Number) => Type::Primitive(Primitive::I32),
-      Type::Var(_) => Type::Error,
+      Type::Var(_) | Type::Default => Type::Error,
       Type::Array(arr) => Type::Array(Box::new(Checker::coerce(*arr))),
+      Type::Option(inner) => Type::Option(Box::new(Checker::coerce(*inner))),
+      Type::Result(ok, err) => Type::Result(
+        Box::new(Checker::coerce(*ok)),
+        Box::new(Checker::coerce(*err)),
+      ),
+      Type::Tuple(types) => Type::Tuple(types.into_iter().map(Checker::coerce).collect()),
+      Type::IdentType { ident, prim } => Type::IdentType {
+        ident,
+        prim: prim.into_iter().map(Checker::coerce).collect(),
+      },
+      Type::Function { params, ret_type } => Type::Function {
+        params: params.into_iter().map(Checker::coerce_typed).collect(),
+        ret_type: Box::new(Checker::coerce(*ret_type)),
+      },
+      Type::Struct(props) => Type::Struct(props.into_iter().map(Checker::coerce_typed).collect()),
       _ => types,
     }
   }
 
+  fn coerce_typed(typed: TypedIdent) -> TypedIdent {
+    TypedIdent {
+      ident: typed.ident,
+      type_ident: Checker::coerce(typed.type_ident),
+    }
+  }
+
+  /// Whether inference left part of `types` open, once substituted.
+  pub fn is_ambiguous(types: &Type) -> bool {
+    match types {
+      Type::Var(_) | Type::Default => true,
+      Type::Array(elem) | Type::Option(elem) => Checker::is_ambiguous(elem),
+      Type::Result(ok, err) => Checker::is_ambiguous(ok) || Checker::is_ambiguous(err),
+      Type::Tuple(types) | Type::IdentType { prim: types, .. } => {
+        types.iter().any(Checker::is_ambiguous)
+      }
+      Type::Function { params, ret_type } => {
+        params
+          .iter()
+          .any(|param| Checker::is_ambiguous(&param.type_ident))
+          || Checker::is_ambiguous(ret_type)
+      }
+      Type::Struct(props) => props
+        .iter()
+        .any(|prop| Checker::is_ambiguous(&prop.type_ident)),
+      _ => false,
+    }
+  }
+
+  /// Settles every variable on its inferred type, defaulting `int` to `i32` and
+  /// `float` to `f64`. Variables with a span that are still ambiguous are
+  /// reported, once for each set of unified variables, and become errors.
+  pub fn default_types(&mut self) {
+    let types: Vec<Type> = (0..self.substitutions.len())
+      .map(|i| self.substitute(Type::Var(i)))
+      .collect();
+    let mut reported = HashSet::new();
+    for (i, types) in types.iter().enumerate() {
+      if let Some(span) = self.var_spans[i] {
+        if Checker::is_ambiguous(types) && reported.insert(self.root(i)) {
+          self.handler.throw(CompilerErrorKind::NoImplicitAny, span);
+        }
+      }
+    }
+    self.substitutions = types.into_iter().map(Checker::coerce).collect();
+  }
+
   pub fn new_type_val(&mut self) -> Type {
     let res = Type::Var(self.substitutions.len());
     self.substitutions.push(res.clone());
+    self.var_spans.push(None);
     res
   }
 
-  pub fn base_type(&self, types: Type) -> Type {
-    if let Type::Var(i) = types {
-      if let Type::Var(j) = self.substitutions[i] {
-        if i != j {
-          return self.base_type(self.substitutions[i].clone());
-        }
-      }
+  /// A new variable for the type of the source at `span`.
+  pub fn new_type_val_at(&mut self, span: Span) -> Type {
+    let res = self.new_type_val();
+    if let Some(last) = self.var_spans.last_mut() {
+      *last = Some(span);
+    }
+    res
+  }
+
+  /// The variable at the end of the chain of links starting at `i`.
+  pub fn root(&self, i: usize) -> usize {
+    match self.substitutions[i] {
+      Type::Var(j) if j != i => self.root(j),
+      _ => i,
+    }
+  }
+
+  /// The type the resolved variable `i` is known to be a subtype of, if any.
+  fn bound(&self, i: usize) -> Option<Type> {
+    match &self.substitutions[i] {
+      Type::Var(_) => None,
+      types => Some(types.clone()),
+    }
+  }
+
+  fn resolve(&self, types: Type) -> Type {
+    match types {
+      Type::Var(i) => Type::Var(self.root(i)),
+      _ => types,
     }
-    types
   }
 
+  /// `types` with every variable replaced by what is known about it.
   pub fn substitute(&self, types: Type) -> Type {
-    if let Type::Var(i) = types {
-      if Type::Var(i) == self.substitutions[i] {
-        return types;
-      }
-      return self.substitute(self.substitutions[i].clone());
-    } else if let Type::Array(arr) = types {
-      return Type::Array(Box::new(self.substitute(*arr)));
-    }
-    types
-  }
-
-  // pub fn substitute(&self, types: Type) -> Type {
-  //   if let Type::Var(i) = types {
-  //     if let Type::Var(j) = self.substitutions[i] {
-  //       return self.substitutions[j].clone();
-  //     }
-  //     return self.substitutions[i].clone();
-  //   }
-  //   types
-  // }
-
-  pub fn is_subtype(type1: Type, type2: Type) -> Option<bool> {
-    if let Type::Var(_) = type1 {
-      return Some(true);
-    }
-
-    if type1 == type2 {
-      return Some(true);
-    }
-
-    match type2 {
-      Type::Primitive(prim) => match prim {
-        Primitive::Number => match type1 {
-          Type::Primitive(Primitive::I32)
-          | Type::Primitive(Primitive::I64)
-          | Type::Primitive(Primitive::U32)
-          | Type::Primitive(Primitive::U64)
-          | Type::Primitive(Primitive::F32)
-          | Type::Primitive(Primitive::F64)
-          | Type::Primitive(Primitive::Int)
-          | Type::Primitive(Primitive::Float) => Some(true),
-          Type::Default => Some(false),
-          _ => None,
-        },
-        Primitive::Int => match type1 {
-          Type::Primitive(Primitive::I32)
-          | Type::Primitive(Primitive::I64)
-          | Type::Primitive(Primitive::U32)
-          | Type::Primitive(Primitive::U64) => Some(true),
-          Type::Primitive(Primitive::Number) | Type::Primitive(Primitive::Int) | Type::Default => {
-            Some(false)
-          }
-          _ => None,
-        },
-        Primitive::Float => match type1 {
-          Type::Primitive(Primitive::F32) | Type::Primitive(Primitive::F64) => Some(true),
-          Type::Primitive(Primitive::Number)
-          | Type::Primitive(Primitive::Float)
-          | Type::Default => Some(false),
-          _ => None,
-        },
-        Primitive::I32
-        | Primitive::I64
-        | Primitive::U32
-        | Primitive::U64
-        | Primitive::F32
-        | Primitive::F64
-          if type1 == Type::Primitive(Primitive::Number) =>
-        {
-          Some(false)
-        }
-        Primitive::I32 | Primitive::I64 | Primitive::U32 | Primitive::U64
-          if type1 == Type::Primitive(Primitive::Int) =>
-        {
-          Some(false)
-        }
-        Primitive::F32 | Primitive::F64 if type1 == Type::Primitive(Primitive::Float) => {
-          Some(false)
+    match types {
+      Type::Var(i) => {
+        let i = self.root(i);
+        match self.bound(i) {
+          Some(bound) => self.substitute(bound),
+          None => Type::Var(i),
         }
-        _ => None,
+      }
+      Type::Array(elem) => Type::Array(Box::new(self.substitute(*elem))),
+      Type::Option(inner) => Type::Option(Box::new(self.substitute(*inner))),
+      Type::Result(ok, err) => Type::Result(
+        Box::new(self.substitute(*ok)),
+        Box::new(self.substitute(*err)),
+      ),
+      Type::Tuple(types) => Type::Tuple(types.into_iter().map(|t| self.substitute(t)).collect()),
+      Type::IdentType { ident, prim } => Type::IdentType {
+        ident,
+        prim: prim.into_iter().map(|t| self.substitute(t)).collect(),
       },
-      Type::Var(_) => Some(true),
-      _ => None,
+      Type::Function { params, ret_type } => Type::Function {
+        params: params
+          .into_iter()
+          .map(|param| TypedIdent {
+            ident: param.ident,
+            type_ident: self.substitute(param.type_ident),
+          })
+          .collect(),
+        ret_type: Box::new(self.substitute(*ret_type)),
+      },
+      Type::Struct(props) => Type::Struct(
+        props
+          .into_iter()
+          .map(|prop| TypedIdent {
+            ident: prop.ident,
+            type_ident: self.substitute(prop.type_ident),
+          })
+          .collect(),
+      ),
+      _ => types,
     }
   }
 }
@@ -250,3 +539,393 @@ pub fn unary_to_type_val(op: &Operator) -> Type {
     _ => unreachable!(),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::check;
+  use crate::test_support::errors;
+  use crate::test_support::warnings;
+  use whistle_ast::Primitive;
+  use whistle_ast::Type;
+  use whistle_common::CompilerErrorKind;
+  use whistle_common::TypedIdent;
+  use whistle_common::WarningKind;
+
+  fn function(param: Type, ret_type: Type) -> Type {
+    Type::Function {
+      params: vec![TypedIdent {
+        ident: String::from("a"),
+        type_ident: param,
+      }],
+      ret_type: Box::new(ret_type),
+    }
+  }
+
+  #[test]
+  fn unify_structural() {
+    let (mut checker, _) = check("");
+    let var1 = checker.new_type_val();
+    let var2 = checker.new_type_val();
+    let int = Type::Primitive(Primitive::Int);
+    let i64 = Type::Primitive(Primitive::I64);
+    checker.unify_types(var1.clone(), int).unwrap();
+    checker
+      .unify_types(
+        function(var1.clone(), Type::Array(Box::new(var2.clone()))),
+        function(i64.clone(), Type::Array(Box::new(var1.clone()))),
+      )
+      .unwrap();
+    assert_eq!(checker.substitute(var1), i64);
+    assert_eq!(checker.substitute(var2.clone()), i64);
+
+    let err = checker.unify_types(
+      function(Type::Primitive(Primitive::Bool), var2),
+      function(
+        Type::Primitive(Primitive::Bool),
+        Type::Primitive(Primitive::F32),
+      ),
+    );
+    assert_eq!(
+      err,
+      Err(CompilerErrorKind::TypeMismatch {
+        type1: Type::Primitive(Primitive::I64),
+        type2: Type::Primitive(Primitive::F32)
+      })
+    );
+  }
+
+  #[test]
+  fn occurs_check() {
+    let (mut checker, _) = check("");
+    let var = checker.new_type_val();
+    let err = checker.unify_types(var.clone(), Type::Array(Box::new(var)));
+    assert!(matches!(err, Err(CompilerErrorKind::InfiniteType { .. })));
+  }
+
+  #[test]
+  fn defaults_and_ambiguous_types() {
+    let (checker, _) = check(
+      "fn f() {
+        val a = 1
+        val b = 2.5
+        val xs = []
+        val ys = xs
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![CompilerErrorKind::NoImplicitAny]
+    );
+    assert!(checker
+      .substitutions
+      .contains(&Type::Primitive(Primitive::I32)));
+    assert!(checker
+      .substitutions
+      .contains(&Type::Primitive(Primitive::F64)));
+  }
+
+  #[test]
+  fn call_arguments() {
+    let (checker, _) = check(
+      "fn f(a: i32, b: i32 = 2): i32 {
+        return a + b
+      }
+      fn g() {
+        val a = f(1)
+        val b = f(b: 3, a: 1)
+        val c = f()
+        val d = f(1, 2, 3)
+        val e = f(1, a: 2)
+        val h = f(c: 1, a: 2)
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::MissingParameters {
+          idents: vec![String::from("a")]
+        },
+        CompilerErrorKind::UnexpectedArgument,
+        CompilerErrorKind::DuplicateArgument {
+          ident: String::from("a")
+        },
+        CompilerErrorKind::UnknownParameter {
+          ident: String::from("c")
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn assignment_targets() {
+    let (checker, _) = check(
+      "fn f(): i32 {
+        return 1
+      }
+      fn g() {
+        var xs = [1, 2]
+        xs[0] = 3
+        val ys = [1, 2]
+        ys[1] = 3
+        val k = 1
+        val a = k = 2
+        f() = 3
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::ImmutableAssign,
+        CompilerErrorKind::ImmutableAssign,
+        CompilerErrorKind::Unassignable,
+      ]
+    );
+  }
+
+  #[test]
+  fn options() {
+    let (checker, _) = check(
+      "fn f(x: i32?): i32 {
+        if x != none {
+          return x + 1
+        }
+        val y: i32? = 2
+        val z = y ?? 0
+        val n: i32 = none
+        val w: i32 = x
+        z
+      }",
+    );
+    let i32 = Type::Primitive(Primitive::I32);
+    let errors = errors(&checker.handler);
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+      &errors[0],
+      CompilerErrorKind::TypeMismatch {
+        type1: Type::Option(_),
+        type2,
+      } if *type2 == i32
+    ));
+    assert_eq!(
+      errors[1],
+      CompilerErrorKind::TypeMismatch {
+        type1: i32.clone(),
+        type2: Type::Option(Box::new(i32)),
+      }
+    );
+  }
+
+  #[test]
+  fn results() {
+    let (checker, _) = check(
+      "fn f(x: i32): i32!bool {
+        if true {
+          return err(false)
+        }
+        x
+      }
+      fn g(): Result<i32, bool> {
+        val y = f(1)?
+        f(y)
+      }
+      fn h(): i32!i32 {
+        f(2)?
+      }
+      fn k(): i32 {
+        f(3)?
+      }",
+    );
+    let errors = errors(&checker.handler);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+      errors[0],
+      CompilerErrorKind::IncompatibleTry {
+        types: Type::Primitive(Primitive::I32),
+      }
+    );
+    assert!(matches!(
+      &errors[1],
+      CompilerErrorKind::TypeMismatch { type1, type2 }
+        if *type1 == Type::Primitive(Primitive::Bool) && *type2 == Type::Primitive(Primitive::I32)
+    ));
+  }
+
+  #[test]
+  fn unstorable_bindings() {
+    let (checker, _) = check(
+      "extern \"env\" {
+        fn divmod(a: i32, b: i32): (i32, i32)
+      }
+      fn g(): none {}
+      fn f(): none {
+        val (q, r) = divmod(7, 2)
+        val x = divmod(7, 2)
+        val h = divmod
+        val n = g()
+      }",
+    );
+    let errors = errors(&checker.handler);
+    let pair = Type::Tuple(vec![Type::Primitive(Primitive::I32); 2]);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0], CompilerErrorKind::UnstorableType { types: pair });
+    assert!(matches!(
+      &errors[1],
+      CompilerErrorKind::UnstorableType {
+        types: Type::Function { .. }
+      }
+    ));
+    assert_eq!(
+      errors[2],
+      CompilerErrorKind::UnstorableType {
+        types: Type::Primitive(Primitive::None)
+      }
+    );
+  }
+
+  #[test]
+  fn interfaces() {
+    let (checker, _) = check(
+      "interface Size {
+        fn size(self): i32
+      }
+      impl Size for i32 {
+        fn size(self): i32 {
+          return self
+        }
+      }
+      impl Size for bool {
+        fn len(self): i32 {
+          return 1
+        }
+      }
+      fn total<T: Size>(x: T, y: T): i32 {
+        return x.size() + y.size()
+      }
+      fn f(): i32 {
+        val a = total(1, 2)
+        val b = total(1.5, 2.5)
+        val c = 3
+        return a + b + c.size()
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::UnknownMethod {
+          ident: String::from("len")
+        },
+        CompilerErrorKind::MissingMethod {
+          ident: String::from("size")
+        },
+        CompilerErrorKind::UnsatisfiedBound {
+          types: Type::Primitive(Primitive::F64),
+          interface: String::from("Size"),
+        },
+      ]
+    );
+    assert_eq!(checker.instances["total"].len(), 1);
+  }
+
+  #[test]
+  fn methods() {
+    let (checker, _) = check(
+      "struct Rect {
+        w: i32,
+        h: i32
+      }
+      impl Rect {
+        fn area(self): i32 {
+          return self.w * self.h
+        }
+        fn unit(): i32 {
+          return 1
+        }
+      }
+      fn f(r: Rect): i32 {
+        val a: bool = r.area()
+        return r.perimeter() + r.depth
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::MissingSelf {
+          ident: String::from("unit")
+        },
+        CompilerErrorKind::MissingProperty,
+        CompilerErrorKind::TypeMismatch {
+          type1: Type::Primitive(Primitive::Bool),
+          type2: Type::Primitive(Primitive::I32),
+        },
+        CompilerErrorKind::MissingMethod {
+          ident: String::from("perimeter")
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn declaration_tips() {
+    let (checker, _) = check(
+      "#(import_module)
+      extern \"sys\" {
+        fn report(code: i32): none
+      }
+      #(packed)
+      struct Point {
+        x: i32,
+        y: i32
+      }
+      #(start)
+      fn init(code: i32): none {
+        report(code)
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::MissingTipValue {
+          ident: String::from("import_module")
+        },
+        CompilerErrorKind::InvalidStartFunction,
+      ]
+    );
+    assert!(checker
+      .handler
+      .warnings
+      .iter()
+      .any(|warning| warning.kind == WarningKind::UnknownTip(String::from("packed"))));
+  }
+
+  #[test]
+  fn deprecated_functions() {
+    let (checker, _) = check(
+      "#(deprecated) \"use add\"
+      fn plus(a: i32, b: i32): i32 {
+        return a + b
+      }
+      #(deprecated)
+      fn old(): none {}
+      export fn main(plus2: i32): i32 {
+        // A local of the same name isn't the deprecated function.
+        old()
+        val old = plus(1, plus2)
+        return old
+      }",
+    );
+    assert_eq!(errors(&checker.handler), vec![]);
+    assert_eq!(
+      warnings(&checker.handler),
+      vec![
+        WarningKind::Deprecated {
+          ident: String::from("old"),
+          note: None,
+        },
+        WarningKind::Deprecated {
+          ident: String::from("plus"),
+          note: Some(String::from("use add")),
+        },
+      ]
+    );
+  }
+}
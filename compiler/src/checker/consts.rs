@@ -0,0 +1,768 @@
+use crate::is_none;
+use crate::Checker;
+
+use std::collections::HashMap;
+use whistle_ast::Expr;
+use whistle_ast::IdentType;
+use whistle_ast::IdentTyped;
+use whistle_ast::IdentVal;
+use whistle_ast::Literal;
+use whistle_ast::Operator;
+use whistle_ast::Primary;
+use whistle_ast::Primitive;
+use whistle_ast::ProgramStmt;
+use whistle_ast::Stmt;
+use whistle_ast::Type;
+use whistle_ast::Unary;
+use whistle_common::CompilerError;
+use whistle_common::CompilerErrorKind;
+use whistle_common::CompilerHandler;
+use whistle_common::Span;
+
+/// A value known at compile time, along with the type it was evaluated at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+  Int(i128, Primitive),
+  Float(f64, Primitive),
+  Bool(bool),
+  Char(char),
+}
+
+impl Const {
+  /// A typed literal for the value. Negative integers are stored as their two's
+  /// complement, the same way `compile_literal` reads them back.
+  pub fn to_literal(&self) -> Literal {
+    match self {
+      Const::Int(val, Primitive::I64) => Literal::I64(*val as i64 as usize),
+      Const::Int(val, Primitive::U32) => Literal::U32(*val as usize),
+      Const::Int(val, Primitive::U64) => Literal::U64(*val as usize),
+      Const::Int(val, _) => Literal::I32(*val as i64 as usize),
+      Const::Float(val, Primitive::F32) => Literal::F32(*val),
+      Const::Float(val, _) => Literal::F64(*val),
+      Const::Bool(val) => Literal::Bool(*val),
+      Const::Char(val) => Literal::Char(*val),
+    }
+  }
+
+  fn to_expr(&self, span: Span) -> Expr {
+    Expr::Unary {
+      unary: Unary::Primary {
+        prim: Primary::Literal {
+          lit: self.to_literal(),
+          meta_id: 0,
+          span,
+        },
+        span,
+      },
+      span,
+    }
+  }
+}
+
+/// Constant `val`s by scope. `None` marks a symbol shadowing an outer constant.
+type ConstScopes = Vec<HashMap<String, Option<Const>>>;
+
+/// Folds `val` initializers built from literals and other constants, once types
+/// are known. Uses of a constant are replaced by its value, local constants are
+/// removed, and global initializers are reduced to a literal.
+pub fn fold_consts(checker: &mut Checker, grammar: &mut Vec<ProgramStmt>) {
+  let mut scopes = vec![HashMap::new()];
+  for program in grammar {
+    fold_program(checker, &mut scopes, program);
+  }
+}
+
+fn fold_program(checker: &mut Checker, scopes: &mut ConstScopes, program: &mut ProgramStmt) {
+  match program {
+    ProgramStmt::FunctionDecl { params, stmt, .. } => {
+      scopes.push(HashMap::new());
+      for param in params.iter() {
+        declare(scopes, &param.ident, None);
+      }
+      fold_stmts(checker, scopes, stmt);
+      scopes.pop();
+    }
+    ProgramStmt::ImplDecl { fns, .. } => {
+      for program in fns {
+        fold_program(checker, scopes, program);
+      }
+    }
+    ProgramStmt::ValDecl {
+      ident_typed, val, ..
+    } => fold_global(checker, scopes, ident_typed, val, false),
+    ProgramStmt::VarDecl {
+      ident_typed, val, ..
+    } => fold_global(checker, scopes, ident_typed, val, true),
+    _ => {}
+  }
+}
+
+fn fold_global(
+  checker: &mut Checker,
+  scopes: &mut ConstScopes,
+  ident_typed: &mut IdentTyped,
+  val: &mut Expr,
+  mutable: bool,
+) {
+  fold_expr(scopes, val);
+  fold_array_size(checker, scopes, ident_typed, val);
+  // Globals are initialized with a constant expression. `none` keeps its
+  // literal, which knows the type of the option.
+  if is_none(val) {
+    return declare(scopes, &ident_typed.ident, None);
+  }
+  // `err(expr)` keeps its wrapper, which knows the type of the result.
+  let (val, err) = match val {
+    Expr::Err { expr, .. } => (&mut **expr, true),
+    val => (val, false),
+  };
+  let value = match eval_const(checker, val) {
+    Ok(value) => value,
+    Err(Some(err)) => return checker.handler.throw(err.kind, err.span),
+    Err(None) => {
+      checker
+        .handler
+        .throw(CompilerErrorKind::ExpectedConstExpr, val.span());
+      return declare(scopes, &ident_typed.ident, None);
+    }
+  };
+  *val = value.to_expr(val.span());
+  declare(
+    scopes,
+    &ident_typed.ident,
+    if mutable || err || is_wrapper(ident_typed) {
+      None
+    } else {
+      Some(value)
+    },
+  );
+}
+
+fn fold_stmts(checker: &mut Checker, scopes: &mut ConstScopes, stmts: &mut Vec<Stmt>) {
+  scopes.push(HashMap::new());
+  let mut folded = Vec::new();
+  for (idx, stmt) in stmts.iter_mut().enumerate() {
+    if fold_stmt(checker, scopes, stmt) {
+      folded.push(idx);
+    }
+  }
+  // Every use of a folded `val` was replaced, so it needs no local.
+  for idx in folded.into_iter().rev() {
+    if let Stmt::ValDecl { ident_typed, .. } = &stmts[idx] {
+      if lookup(scopes, &ident_typed.ident).is_some() {
+        stmts.remove(idx);
+      }
+    }
+  }
+  scopes.pop();
+}
+
+/// Folds the constants in `stmt`, returning whether it declared a constant.
+fn fold_stmt(checker: &mut Checker, scopes: &mut ConstScopes, stmt: &mut Stmt) -> bool {
+  match stmt {
+    Stmt::While { cond, do_stmt, .. } => {
+      fold_expr(scopes, cond);
+      fold_stmts(checker, scopes, do_stmt);
+    }
+    Stmt::If {
+      cond,
+      then_stmt,
+      else_stmt,
+      ..
+    } => {
+      fold_expr(scopes, cond);
+      fold_stmts(checker, scopes, then_stmt);
+      if let Some(else_stmt) = else_stmt {
+        fold_stmts(checker, scopes, else_stmt);
+      }
+    }
+    Stmt::ValDecl {
+      ident_typed, val, ..
+    } => {
+      fold_expr(scopes, val);
+      fold_array_size(checker, scopes, ident_typed, val);
+      match eval_const(checker, val) {
+        // An option or result can't be replaced by the value it holds.
+        Ok(_) if is_wrapper(ident_typed) => {}
+        Ok(value) => {
+          declare(scopes, &ident_typed.ident, Some(value));
+          return true;
+        }
+        Err(Some(err)) => checker.handler.throw(err.kind, err.span),
+        Err(None) => {}
+      }
+      declare(scopes, &ident_typed.ident, None);
+    }
+    Stmt::VarDecl {
+      ident_typed, val, ..
+    } => {
+      fold_expr(scopes, val);
+      fold_array_size(checker, scopes, ident_typed, val);
+      declare(scopes, &ident_typed.ident, None);
+    }
+    Stmt::TupleDecl { idents, val, .. } => {
+      fold_expr(scopes, val);
+      for ident in idents.iter() {
+        declare(scopes, &ident.ident, None);
+      }
+    }
+    Stmt::Block { stmts, .. } => fold_stmts(checker, scopes, stmts),
+    Stmt::Return {
+      ret_type: Some(expr),
+      ..
+    } => fold_expr(scopes, expr),
+    Stmt::Expr { expr, .. } => fold_expr(scopes, expr),
+    Stmt::Assign { lhs, rhs, .. } => {
+      fold_target(scopes, lhs);
+      fold_expr(scopes, rhs);
+    }
+    _ => {}
+  }
+  false
+}
+
+/// Evaluates the size of a `T[N]` annotation and pads an array literal
+/// initializer with zeroes up to it.
+fn fold_array_size(
+  checker: &mut Checker,
+  scopes: &mut ConstScopes,
+  ident_typed: &mut IdentTyped,
+  val: &mut Expr,
+) {
+  let size = match &mut ident_typed.type_ident {
+    IdentType::Array {
+      size: Some(size), ..
+    } => size,
+    _ => return,
+  };
+  fold_expr(scopes, size);
+  let len = match eval_const(checker, size) {
+    Ok(Const::Int(len, _)) if len >= 0 => len as usize,
+    Err(Some(err)) => return checker.handler.throw(err.kind, err.span),
+    _ => {
+      return checker
+        .handler
+        .throw(CompilerErrorKind::ExpectedConstExpr, size.span())
+    }
+  };
+
+  if let Expr::Unary {
+    unary:
+      Unary::Primary {
+        prim: Primary::Array {
+          exprs,
+          meta_id,
+          span,
+        },
+        ..
+      },
+    ..
+  } = val
+  {
+    if exprs.len() > len {
+      let err = CompilerErrorKind::ArraySizeMismatch {
+        expected: len,
+        found: exprs.len(),
+      };
+      return checker.handler.throw(err, *span);
+    }
+    let zero = match &checker.substitutions[*meta_id] {
+      Type::Array(types) => zero_value(types),
+      _ => Const::Int(0, Primitive::I32),
+    };
+    exprs.resize(len, zero.to_expr(*span));
+  }
+}
+
+fn zero_value(types: &Type) -> Const {
+  match types {
+    Type::Primitive(prim @ (Primitive::I64 | Primitive::U32 | Primitive::U64)) => {
+      Const::Int(0, prim.clone())
+    }
+    Type::Primitive(prim @ (Primitive::F32 | Primitive::F64)) => Const::Float(0.0, prim.clone()),
+    Type::Primitive(Primitive::Bool) => Const::Bool(false),
+    Type::Primitive(Primitive::Char) => Const::Char('\0'),
+    _ => Const::Int(0, Primitive::I32),
+  }
+}
+
+fn is_wrapper(ident_typed: &IdentTyped) -> bool {
+  matches!(
+    ident_typed.type_ident,
+    IdentType::Option { .. } | IdentType::Result { .. }
+  )
+}
+
+fn declare(scopes: &mut ConstScopes, ident: &str, value: Option<Const>) {
+  if let Some(scope) = scopes.last_mut() {
+    scope.insert(ident.to_string(), value);
+  }
+}
+
+fn lookup(scopes: &ConstScopes, ident: &str) -> Option<Const> {
+  scopes
+    .iter()
+    .rev()
+    .find_map(|scope| scope.get(ident))
+    .cloned()
+    .flatten()
+}
+
+/// Replaces uses of constants in `expr` with their values.
+fn fold_expr(scopes: &mut ConstScopes, expr: &mut Expr) {
+  match expr {
+    Expr::Binary { op, lhs, rhs, .. } => {
+      if *op == Operator::Assign {
+        fold_target(scopes, lhs);
+      } else {
+        fold_expr(scopes, lhs);
+      }
+      fold_expr(scopes, rhs);
+    }
+    Expr::Unary { unary, .. } => fold_unary(scopes, unary),
+    Expr::Cond {
+      cond,
+      then_expr,
+      else_expr,
+      ..
+    } => {
+      fold_expr(scopes, cond);
+      fold_expr(scopes, then_expr);
+      fold_expr(scopes, else_expr);
+    }
+    Expr::Cast { expr, .. } | Expr::Try { expr, .. } | Expr::Err { expr, .. } => {
+      fold_expr(scopes, expr)
+    }
+  }
+}
+
+/// Folds the indices of an assigned place, but not the variable itself.
+fn fold_target(scopes: &mut ConstScopes, lhs: &mut Expr) {
+  if let Expr::Unary {
+    unary: Unary::Primary { prim, .. },
+    ..
+  } = lhs
+  {
+    if matches!(prim, Primary::IdentVal { prim, .. } if !prim.is_empty()) {
+      fold_primary(scopes, prim);
+    }
+  }
+}
+
+fn fold_unary(scopes: &mut ConstScopes, unary: &mut Unary) {
+  match unary {
+    Unary::Primary { prim, .. } => fold_primary(scopes, prim),
+    Unary::UnaryOp { expr, .. } => fold_unary(scopes, expr),
+  }
+}
+
+fn fold_primary(scopes: &mut ConstScopes, prim: &mut Primary) {
+  match prim {
+    Primary::IdentVal {
+      ident,
+      prim: vals,
+      span,
+    } => {
+      if vals.is_empty() {
+        if let Some(value) = lookup(scopes, ident) {
+          *prim = Primary::Literal {
+            lit: value.to_literal(),
+            meta_id: 0,
+            span: *span,
+          };
+        }
+        return;
+      }
+      // The receiver of a method call needs a symbol, so the constant keeps
+      // its local.
+      if let Some(scope) = scopes
+        .iter_mut()
+        .rev()
+        .find(|scope| scope.contains_key(ident))
+      {
+        scope.insert(ident.clone(), None);
+      }
+      for val in vals {
+        match val {
+          IdentVal::Arguments { args, .. } => {
+            for arg in args {
+              fold_expr(scopes, arg);
+            }
+          }
+          IdentVal::Index { expr, .. } => fold_expr(scopes, expr),
+          _ => {}
+        }
+      }
+    }
+    Primary::Grouping { group, .. } => fold_expr(scopes, group),
+    Primary::Array { exprs, .. } => {
+      for expr in exprs {
+        fold_expr(scopes, expr);
+      }
+    }
+    Primary::Literal { .. } => {}
+  }
+}
+
+/// Whether `expr` is built from literals only, without naming any symbol.
+pub fn is_literal_expr(expr: &Expr) -> bool {
+  match expr {
+    Expr::Binary { lhs, rhs, .. } => is_literal_expr(lhs) && is_literal_expr(rhs),
+    Expr::Unary { unary, .. } => is_literal_unary(unary),
+    Expr::Cond {
+      cond,
+      then_expr,
+      else_expr,
+      ..
+    } => is_literal_expr(cond) && is_literal_expr(then_expr) && is_literal_expr(else_expr),
+    Expr::Cast { expr, .. } | Expr::Err { expr, .. } => is_literal_expr(expr),
+    Expr::Try { .. } => false,
+  }
+}
+
+fn is_literal_unary(unary: &Unary) -> bool {
+  match unary {
+    Unary::Primary { prim, .. } => match prim {
+      Primary::Literal { .. } => true,
+      Primary::Grouping { group, .. } => is_literal_expr(group),
+      Primary::Array { exprs, .. } => exprs.iter().all(is_literal_expr),
+      Primary::IdentVal { .. } => false,
+    },
+    Unary::UnaryOp { expr, .. } => is_literal_unary(expr),
+  }
+}
+
+/// Evaluates a constant expression. `Err(None)` means the expression is not
+/// constant, `Err(Some(..))` that evaluating it failed.
+pub fn eval_const(checker: &Checker, expr: &Expr) -> Result<Const, Option<CompilerError>> {
+  match expr {
+    Expr::Binary { op, lhs, rhs, span } => {
+      let lhs = eval_const(checker, lhs)?;
+      // `&&` and `||` short-circuit.
+      match (op, &lhs) {
+        (Operator::LogAnd, Const::Bool(false)) | (Operator::LogOr, Const::Bool(true)) => {
+          return Ok(lhs)
+        }
+        _ => {}
+      }
+      let rhs = eval_const(checker, rhs)?;
+      eval_binary(op, lhs, rhs).map_err(|err| err.map(|kind| CompilerError::new(kind, *span)))
+    }
+    Expr::Unary { unary, .. } => eval_unary(checker, unary),
+    Expr::Cond {
+      cond,
+      then_expr,
+      else_expr,
+      ..
+    } => match eval_const(checker, cond)? {
+      Const::Bool(true) => eval_const(checker, then_expr),
+      Const::Bool(false) => eval_const(checker, else_expr),
+      _ => Err(None),
+    },
+    Expr::Cast { expr, types, .. } => match types.to_type() {
+      Type::Primitive(prim) => eval_cast(eval_const(checker, expr)?, prim).ok_or(None),
+      _ => Err(None),
+    },
+    Expr::Try { .. } | Expr::Err { .. } => Err(None),
+  }
+}
+
+/// Converts `val` the way the cast compiles: integers wrap and floats saturate.
+fn eval_cast(val: Const, prim: Primitive) -> Option<Const> {
+  let wrap = |val: i128| match prim {
+    Primitive::I32 => val as i32 as i128,
+    Primitive::U32 => val as u32 as i128,
+    Primitive::I64 => val as i64 as i128,
+    _ => val as u64 as i128,
+  };
+  Some(match (val, &prim) {
+    (val, Primitive::Bool) => match val {
+      Const::Bool(val) => Const::Bool(val),
+      Const::Int(val, _) => Const::Bool(val != 0),
+      _ => return None,
+    },
+    (val, Primitive::Char) => match val {
+      Const::Char(val) => Const::Char(val),
+      Const::Int(val, _) => Const::Char(char::from_u32(val as u32)?),
+      _ => return None,
+    },
+    (Const::Float(val, _), Primitive::F32) => Const::Float(val as f32 as f64, prim),
+    (Const::Float(val, _), Primitive::F64) => Const::Float(val, prim),
+    (Const::Int(val, _), Primitive::F32) => Const::Float(val as f32 as f64, prim),
+    (Const::Int(val, _), Primitive::F64) => Const::Float(val as f64, prim),
+    (Const::Float(val, _), Primitive::I32) => Const::Int(val as i32 as i128, prim),
+    (Const::Float(val, _), Primitive::U32) => Const::Int(val as u32 as i128, prim),
+    (Const::Float(val, _), Primitive::I64) => Const::Int(val as i64 as i128, prim),
+    (Const::Float(val, _), Primitive::U64) => Const::Int(val as u64 as i128, prim),
+    (Const::Int(val, _), _) => Const::Int(wrap(val), prim),
+    (Const::Bool(val), _) => Const::Int(val as i128, prim),
+    (Const::Char(val), _) => Const::Int(wrap(val as i128), prim),
+    _ => return None,
+  })
+}
+
+fn eval_unary(checker: &Checker, unary: &Unary) -> Result<Const, Option<CompilerError>> {
+  let (op, expr, span) = match unary {
+    Unary::Primary { prim, .. } => return eval_primary(checker, prim),
+    Unary::UnaryOp { op, expr, span } => (op, expr, span),
+  };
+  let overflow = || Some(CompilerError::new(CompilerErrorKind::ConstOverflow, *span));
+  // `-2147483648` is an `i32` even though `2147483648` is not.
+  if let (
+    Operator::Sub,
+    Unary::Primary {
+      prim: Primary::Literal {
+        lit: Literal::Int(val),
+        meta_id,
+        ..
+      },
+      ..
+    },
+  ) = (op, &**expr)
+  {
+    if let Type::Primitive(prim) = &checker.substitutions[*meta_id] {
+      return int(-(*val as i128), prim.clone()).ok_or_else(overflow);
+    }
+  }
+  match (op, eval_unary(checker, expr)?) {
+    (Operator::Sub, Const::Int(val, prim)) => int(-val, prim).ok_or_else(overflow),
+    (Operator::Sub, Const::Float(val, prim)) => Ok(Const::Float(-val, prim)),
+    (Operator::BitNot, Const::Int(val, prim)) => match prim {
+      Primitive::U32 => Ok(Const::Int(u32::MAX as i128 - val, prim)),
+      Primitive::U64 => Ok(Const::Int(u64::MAX as i128 - val, prim)),
+      _ => Ok(Const::Int(!val, prim)),
+    },
+    (Operator::LogNot, Const::Bool(val)) => Ok(Const::Bool(!val)),
+    _ => Err(None),
+  }
+}
+
+fn eval_primary(checker: &Checker, prim: &Primary) -> Result<Const, Option<CompilerError>> {
+  match prim {
+    Primary::Literal { lit, meta_id, span } => {
+      let out_of_range = |types: &Type| {
+        let kind = CompilerErrorKind::LiteralOutOfRange {
+          types: types.clone(),
+        };
+        Some(CompilerError::new(kind, *span))
+      };
+      match lit {
+        Literal::Int(val) => match &checker.substitutions[*meta_id] {
+          types @ Type::Primitive(prim) => {
+            int(*val as i128, prim.clone()).ok_or_else(|| out_of_range(types))
+          }
+          _ => Err(None),
+        },
+        Literal::Float(val) => match &checker.substitutions[*meta_id] {
+          types @ Type::Primitive(Primitive::F32)
+            if val.is_finite() && (*val as f32).is_infinite() =>
+          {
+            Err(out_of_range(types))
+          }
+          Type::Primitive(prim @ (Primitive::F32 | Primitive::F64)) => {
+            Ok(Const::Float(*val, prim.clone()))
+          }
+          _ => Err(None),
+        },
+        Literal::I32(val) => Ok(Const::Int(*val as i32 as i128, Primitive::I32)),
+        Literal::I64(val) => Ok(Const::Int(*val as i64 as i128, Primitive::I64)),
+        Literal::U32(val) => Ok(Const::Int(*val as u32 as i128, Primitive::U32)),
+        Literal::U64(val) => Ok(Const::Int(*val as u64 as i128, Primitive::U64)),
+        Literal::F32(val) => Ok(Const::Float(*val, Primitive::F32)),
+        Literal::F64(val) => Ok(Const::Float(*val, Primitive::F64)),
+        Literal::Bool(val) => Ok(Const::Bool(*val)),
+        Literal::Char(val) => Ok(Const::Char(*val)),
+        _ => Err(None),
+      }
+    }
+    Primary::Grouping { group, .. } => eval_const(checker, group),
+    _ => Err(None),
+  }
+}
+
+/// An integer of type `prim`, if it is in range.
+fn int(val: i128, prim: Primitive) -> Option<Const> {
+  let (min, max) = match prim {
+    Primitive::I32 => (i32::MIN as i128, i32::MAX as i128),
+    Primitive::I64 => (i64::MIN as i128, i64::MAX as i128),
+    Primitive::U32 => (0, u32::MAX as i128),
+    Primitive::U64 => (0, u64::MAX as i128),
+    _ => return None,
+  };
+  (min..=max).contains(&val).then_some(Const::Int(val, prim))
+}
+
+fn eval_binary(op: &Operator, lhs: Const, rhs: Const) -> Result<Const, Option<CompilerErrorKind>> {
+  let overflow = Some(CompilerErrorKind::ConstOverflow);
+  match (lhs, rhs) {
+    (Const::Int(a, prim), Const::Int(b, _)) => {
+      let val = match op {
+        Operator::Add => a.checked_add(b),
+        Operator::Sub => a.checked_sub(b),
+        Operator::Mul => a.checked_mul(b),
+        Operator::Div | Operator::Mod if b == 0 => {
+          return Err(Some(CompilerErrorKind::ConstDivisionByZero))
+        }
+        Operator::Div => a.checked_div(b),
+        Operator::Mod => a.checked_rem(b),
+        Operator::BitAnd => Some(a & b),
+        Operator::BitOr => Some(a | b),
+        Operator::BitXor => Some(a ^ b),
+        Operator::BitLeftShift => u32::try_from(b)
+          .ok()
+          .filter(|b| *b < 64)
+          .and_then(|b| a.checked_mul(1 << b)),
+        Operator::BitRightShift => u32::try_from(b).ok().filter(|b| *b < 64).map(|b| a >> b),
+        Operator::Eq => return Ok(Const::Bool(a == b)),
+        Operator::NotEq => return Ok(Const::Bool(a != b)),
+        Operator::LessThan => return Ok(Const::Bool(a < b)),
+        Operator::LessThanOrEq => return Ok(Const::Bool(a <= b)),
+        Operator::GreaterThan => return Ok(Const::Bool(a > b)),
+        Operator::GreaterThanOrEq => return Ok(Const::Bool(a >= b)),
+        _ => return Err(None),
+      };
+      val.and_then(|val| int(val, prim)).ok_or(overflow)
+    }
+    (Const::Float(a, prim), Const::Float(b, _)) => {
+      let val = match op {
+        Operator::Add => a + b,
+        Operator::Sub => a - b,
+        Operator::Mul => a * b,
+        Operator::Div => a / b,
+        Operator::Mod => a % b,
+        Operator::Eq => return Ok(Const::Bool(a == b)),
+        Operator::NotEq => return Ok(Const::Bool(a != b)),
+        Operator::LessThan => return Ok(Const::Bool(a < b)),
+        Operator::LessThanOrEq => return Ok(Const::Bool(a <= b)),
+        Operator::GreaterThan => return Ok(Const::Bool(a > b)),
+        Operator::GreaterThanOrEq => return Ok(Const::Bool(a >= b)),
+        _ => return Err(None),
+      };
+      let val = match prim {
+        Primitive::F32 => val as f32 as f64,
+        _ => val,
+      };
+      Ok(Const::Float(val, prim))
+    }
+    (Const::Bool(a), Const::Bool(b)) => match op {
+      Operator::LogAnd => Ok(Const::Bool(a && b)),
+      Operator::LogOr => Ok(Const::Bool(a || b)),
+      Operator::Eq => Ok(Const::Bool(a == b)),
+      Operator::NotEq => Ok(Const::Bool(a != b)),
+      _ => Err(None),
+    },
+    (Const::Char(a), Const::Char(b)) => match op {
+      Operator::Eq => Ok(Const::Bool(a == b)),
+      Operator::NotEq => Ok(Const::Bool(a != b)),
+      _ => Err(None),
+    },
+    _ => Err(None),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::check;
+  use crate::test_support::errors;
+  use whistle_ast::Expr;
+  use whistle_ast::Literal;
+  use whistle_ast::Primary;
+  use whistle_ast::Primitive;
+  use whistle_ast::ProgramStmt;
+  use whistle_ast::Stmt;
+  use whistle_ast::Type;
+  use whistle_ast::Unary;
+  use whistle_common::CompilerErrorKind;
+
+  #[test]
+  fn fold_and_substitute() {
+    let (checker, grammar) = check(
+      "val A = 6
+      fn f(): i32 {
+        val b = A * 7 - 50
+        return b
+      }",
+    );
+    assert!(errors(&checker.handler).is_empty());
+    let stmts = match &grammar[1] {
+      ProgramStmt::FunctionDecl { stmt, .. } => stmt,
+      _ => unreachable!(),
+    };
+    assert_eq!(stmts.len(), 1);
+    assert!(matches!(
+      &stmts[0],
+      Stmt::Return {
+        ret_type: Some(Expr::Unary {
+          unary: Unary::Primary {
+            prim: Primary::Literal {
+              lit: Literal::I32(val),
+              ..
+            },
+            ..
+          },
+          ..
+        }),
+        ..
+      } if *val as i32 == -8
+    ));
+  }
+
+  #[test]
+  fn overflow_and_division_by_zero() {
+    let (checker, _) = check(
+      "val A: i32 = 2147483647 + 1
+      val B = 10
+      val C = B / (B - 10)
+      val D: i32 = 3000000000
+      val E: i32 = -2147483648",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![
+        CompilerErrorKind::ConstOverflow,
+        CompilerErrorKind::ConstDivisionByZero,
+        CompilerErrorKind::LiteralOutOfRange {
+          types: Type::Primitive(Primitive::I32)
+        }
+      ]
+    );
+  }
+
+  #[test]
+  fn casts() {
+    let (checker, grammar) = check(
+      "val A: u32 = -1 as u32
+      val B: i32 = 3.9 as i32
+      val C = true as f64",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![CompilerErrorKind::InvalidCast {
+        from: Type::Primitive(Primitive::Bool),
+        to: Type::Primitive(Primitive::F64)
+      }]
+    );
+    let values: Vec<Literal> = grammar[..2]
+      .iter()
+      .map(|program| match program {
+        ProgramStmt::ValDecl {
+          val:
+            Expr::Unary {
+              unary:
+                Unary::Primary {
+                  prim: Primary::Literal { lit, .. },
+                  ..
+                },
+              ..
+            },
+          ..
+        } => lit.clone(),
+        _ => unreachable!(),
+      })
+      .collect();
+    assert_eq!(
+      values,
+      vec![Literal::U32(u32::MAX as usize), Literal::I32(3)]
+    );
+  }
+}
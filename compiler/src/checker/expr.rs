@@ -1,4 +1,7 @@
 use crate::binary_to_type_val;
+use crate::check_assign;
+use crate::check_generic_call;
+use crate::check_method;
 use crate::unary_to_type_val;
 use crate::Checker;
 use crate::IndexedSymbol;
@@ -15,6 +18,7 @@ use whistle_ast::Type;
 use whistle_ast::Unary;
 use whistle_common::CompilerHandler;
 use whistle_common::Span;
+use whistle_common::WarningKind;
 
 pub fn check_expr(checker: &mut Checker, expr: &mut Expr) -> Type {
   match expr {
@@ -26,6 +30,84 @@ pub fn check_expr(checker: &mut Checker, expr: &mut Expr) -> Type {
       else_expr,
       ..
     } => check_cond(checker, cond, then_expr, else_expr),
+    Expr::Cast { expr, types, span } => {
+      let from = check_expr(checker, expr);
+      let to = types.to_type();
+      checker.casts.push((from, to.clone(), *span));
+      to
+    }
+    Expr::Try { expr, span } => check_try(checker, expr, span),
+    Expr::Err { expr, meta_id, .. } => {
+      let err = check_expr(checker, expr);
+      *meta_id = checker.substitutions.len();
+      let types = checker.new_type_val();
+      let ok = checker.new_type_val();
+      checker.constraint(
+        types.clone(),
+        Type::Result(Box::new(ok), Box::new(err)),
+        None,
+      );
+      types
+    }
+  }
+}
+
+/// Checks `expr?`, which returns the error of `expr` from a function returning
+/// a result with the same error type.
+pub fn check_try(checker: &mut Checker, expr: &mut Expr, span: &mut Span) -> Type {
+  let types = check_expr(checker, expr);
+  let ok = checker.new_type_val();
+  let err = checker.new_type_val();
+  checker.constraint(
+    types,
+    Type::Result(Box::new(ok.clone()), Box::new(err.clone())),
+    Some(expr.span()),
+  );
+  match checker.ret_type.clone() {
+    Some(Type::Result(_, ret_err)) => checker.constraint(err, *ret_err, Some(*span)),
+    Some(types) => checker
+      .handler
+      .throw(CompilerErrorKind::IncompatibleTry { types }, *span),
+    None => checker
+      .handler
+      .throw(CompilerErrorKind::ScopeNotInFunction, *span),
+  }
+  ok
+}
+
+/// Reports the casts whose operand can't be converted to the target type.
+pub fn check_casts(checker: &mut Checker) {
+  for (from, to, span) in checker.casts.clone() {
+    let from = Checker::coerce(checker.substitute(from));
+    if from != Type::Error && !is_castable(&from, &to) {
+      checker
+        .handler
+        .throw(CompilerErrorKind::InvalidCast { from, to }, span);
+    }
+  }
+}
+
+/// Numbers convert to each other, chars and bools to integers, and integers
+/// to chars and bools.
+pub fn is_castable(from: &Type, to: &Type) -> bool {
+  let (from, to) = match (from, to) {
+    (Type::Primitive(from), Type::Primitive(to)) => (from, to),
+    _ => return false,
+  };
+  let is_int = |prim: &Primitive| {
+    matches!(
+      prim,
+      Primitive::I32 | Primitive::I64 | Primitive::U32 | Primitive::U64
+    )
+  };
+  let is_number =
+    |prim: &Primitive| is_int(prim) || matches!(prim, Primitive::F32 | Primitive::F64);
+  match (from, to) {
+    _ if from == to => true,
+    (from, to) if is_number(from) && is_number(to) => true,
+    (Primitive::Char | Primitive::Bool, to) => is_int(to),
+    (from, Primitive::Char | Primitive::Bool) => is_int(from),
+    _ => false,
   }
 }
 
@@ -46,39 +128,15 @@ pub fn check_bin_expr(
   lhs: &mut Expr,
 ) -> Type {
   if op == &Operator::Assign {
-    if let Expr::Unary {
-      unary: Unary::Primary {
-        prim: Primary::IdentVal { ident, .. },
-        ..
-      },
-      span,
-    } = lhs
-    {
-      let ret_type = checker.new_type_val();
-      let type1 = check_expr(checker, rhs);
-      let sym = match checker.scope.get_sym(ident) {
-        Ok(sym) => sym.clone(),
-        Err(err) => {
-          checker.handler.throw(err, span.clone());
-          IndexedSymbol(0, Symbol::default())
-        }
-      };
-
-      checker.constraint(sym.1.types.clone(), type1, Some(rhs.span()));
-      checker.constraint(ret_type.clone(), sym.1.types, None);
-
-      if !sym.1.mutable {
-        checker
-          .handler
-          .throw(CompilerErrorKind::ImmutableAssign, span.clone());
-      }
-
-      return ret_type;
-    }
-    checker
-      .handler
-      .throw(CompilerErrorKind::Unassignable, rhs.span());
-    Type::Error
+    check_assign(checker, lhs, rhs)
+  } else if op == &Operator::Coalesce {
+    check_coalesce(checker, lhs, rhs)
+  } else if matches!(op, Operator::Eq | Operator::NotEq) && (is_none(lhs) || is_none(rhs)) {
+    // Comparing with `none` tests whether an option is empty.
+    let type1 = check_expr(checker, lhs);
+    let type2 = check_expr(checker, rhs);
+    checker.constraint(type2, type1, Some(rhs.span()));
+    Type::Primitive(Primitive::Bool)
   } else {
     let ret_type = checker.new_type_val();
     let type1 = check_expr(checker, lhs);
@@ -93,6 +151,79 @@ pub fn check_bin_expr(
   }
 }
 
+/// Checks `lhs ?? rhs`, the value of the option `lhs` or `rhs` if it is empty.
+pub fn check_coalesce(checker: &mut Checker, lhs: &mut Expr, rhs: &mut Expr) -> Type {
+  let inner = checker.new_type_val();
+  let type1 = check_expr(checker, lhs);
+  let type2 = check_expr(checker, rhs);
+  checker.constraint(
+    type1,
+    Type::Option(Box::new(inner.clone())),
+    Some(lhs.span()),
+  );
+  checker.constraint(type2, inner.clone(), Some(rhs.span()));
+  inner
+}
+
+/// Whether `expr` is the literal `none`.
+pub fn is_none(expr: &Expr) -> bool {
+  matches!(
+    expr,
+    Expr::Unary {
+      unary: Unary::Primary {
+        prim: Primary::Literal {
+          lit: Literal::None,
+          ..
+        },
+        ..
+      },
+      ..
+    }
+  )
+}
+
+/// The variable an `if` condition compares with `none`, the `meta_id` of the
+/// `none` literal, and whether the variable holds a value in the then branch.
+pub fn narrowed_ident(cond: &Expr) -> Option<(String, usize, bool)> {
+  let (op, lhs, rhs) = match cond {
+    Expr::Binary { op, lhs, rhs, .. } => (op, lhs, rhs),
+    _ => return None,
+  };
+  let then = match op {
+    Operator::NotEq => true,
+    Operator::Eq => false,
+    _ => return None,
+  };
+  let (ident, none) = if is_none(rhs) { (lhs, rhs) } else { (rhs, lhs) };
+  let ident = match &**ident {
+    Expr::Unary {
+      unary:
+        Unary::Primary {
+          prim: Primary::IdentVal { ident, prim, .. },
+          ..
+        },
+      ..
+    } if prim.is_empty() => ident.clone(),
+    _ => return None,
+  };
+  match &**none {
+    Expr::Unary {
+      unary:
+        Unary::Primary {
+          prim:
+            Primary::Literal {
+              lit: Literal::None,
+              meta_id,
+              ..
+            },
+          ..
+        },
+      ..
+    } => Some((ident, *meta_id, then)),
+    _ => None,
+  }
+}
+
 pub fn check_unary(checker: &mut Checker, expr: &mut Unary) -> Type {
   match expr {
     Unary::Primary { prim, .. } => check_primary(checker, prim),
@@ -120,7 +251,11 @@ pub fn check_primary(checker: &mut Checker, expr: &mut Primary) -> Type {
     } => check_literal(checker, lit, meta_id, span),
     Primary::IdentVal { ident, prim, span } => check_ident(checker, ident, prim, span),
     Primary::Grouping { group, .. } => check_expr(checker, group),
-    Primary::Array { exprs, meta_id, .. } => check_array(checker, exprs, meta_id),
+    Primary::Array {
+      exprs,
+      meta_id,
+      span,
+    } => check_array(checker, exprs, meta_id, span),
   }
 }
 
@@ -135,18 +270,25 @@ pub fn check_literal(
     Literal::Char(_) => Type::Primitive(Primitive::Char),
     Literal::Int(_) => {
       *id = checker.substitutions.len();
-      let lit_type = checker.new_type_val();
+      let lit_type = checker.new_type_val_at(*span);
       checker.constraint(lit_type.clone(), Type::Primitive(Primitive::Int), None);
       lit_type
     }
     Literal::Float(_) => {
       *id = checker.substitutions.len();
-      let lit_type = checker.new_type_val();
+      let lit_type = checker.new_type_val_at(*span);
       checker.constraint(lit_type.clone(), Type::Primitive(Primitive::Float), None);
       lit_type
     }
     Literal::Str(_) => Type::Primitive(Primitive::Str),
-    Literal::None => Type::Primitive(Primitive::None),
+    // The type of the value follows the type of the literal, for narrowing.
+    Literal::None => {
+      *id = checker.substitutions.len();
+      let lit_type = checker.new_type_val();
+      let inner = checker.new_type_val();
+      checker.constraint(lit_type.clone(), Type::Option(Box::new(inner)), None);
+      lit_type
+    }
     _ => {
       checker
         .handler
@@ -159,7 +301,7 @@ pub fn check_literal(
 pub fn check_ident(
   checker: &mut Checker,
   ident: &mut str,
-  prim: &mut Vec<IdentVal>,
+  prim: &mut [IdentVal],
   span: &mut Span,
 ) -> Type {
   let sym = match checker.scope.get_sym(ident) {
@@ -169,39 +311,84 @@ pub fn check_ident(
       IndexedSymbol(0, Symbol::default())
     }
   };
-  check_ident_val(checker, &sym, prim, 0)
+  // Inline intrinsics have no function to refer to.
+  if sym.1.intrinsic && !matches!(prim.first(), Some(IdentVal::Arguments { .. })) {
+    checker.handler.throw(
+      CompilerErrorKind::UncalledIntrinsic {
+        ident: ident.to_string(),
+      },
+      *span,
+    );
+  }
+  if sym.1.global {
+    if let Some(note) = checker.deprecated.get(ident) {
+      let kind = WarningKind::Deprecated {
+        ident: ident.to_string(),
+        note: note.clone(),
+      };
+      checker.handler.warn(kind, *span);
+    }
+  }
+  check_ident_val(checker, ident, &sym, prim)
 }
 
 pub fn check_ident_val(
   checker: &mut Checker,
+  ident: &str,
   sym: &IndexedSymbol,
-  prim: &mut Vec<IdentVal>,
-  index: usize,
+  prim: &mut [IdentVal],
 ) -> Type {
-  if prim.is_empty() {
-    sym.1.types.clone()
-  } else {
-    let types = match &mut prim[index] {
-      IdentVal::Arguments { args, span } => check_arguments(checker, sym, args, span),
-      IdentVal::Selector { ident, span } => check_selector(checker, sym, ident, span),
-      _ => {
+  let mut types = sym.1.types.clone();
+  let mut i = 0;
+  while i < prim.len() {
+    // A selector followed by arguments calls a method, unless it selects a
+    // property of a struct.
+    if let [IdentVal::Selector { ident, .. }, IdentVal::Arguments {
+      args, named, span, ..
+    }, ..] = &mut prim[i..]
+    {
+      if !has_property(checker, &types, ident) {
+        types = check_method(checker, types, ident, args, named, *span);
+        i += 2;
+        continue;
+      }
+    }
+    types = match &mut prim[i] {
+      IdentVal::Arguments {
+        args,
+        named,
+        meta_id,
+        span,
+      } => check_arguments(checker, ident, sym, args, named, meta_id, span),
+      IdentVal::Selector { ident, span } => check_selector(checker, types, ident, span),
+      IdentVal::Index { expr, span } => check_index(checker, types, expr, span),
+      IdentVal::Slice { span, .. } => {
         checker
           .handler
-          .throw(CompilerErrorKind::Unimplemented, prim[index].span());
+          .throw(CompilerErrorKind::Unimplemented, *span);
         Type::Error
       }
     };
-    if prim.len() > index + 1 {
-      check_ident_val(checker, sym, prim, index + 1)
-    } else {
-      types
-    }
+    i += 1;
   }
+  types
+}
+
+/// Whether `types` is a struct with the property `ident`.
+pub fn has_property(checker: &Checker, types: &Type, ident: &str) -> bool {
+  checker
+    .props(types.clone())
+    .is_some_and(|props| props.iter().any(|prop| prop.ident == ident))
 }
 
-pub fn check_array(checker: &mut Checker, exprs: &mut Vec<Expr>, id: &mut usize) -> Type {
+pub fn check_array(
+  checker: &mut Checker,
+  exprs: &mut [Expr],
+  id: &mut usize,
+  span: &mut Span,
+) -> Type {
   *id = checker.substitutions.len();
-  let ret_type = checker.new_type_val();
+  let ret_type = checker.new_type_val_at(*span);
   let type1;
   if !exprs.is_empty() {
     type1 = check_expr(checker, &mut exprs[0]);
@@ -216,39 +403,99 @@ pub fn check_array(checker: &mut Checker, exprs: &mut Vec<Expr>, id: &mut usize)
   ret_type
 }
 
+/// Checks a call and rewrites its arguments into parameter order, moving named
+/// arguments into place and filling in defaults for the ones left out.
 pub fn check_arguments(
   checker: &mut Checker,
+  ident: &str,
   sym: &IndexedSymbol,
   args: &mut Vec<Expr>,
+  named: &mut Vec<(String, Expr)>,
+  meta_id: &mut usize,
   span: &mut Span,
 ) -> Type {
-  if let Type::Function { params, ret_type } = sym.1.types.clone() {
-    for (i, param) in params.into_iter().enumerate() {
-      if args.len() > i {
-        let expr_type = check_expr(checker, &mut args[i]);
-        checker.constraint(expr_type, param.type_ident, Some(args[i].span()));
-      } else {
+  let types = match sym.1.global {
+    true => check_generic_call(checker, ident, sym.1.types.clone(), meta_id, *span),
+    false => sym.1.types.clone(),
+  };
+  let (params, ret_type) = match checker.substitute(types) {
+    Type::Function { params, ret_type } => (params, ret_type),
+    types => {
+      if types != Type::Error {
         checker
           .handler
-          .throw(CompilerErrorKind::MissingParameters, span.clone());
+          .throw(CompilerErrorKind::MissingCallSignature, *span);
+      }
+      for expr in args
+        .iter_mut()
+        .chain(named.iter_mut().map(|(_, expr)| expr))
+      {
+        check_expr(checker, expr);
       }
+      return Type::Error;
+    }
+  };
+
+  let mut slots: Vec<Option<Expr>> = Vec::new();
+  for expr in args.drain(..) {
+    if slots.len() < params.len() {
+      slots.push(Some(expr));
+    } else {
+      checker
+        .handler
+        .throw(CompilerErrorKind::UnexpectedArgument, expr.span());
     }
-    *ret_type
-  } else {
-    checker
-      .handler
-      .throw(CompilerErrorKind::MissingCallSignature, span.clone());
-    Type::Error
   }
+  slots.resize(params.len(), None);
+  for (name, expr) in named.drain(..) {
+    match params.iter().position(|param| param.ident == name) {
+      Some(i) if slots[i].is_some() => checker.handler.throw(
+        CompilerErrorKind::DuplicateArgument { ident: name },
+        expr.span(),
+      ),
+      Some(i) => slots[i] = Some(expr),
+      None => checker.handler.throw(
+        CompilerErrorKind::UnknownParameter { ident: name },
+        expr.span(),
+      ),
+    }
+  }
+
+  // Defaults only apply to a call of the function itself, not of a local that
+  // shadows it.
+  let defaults = match sym.1.global {
+    true => checker.defaults.get(ident).cloned().unwrap_or_default(),
+    false => Vec::new(),
+  };
+  let mut missing = Vec::new();
+  for (i, (slot, param)) in slots.into_iter().zip(params).enumerate() {
+    let mut expr = match slot.or_else(|| defaults.get(i).cloned().flatten()) {
+      Some(expr) => expr,
+      None => {
+        missing.push(param.ident);
+        continue;
+      }
+    };
+    let expr_type = check_expr(checker, &mut expr);
+    checker.flow(expr_type, param.type_ident, Some(expr.span()));
+    args.push(expr);
+  }
+  if !missing.is_empty() {
+    checker.handler.throw(
+      CompilerErrorKind::MissingParameters { idents: missing },
+      *span,
+    );
+  }
+  *ret_type
 }
 
 pub fn check_selector(
   checker: &mut Checker,
-  sym: &IndexedSymbol,
+  types: Type,
   ident: &mut String,
   span: &mut Span,
 ) -> Type {
-  if let Type::Struct(props) = sym.1.types.clone() {
+  if let Some(props) = checker.props(types) {
     for prop in props {
       if prop.ident == *ident {
         return prop.type_ident;
@@ -258,6 +505,7 @@ pub fn check_selector(
     checker
       .handler
       .throw(CompilerErrorKind::MissingProperty, span.clone());
+    return Type::Error;
   }
   checker
     .handler
@@ -265,6 +513,18 @@ pub fn check_selector(
   Type::Error
 }
 
+pub fn check_index(checker: &mut Checker, types: Type, expr: &mut Expr, span: &mut Span) -> Type {
+  let index_type = check_expr(checker, expr);
+  checker.constraint(
+    index_type,
+    Type::Primitive(Primitive::Int),
+    Some(expr.span()),
+  );
+  let elem_type = checker.new_type_val();
+  checker.constraint(types, Type::Array(Box::new(elem_type.clone())), Some(*span));
+  elem_type
+}
+
 pub fn check_cond(
   checker: &mut Checker,
   cond: &mut Expr,
@@ -0,0 +1,139 @@
+use crate::Checker;
+
+use whistle_ast::Expr;
+use whistle_ast::Literal;
+use whistle_ast::Primary;
+use whistle_ast::Stmt;
+use whistle_ast::Unary;
+use whistle_common::WarningKind;
+
+/// How control leaves a statement, ordered from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Flow {
+  /// Falls through to the next statement.
+  Next,
+  /// Leaves through `break` or `continue`.
+  Jump,
+  /// Returns from the function or never finishes.
+  Return,
+}
+
+/// The flow of a block of statements. Statements after one that doesn't fall
+/// through are reported as unreachable, once per block.
+pub fn check_flow(checker: &mut Checker, stmts: &[Stmt]) -> Flow {
+  let mut flow = Flow::Next;
+  let mut warned = false;
+  for stmt in stmts {
+    if flow == Flow::Next {
+      flow = check_stmt_flow(checker, stmt);
+      continue;
+    }
+    if !warned {
+      checker
+        .handler
+        .warn(WarningKind::UnreachableCode, stmt.span());
+      warned = true;
+    }
+    // Dead statements are still walked for nested diagnostics.
+    check_stmt_flow(checker, stmt);
+  }
+  flow
+}
+
+pub fn check_stmt_flow(checker: &mut Checker, stmt: &Stmt) -> Flow {
+  match stmt {
+    Stmt::Return { .. } => Flow::Return,
+    Stmt::Break { .. } | Stmt::Continue { .. } => Flow::Jump,
+    Stmt::If {
+      then_stmt,
+      else_stmt,
+      ..
+    } => {
+      let then_flow = check_flow(checker, then_stmt);
+      match else_stmt {
+        Some(else_stmt) => then_flow.min(check_flow(checker, else_stmt)),
+        None => Flow::Next,
+      }
+    }
+    Stmt::While { cond, do_stmt, .. } => {
+      check_flow(checker, do_stmt);
+      // `while true` only finishes through a `break`.
+      if is_true(cond) && !has_break(do_stmt) {
+        Flow::Return
+      } else {
+        Flow::Next
+      }
+    }
+    Stmt::Block { stmts, .. } => check_flow(checker, stmts),
+    _ => Flow::Next,
+  }
+}
+
+fn is_true(expr: &Expr) -> bool {
+  matches!(
+    expr,
+    Expr::Unary {
+      unary: Unary::Primary {
+        prim: Primary::Literal {
+          lit: Literal::Bool(true),
+          ..
+        },
+        ..
+      },
+      ..
+    }
+  )
+}
+
+/// Whether a `break` in `stmts` leaves the loop they are the body of.
+fn has_break(stmts: &[Stmt]) -> bool {
+  stmts.iter().any(|stmt| match stmt {
+    Stmt::Break { .. } => true,
+    Stmt::If {
+      then_stmt,
+      else_stmt,
+      ..
+    } => has_break(then_stmt) || else_stmt.as_deref().is_some_and(has_break),
+    Stmt::Block { stmts, .. } => has_break(stmts),
+    _ => false,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::check;
+  use crate::test_support::errors;
+  use crate::test_support::warnings;
+  use whistle_common::CompilerErrorKind;
+  use whistle_common::WarningKind;
+
+  #[test]
+  fn missing_return_and_unreachable_code() {
+    let (checker, _) = check(
+      "export fn a(x: bool): i32 {
+        if x {
+          return 1
+        } else {
+          return 2
+        }
+        return 3
+      }
+      export fn b(x: bool): i32 {
+        if x {
+          return 1
+        }
+      }
+      export fn c(): i32 {
+        while true {}
+      }",
+    );
+    assert_eq!(
+      errors(&checker.handler),
+      vec![CompilerErrorKind::MissingReturn]
+    );
+    assert_eq!(
+      warnings(&checker.handler),
+      vec![WarningKind::UnreachableCode]
+    );
+  }
+}
@@ -0,0 +1,549 @@
+use crate::check_expr;
+use crate::check_program;
+use crate::Checker;
+use crate::Constraint;
+use crate::Symbol;
+
+use std::collections::HashMap;
+
+use whistle_ast::Expr;
+use whistle_ast::IdentExternFn;
+use whistle_ast::IdentGeneric;
+use whistle_ast::IdentType;
+use whistle_ast::IdentTyped;
+use whistle_ast::ProgramStmt;
+use whistle_ast::Type;
+use whistle_common::CompilerErrorKind;
+use whistle_common::CompilerHandler;
+use whistle_common::Keyword;
+use whistle_common::Span;
+use whistle_common::TypedIdent;
+
+/// A generic function, which is compiled once for every set of types it is
+/// called with.
+#[derive(Debug, Clone)]
+pub struct GenericFn {
+  pub params: Vec<IdentGeneric>,
+  /// How many scopes the body of the function opens.
+  pub scopes: usize,
+}
+
+/// A call `receiver.ident(args)`, resolved once the type of the receiver is
+/// known.
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+  pub receiver: Type,
+  pub ident: String,
+  pub args: Vec<(Type, Span)>,
+  pub ret_type: Type,
+  /// The generic function the call is in, whose bounds apply to the receiver.
+  pub caller: Option<String>,
+  pub span: Span,
+}
+
+/// A call of the generic function `callee`, which binds its generic parameters
+/// to the type variables from `meta_id` on.
+#[derive(Debug, Clone)]
+pub struct GenericCall {
+  pub callee: String,
+  pub caller: Option<String>,
+  pub meta_id: usize,
+  pub span: Span,
+}
+
+/// How `types` is written, for the names of methods and instances.
+pub fn type_name(types: &Type) -> String {
+  let names = |types: &[Type]| types.iter().map(type_name).collect::<Vec<_>>().join(", ");
+  let typed_names = |types: &[TypedIdent]| {
+    types
+      .iter()
+      .map(|typed| format!("{}: {}", typed.ident, type_name(&typed.type_ident)))
+      .collect::<Vec<_>>()
+      .join(", ")
+  };
+  match types {
+    Type::Primitive(prim) => String::from(<&str>::from(Keyword::Primitive(prim.clone()))),
+    Type::Ident(ident) | Type::Generic(ident) => ident.clone(),
+    Type::IdentType { ident, prim } => format!("{}<{}>", ident, names(prim)),
+    Type::Array(elem) => format!("{}[]", type_name(elem)),
+    Type::Option(inner) => format!("{}?", type_name(inner)),
+    Type::Result(ok, err) => format!("{}!{}", type_name(ok), type_name(err)),
+    Type::Tuple(types) => format!("({})", names(types)),
+    Type::Struct(props) => format!("{{ {} }}", typed_names(props)),
+    Type::Function { params, ret_type } => {
+      format!("fn({}): {}", typed_names(params), type_name(ret_type))
+    }
+    Type::Var(i) => format!("${}", i),
+    Type::Default => String::from("_"),
+    Type::Error => String::from("error"),
+  }
+}
+
+/// The name of the function implementing the method `ident` of `types`.
+pub fn method_ident(types: &Type, ident: &str) -> String {
+  format!("{}.{}", type_name(types), ident)
+}
+
+/// The name of the instance of the generic function `ident` for `types`.
+pub fn instance_ident(ident: &str, types: &[Type]) -> String {
+  let names: Vec<String> = types.iter().map(type_name).collect();
+  format!("{}<{}>", ident, names.join(", "))
+}
+
+/// `types` with its generic parameters replaced by the types in `generics`.
+pub fn instantiate(types: Type, generics: &HashMap<String, Type>) -> Type {
+  let typed = |typed: TypedIdent| TypedIdent {
+    ident: typed.ident,
+    type_ident: instantiate(typed.type_ident, generics),
+  };
+  match types {
+    Type::Generic(ident) => match generics.get(&ident) {
+      Some(types) => types.clone(),
+      None => Type::Generic(ident),
+    },
+    Type::Array(elem) => Type::Array(Box::new(instantiate(*elem, generics))),
+    Type::Option(inner) => Type::Option(Box::new(instantiate(*inner, generics))),
+    Type::Result(ok, err) => Type::Result(
+      Box::new(instantiate(*ok, generics)),
+      Box::new(instantiate(*err, generics)),
+    ),
+    Type::Tuple(types) => Type::Tuple(
+      types
+        .into_iter()
+        .map(|types| instantiate(types, generics))
+        .collect(),
+    ),
+    Type::IdentType { ident, prim } => Type::IdentType {
+      ident,
+      prim: prim
+        .into_iter()
+        .map(|types| instantiate(types, generics))
+        .collect(),
+    },
+    Type::Function { params, ret_type } => Type::Function {
+      params: params.into_iter().map(typed).collect(),
+      ret_type: Box::new(instantiate(*ret_type, generics)),
+    },
+    Type::Struct(props) => Type::Struct(props.into_iter().map(typed).collect()),
+    _ => types,
+  }
+}
+
+/// Whether two function types take and return the same types, whatever their
+/// parameters are called.
+fn same_signature(type1: &Type, type2: &Type) -> bool {
+  match (type1, type2) {
+    (
+      Type::Function {
+        params: params1,
+        ret_type: ret1,
+      },
+      Type::Function {
+        params: params2,
+        ret_type: ret2,
+      },
+    ) => {
+      params1.len() == params2.len()
+        && params1
+          .iter()
+          .zip(params2)
+          .all(|(param1, param2)| param1.type_ident == param2.type_ident)
+        && ret1 == ret2
+    }
+    _ => false,
+  }
+}
+
+pub fn check_interface(
+  checker: &mut Checker,
+  ident: &str,
+  methods: &mut [IdentExternFn],
+  span: Span,
+) {
+  let signatures = methods
+    .iter()
+    .map(|method| TypedIdent {
+      ident: method.ident.clone(),
+      type_ident: Type::Function {
+        params: IdentTyped::vec_to_type(&method.params),
+        ret_type: Box::new(method.ret_type.to_type()),
+      },
+    })
+    .collect();
+  if checker
+    .interfaces
+    .insert(ident.to_string(), signatures)
+    .is_some()
+  {
+    checker
+      .handler
+      .throw(CompilerErrorKind::SymbolRedifinition, span);
+  }
+}
+
+/// Checks the methods of `impl interface for types` against the interface, and
+/// declares them as functions named by `method_ident`. The methods of
+/// `impl types` only need to take `self`.
+pub fn check_impl(
+  checker: &mut Checker,
+  interface: &Option<String>,
+  types: &IdentType,
+  fns: &mut [ProgramStmt],
+  span: Span,
+) {
+  let target = types.to_type();
+  let interface = match interface {
+    Some(interface) => interface,
+    None => return check_methods_of(checker, &target, fns),
+  };
+  let signatures = match checker.interfaces.get(interface) {
+    Some(signatures) => signatures.clone(),
+    None => {
+      checker.handler.throw(
+        CompilerErrorKind::UnknownInterface {
+          ident: interface.to_string(),
+        },
+        span,
+      );
+      Vec::new()
+    }
+  };
+  let generics = HashMap::from([(String::from("Self"), target.clone())]);
+
+  for program in fns.iter_mut() {
+    if let ProgramStmt::FunctionDecl {
+      ident,
+      params,
+      ret_type,
+      span,
+      ..
+    } = program
+    {
+      let types = Type::Function {
+        params: IdentTyped::vec_to_type(params),
+        ret_type: Box::new(ret_type.to_type()),
+      };
+      match signatures
+        .iter()
+        .find(|signature| signature.ident == *ident)
+      {
+        Some(signature) => {
+          let expected = instantiate(signature.type_ident.clone(), &generics);
+          if !same_signature(&types, &expected) {
+            checker.handler.throw(
+              CompilerErrorKind::TypeMismatch {
+                type1: types,
+                type2: expected,
+              },
+              *span,
+            );
+          }
+        }
+        None if checker.interfaces.contains_key(interface) => checker.handler.throw(
+          CompilerErrorKind::UnknownMethod {
+            ident: ident.clone(),
+          },
+          *span,
+        ),
+        None => {}
+      }
+      *ident = method_ident(&target, ident);
+    }
+    check_program(checker, program);
+  }
+
+  for signature in signatures.iter() {
+    let ident = method_ident(&target, &signature.ident);
+    let defined = fns.iter().any(|program| {
+      matches!(program, ProgramStmt::FunctionDecl { ident: fn_ident, .. } if *fn_ident == ident)
+    });
+    if !defined {
+      checker.handler.throw(
+        CompilerErrorKind::MissingMethod {
+          ident: signature.ident.clone(),
+        },
+        span,
+      );
+    }
+  }
+  checker.impls.push((target, interface.to_string()));
+}
+
+/// Checks the methods of `impl types`, which are called on a receiver passed as
+/// their `self` parameter.
+fn check_methods_of(checker: &mut Checker, target: &Type, fns: &mut [ProgramStmt]) {
+  for program in fns.iter_mut() {
+    if let ProgramStmt::FunctionDecl {
+      ident,
+      params,
+      span,
+      ..
+    } = program
+    {
+      if params.first().map(|param| param.ident.as_str()) != Some("self") {
+        checker.handler.throw(
+          CompilerErrorKind::MissingSelf {
+            ident: ident.clone(),
+          },
+          *span,
+        );
+      }
+      *ident = method_ident(target, ident);
+    }
+    check_program(checker, program);
+  }
+}
+
+/// Checks the arguments of a method call, which is resolved once the type of
+/// `receiver` is known.
+pub fn check_method(
+  checker: &mut Checker,
+  receiver: Type,
+  ident: &str,
+  args: &mut [Expr],
+  named: &mut [(String, Expr)],
+  span: Span,
+) -> Type {
+  let args = args
+    .iter_mut()
+    .map(|arg| (check_expr(checker, arg), arg.span()))
+    .collect();
+  for (_, expr) in named.iter_mut() {
+    check_expr(checker, expr);
+    checker
+      .handler
+      .throw(CompilerErrorKind::Unimplemented, expr.span());
+  }
+  let ret_type = checker.new_type_val();
+  checker.methods.push(MethodCall {
+    receiver,
+    ident: ident.to_string(),
+    args,
+    ret_type: ret_type.clone(),
+    caller: checker.generic_fn.clone(),
+    span,
+  });
+  ret_type
+}
+
+/// The type of a call of the generic function `ident`, with its generic
+/// parameters bound to new type variables from `meta_id` on.
+pub fn check_generic_call(
+  checker: &mut Checker,
+  ident: &str,
+  types: Type,
+  meta_id: &mut usize,
+  span: Span,
+) -> Type {
+  let generic = match checker.generics.get(ident) {
+    Some(generic) => generic.clone(),
+    None => return types,
+  };
+  *meta_id = checker.substitutions.len();
+  let mut generics = HashMap::new();
+  for param in generic.params {
+    generics.insert(param.ident, checker.new_type_val());
+  }
+  checker.generic_calls.push(GenericCall {
+    callee: ident.to_string(),
+    caller: checker.generic_fn.clone(),
+    meta_id: *meta_id,
+    span,
+  });
+  instantiate(types, &generics)
+}
+
+/// The type of the method `ident` of `receiver`, including its `self`
+/// parameter.
+fn method_type(
+  checker: &Checker,
+  receiver: &Type,
+  ident: &str,
+  caller: &Option<String>,
+) -> Option<Type> {
+  match receiver {
+    Type::Generic(generic) => {
+      let bounds = caller
+        .as_ref()
+        .and_then(|caller| checker.generics.get(caller))
+        .and_then(|generic_fn| {
+          generic_fn
+            .params
+            .iter()
+            .find(|param| param.ident == *generic)
+        })
+        .map(|param| param.bounds.clone())
+        .unwrap_or_default();
+      let generics = HashMap::from([(String::from("Self"), receiver.clone())]);
+      bounds
+        .iter()
+        .filter_map(|bound| checker.interfaces.get(bound))
+        .flatten()
+        .find(|signature| signature.ident == ident)
+        .map(|signature| instantiate(signature.type_ident.clone(), &generics))
+    }
+    _ => checker
+      .scope
+      .get_sym_of(0, &method_ident(receiver, ident))
+      .ok()
+      .map(|sym| sym.1.types.clone()),
+  }
+}
+
+/// Resolves the method calls, now that the types of their receivers are known,
+/// and unifies their arguments and results with the methods.
+pub fn check_methods(checker: &mut Checker) {
+  for call in std::mem::take(&mut checker.methods) {
+    let receiver = Checker::coerce(checker.substitute(call.receiver.clone()));
+    if receiver == Type::Error {
+      continue;
+    }
+    // Literals take their default type before their methods are looked up.
+    let _ = checker.unify_types(call.receiver.clone(), receiver.clone());
+    let (params, ret_type) = match method_type(checker, &receiver, &call.ident, &call.caller) {
+      Some(Type::Function { params, ret_type })
+        if params.first().map(|param| param.ident.as_str()) == Some("self") =>
+      {
+        (params, ret_type)
+      }
+      _ => {
+        checker.handler.throw(
+          CompilerErrorKind::MissingMethod { ident: call.ident },
+          call.span,
+        );
+        continue;
+      }
+    };
+
+    let params = &params[1..];
+    for (_, span) in call.args.iter().skip(params.len()) {
+      checker
+        .handler
+        .throw(CompilerErrorKind::UnexpectedArgument, *span);
+    }
+    if call.args.len() < params.len() {
+      checker.handler.throw(
+        CompilerErrorKind::MissingParameters {
+          idents: params[call.args.len()..]
+            .iter()
+            .map(|param| param.ident.clone())
+            .collect(),
+        },
+        call.span,
+      );
+    }
+    for ((arg, span), param) in call.args.into_iter().zip(params) {
+      checker.unify(Constraint {
+        type1: arg,
+        type2: param.type_ident.clone(),
+        span: Some(span),
+        wrap: true,
+      });
+    }
+    checker.unify(Constraint {
+      type1: call.ret_type,
+      type2: *ret_type,
+      span: Some(call.span),
+      wrap: false,
+    });
+  }
+}
+
+/// Whether `types` implements `interface`, within the generic function
+/// `caller`.
+fn implements(checker: &Checker, types: &Type, interface: &str, caller: &Option<String>) -> bool {
+  match types {
+    Type::Error => true,
+    Type::Generic(generic) => caller
+      .as_ref()
+      .and_then(|caller| checker.generics.get(caller))
+      .and_then(|generic_fn| {
+        generic_fn
+          .params
+          .iter()
+          .find(|param| param.ident == *generic)
+      })
+      .is_some_and(|param| param.bounds.iter().any(|bound| bound == interface)),
+    _ => checker
+      .impls
+      .iter()
+      .any(|(impl_type, impl_interface)| impl_type == types && impl_interface == interface),
+  }
+}
+
+/// Checks the bounds of every call of a generic function, and declares an
+/// instance of each generic function for every set of types it is called
+/// with, directly or through other generic functions.
+pub fn check_instances(checker: &mut Checker) {
+  let type_args = |checker: &Checker, call: &GenericCall| -> Vec<Type> {
+    let count = checker.generics[&call.callee].params.len();
+    checker.substitutions[call.meta_id..call.meta_id + count].to_vec()
+  };
+
+  let mut work = Vec::new();
+  for call in checker.generic_calls.clone() {
+    let types = type_args(checker, &call);
+    let params = checker.generics[&call.callee].params.clone();
+    let mut satisfied = true;
+    for (param, types) in params.iter().zip(&types) {
+      for bound in &param.bounds {
+        if !implements(checker, types, bound, &call.caller) {
+          satisfied = false;
+          checker.handler.throw(
+            CompilerErrorKind::UnsatisfiedBound {
+              types: types.clone(),
+              interface: bound.clone(),
+            },
+            call.span,
+          );
+        }
+      }
+    }
+    if satisfied && call.caller.is_none() {
+      work.push((call.callee, types));
+    }
+  }
+
+  while let Some((ident, types)) = work.pop() {
+    let instances = checker.instances.entry(ident.clone()).or_default();
+    if types.contains(&Type::Error) || instances.iter().any(|(other, _)| *other == types) {
+      continue;
+    }
+    let generics: HashMap<String, Type> = checker.generics[&ident]
+      .params
+      .iter()
+      .map(|param| param.ident.clone())
+      .zip(types.iter().cloned())
+      .collect();
+    let sym = match checker.scope.get_sym_of(0, &ident) {
+      Ok(sym) => sym.1.clone(),
+      Err(_) => continue,
+    };
+    let sym = Symbol {
+      types: instantiate(sym.types, &generics),
+      ..sym
+    };
+    let index = match checker
+      .scope
+      .set_function_sym_of(0, &instance_ident(&ident, &types), sym)
+    {
+      Ok(index) => index,
+      Err(_) => continue,
+    };
+    checker
+      .instances
+      .entry(ident.clone())
+      .or_default()
+      .push((types, index));
+
+    for call in checker.generic_calls.clone() {
+      if call.caller.as_ref() == Some(&ident) {
+        let types = type_args(checker, &call)
+          .into_iter()
+          .map(|types| instantiate(types, &generics))
+          .collect();
+        work.push((call.callee, types));
+      }
+    }
+  }
+}
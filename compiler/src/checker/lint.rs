@@ -0,0 +1,513 @@
+use crate::allowed_lints;
+use crate::Checker;
+
+use std::collections::HashSet;
+use whistle_ast::Expr;
+use whistle_ast::Grammar;
+use whistle_ast::IdentImport;
+use whistle_ast::IdentTyped;
+use whistle_ast::IdentVal;
+use whistle_ast::Primary;
+use whistle_ast::ProgramStmt;
+use whistle_ast::Stmt;
+use whistle_ast::Unary;
+use whistle_common::Source;
+use whistle_common::Span;
+use whistle_common::Tip;
+use whistle_common::Warning;
+use whistle_common::WarningKind;
+
+pub struct Binding {
+  pub ident: String,
+  pub param: bool,
+  pub span: Span,
+  pub used: bool,
+  /// The last value written to the binding that nothing has read yet, and the
+  /// scope it was written in.
+  pub store: Option<(Span, usize)>,
+}
+
+pub struct LintScope {
+  pub id: usize,
+  pub bindings: Vec<Binding>,
+}
+
+/// State of the lint pass, which walks the checked program for bindings that
+/// are never read.
+#[derive(Default)]
+pub struct Linter {
+  pub scopes: Vec<LintScope>,
+  pub next_scope: usize,
+  /// Top level functions and imports, reported if nothing refers to them.
+  pub globals: Vec<(String, WarningKind, Span)>,
+  pub used: HashSet<String>,
+  /// The function being linted, whose calls to itself don't count as uses.
+  pub function: Option<String>,
+  /// Whether the function contains raw code, which may use locals by index.
+  pub raw: bool,
+  /// Source ranges and the lints `#(allow)` silences within them.
+  pub allows: Vec<(Span, Vec<String>)>,
+  pub warnings: Vec<Warning>,
+}
+
+impl Linter {
+  pub fn warn(&mut self, kind: WarningKind, span: Span) {
+    self.warnings.push(Warning { kind, span })
+  }
+
+  pub fn allow(&mut self, tip: &Tip, span: Span) {
+    let lints = allowed_lints(tip).map(String::from).collect();
+    self.allows.push((span, lints))
+  }
+
+  pub fn is_allowed(&self, warning: &Warning) -> bool {
+    let lint = warning.kind.lint();
+    self.allows.iter().any(|(span, lints)| {
+      span.start <= warning.span.start
+        && warning.span.start <= span.end
+        && lints
+          .iter()
+          .any(|allowed| allowed == lint || allowed == "unused" && lint.starts_with("unused_"))
+    })
+  }
+
+  pub fn enter_scope(&mut self) {
+    self.scopes.push(LintScope {
+      id: self.next_scope,
+      bindings: Vec::new(),
+    });
+    self.next_scope += 1;
+  }
+
+  pub fn exit_scope(&mut self) {
+    let scope = self.scopes.pop().unwrap();
+    for binding in scope.bindings {
+      if !binding.used {
+        if !self.raw && !binding.ident.starts_with('_') {
+          let kind = if binding.param {
+            WarningKind::UnusedParameter(binding.ident)
+          } else {
+            WarningKind::UnusedVariable(binding.ident)
+          };
+          self.warn(kind, binding.span);
+        }
+      } else if let Some((span, _)) = binding.store {
+        self.warn(WarningKind::DeadStore(binding.ident), span);
+      }
+    }
+    // Whether a store in a nested block is read depends on the path taken out
+    // of it, so it is no longer tracked.
+    for binding in self.bindings_mut() {
+      if matches!(binding.store, Some((_, id)) if id == scope.id) {
+        binding.store = None;
+      }
+    }
+  }
+
+  pub fn bindings_mut(&mut self) -> impl Iterator<Item = &mut Binding> {
+    self
+      .scopes
+      .iter_mut()
+      .flat_map(|scope| scope.bindings.iter_mut())
+  }
+
+  pub fn get_binding(&mut self, ident: &str) -> Option<&mut Binding> {
+    self
+      .scopes
+      .iter_mut()
+      .rev()
+      .flat_map(|scope| scope.bindings.iter_mut().rev())
+      .find(|binding| binding.ident == ident)
+  }
+
+  pub fn declare(&mut self, ident: &str, param: bool, span: Span, stored: bool) {
+    let outer = &self.scopes[..self.scopes.len() - 1];
+    if outer
+      .iter()
+      .any(|scope| scope.bindings.iter().any(|binding| binding.ident == ident))
+    {
+      self.warn(WarningKind::ShadowedBinding(ident.to_string()), span);
+    }
+    let scope = self.scopes.last_mut().unwrap();
+    let store = stored.then_some((span, scope.id));
+    scope.bindings.push(Binding {
+      ident: ident.to_string(),
+      param,
+      span,
+      used: false,
+      store,
+    });
+  }
+
+  pub fn read(&mut self, ident: &str) {
+    let recursive = self.function.as_deref() == Some(ident);
+    match self.get_binding(ident) {
+      Some(binding) => {
+        binding.used = true;
+        binding.store = None;
+      }
+      None if !recursive => {
+        self.used.insert(ident.to_string());
+      }
+      None => {}
+    }
+  }
+
+  pub fn write(&mut self, ident: &str, span: Span) {
+    let id = self.scopes.last().map_or(0, |scope| scope.id);
+    let dead = match self.get_binding(ident) {
+      Some(binding) => {
+        let dead = match binding.store {
+          Some((store, store_id)) if store_id == id => Some(store),
+          _ => None,
+        };
+        binding.store = Some((span, id));
+        dead
+      }
+      None => None,
+    };
+    if let Some(store) = dead {
+      self.warn(WarningKind::DeadStore(ident.to_string()), store);
+    }
+  }
+
+  /// Forgets every pending store, as a jump may carry them to a read.
+  pub fn jump(&mut self) {
+    for binding in self.bindings_mut() {
+      binding.store = None;
+    }
+  }
+}
+
+/// Reports unused bindings, shadowing and stores that are never read, then
+/// drops every warning an `#(allow)` tip covers. Only declarations in the root
+/// file are linted, not the std or imported files.
+pub fn lint_all(checker: &mut Checker, grammar: &Grammar) {
+  let mut linter = Linter::default();
+  for program in grammar {
+    if Source::find(&checker.sources, program.span().start) == Some(0) {
+      lint_program(&mut linter, program);
+    }
+  }
+  for (ident, kind, span) in std::mem::take(&mut linter.globals) {
+    if !linter.used.contains(&ident) {
+      linter.warn(kind, span);
+    }
+  }
+  checker.handler.warnings.append(&mut linter.warnings);
+  checker
+    .handler
+    .warnings
+    .retain(|warning| !linter.is_allowed(warning));
+}
+
+pub fn lint_program(linter: &mut Linter, program: &ProgramStmt) {
+  match program {
+    ProgramStmt::Import { idents, .. } | ProgramStmt::Builtin { idents, .. } => {
+      lint_imports(linter, idents)
+    }
+    ProgramStmt::Extern { tips, span, .. } | ProgramStmt::StructDecl { tips, span, .. } => {
+      lint_decl_tips(linter, tips, *span)
+    }
+    ProgramStmt::FunctionDecl {
+      tips,
+      export,
+      ident,
+      params,
+      stmt,
+      span,
+      ..
+    } => lint_fn(linter, tips, *export, ident, params, stmt, *span),
+    // Methods are called through their receiver, not by name.
+    ProgramStmt::ImplDecl { fns, .. } => {
+      for program in fns {
+        if let ProgramStmt::FunctionDecl {
+          tips,
+          ident,
+          params,
+          stmt,
+          span,
+          ..
+        } = program
+        {
+          lint_fn(linter, tips, true, ident, params, stmt, *span);
+        }
+      }
+    }
+    ProgramStmt::ValDecl { val, .. } | ProgramStmt::VarDecl { val, .. } => lint_expr(linter, val),
+    ProgramStmt::Tip { tip, .. } if tip.ident == "allow" => linter.allow(
+      tip,
+      Span {
+        start: 0,
+        end: usize::MAX,
+      },
+    ),
+    _ => {}
+  }
+}
+
+pub fn lint_imports(linter: &mut Linter, idents: &[IdentImport]) {
+  for import in idents {
+    let ident = import.as_ident.as_ref().unwrap_or(&import.ident);
+    linter.globals.push((
+      ident.clone(),
+      WarningKind::UnusedImport(ident.clone()),
+      import.span,
+    ));
+  }
+}
+
+pub fn lint_decl_tips(linter: &mut Linter, tips: &[Tip], span: Span) {
+  for tip in tips.iter().filter(|tip| tip.ident == "allow") {
+    linter.allow(tip, span);
+  }
+}
+
+pub fn lint_fn(
+  linter: &mut Linter,
+  tips: &[Tip],
+  export: bool,
+  ident: &str,
+  params: &[IdentTyped],
+  stmts: &[Stmt],
+  span: Span,
+) {
+  lint_decl_tips(linter, tips, span);
+  // Entry points are kept by the tree shaker without being referred to.
+  let entry = ident == "main"
+    || ident == "_start"
+    || tips
+      .iter()
+      .any(|tip| tip.ident == "export" || tip.ident == "start");
+  if !export && !entry {
+    linter.globals.push((
+      ident.to_string(),
+      WarningKind::UnusedFunction(ident.to_string()),
+      span,
+    ));
+  }
+
+  linter.function = Some(ident.to_string());
+  linter.raw = stmts
+    .iter()
+    .any(|stmt| matches!(stmt, Stmt::Tip { tip, .. } if tip.ident != "allow"));
+  linter.enter_scope();
+  // The receiver of a method is part of its signature, used or not.
+  for param in params.iter().filter(|param| param.ident != "self") {
+    linter.declare(&param.ident, true, param.span.unwrap_or(span), false);
+  }
+  lint_stmts(linter, stmts);
+  linter.exit_scope();
+  linter.function = None;
+  linter.raw = false;
+}
+
+pub fn lint_stmts(linter: &mut Linter, stmts: &[Stmt]) {
+  linter.enter_scope();
+  for stmt in stmts {
+    // An allow tip in a block covers the rest of it.
+    if let Stmt::Tip { tip, span } = stmt {
+      if tip.ident == "allow" {
+        let end = stmts.last().map_or(span.end, |last| last.span().end);
+        linter.allow(
+          tip,
+          Span {
+            start: span.start,
+            end,
+          },
+        );
+      }
+    }
+    lint_stmt(linter, stmt);
+  }
+  linter.exit_scope();
+}
+
+pub fn lint_stmt(linter: &mut Linter, stmt: &Stmt) {
+  match stmt {
+    Stmt::If {
+      cond,
+      then_stmt,
+      else_stmt,
+      ..
+    } => {
+      lint_expr(linter, cond);
+      lint_stmts(linter, then_stmt);
+      if let Some(else_stmt) = else_stmt {
+        lint_stmts(linter, else_stmt);
+      }
+    }
+    Stmt::While { cond, do_stmt, .. } => {
+      lint_expr(linter, cond);
+      lint_stmts(linter, do_stmt);
+    }
+    Stmt::Continue { .. } | Stmt::Break { .. } => linter.jump(),
+    Stmt::Return { ret_type, .. } => {
+      if let Some(expr) = ret_type {
+        lint_expr(linter, expr);
+      }
+    }
+    Stmt::VarDecl {
+      ident_typed,
+      val,
+      span,
+    } => {
+      lint_expr(linter, val);
+      linter.declare(&ident_typed.ident, false, *span, true);
+    }
+    Stmt::ValDecl {
+      ident_typed,
+      val,
+      span,
+    } => {
+      lint_expr(linter, val);
+      linter.declare(&ident_typed.ident, false, *span, false);
+    }
+    Stmt::TupleDecl {
+      mutable,
+      idents,
+      val,
+      span,
+    } => {
+      lint_expr(linter, val);
+      for ident in idents {
+        linter.declare(&ident.ident, false, ident.span.unwrap_or(*span), *mutable);
+      }
+    }
+    Stmt::Block { stmts, .. } => lint_stmts(linter, stmts),
+    Stmt::Tip { .. } => {}
+    Stmt::Expr { expr, .. } => lint_expr(linter, expr),
+    Stmt::Assign { lhs, rhs, span } => {
+      lint_expr(linter, rhs);
+      match lhs {
+        Expr::Unary {
+          unary:
+            Unary::Primary {
+              prim: Primary::IdentVal { ident, prim, .. },
+              ..
+            },
+          ..
+        } if prim.is_empty() => linter.write(ident, *span),
+        // Storing into a field or element reads the variable holding it.
+        lhs => lint_expr(linter, lhs),
+      }
+    }
+  }
+}
+
+pub fn lint_expr(linter: &mut Linter, expr: &Expr) {
+  match expr {
+    Expr::Unary { unary, .. } => lint_unary(linter, unary),
+    Expr::Binary { lhs, rhs, .. } => {
+      lint_expr(linter, lhs);
+      lint_expr(linter, rhs);
+    }
+    Expr::Cond {
+      cond,
+      then_expr,
+      else_expr,
+      ..
+    } => {
+      lint_expr(linter, cond);
+      lint_expr(linter, then_expr);
+      lint_expr(linter, else_expr);
+    }
+    Expr::Cast { expr, .. } | Expr::Try { expr, .. } | Expr::Err { expr, .. } => {
+      lint_expr(linter, expr)
+    }
+  }
+}
+
+pub fn lint_unary(linter: &mut Linter, unary: &Unary) {
+  match unary {
+    Unary::Primary { prim, .. } => lint_primary(linter, prim),
+    Unary::UnaryOp { expr, .. } => lint_unary(linter, expr),
+  }
+}
+
+pub fn lint_primary(linter: &mut Linter, prim: &Primary) {
+  match prim {
+    Primary::Literal { .. } => {}
+    Primary::IdentVal { ident, prim, .. } => {
+      linter.read(ident);
+      for val in prim {
+        match val {
+          IdentVal::Arguments { args, named, .. } => {
+            args.iter().for_each(|arg| lint_expr(linter, arg));
+            named.iter().for_each(|(_, arg)| lint_expr(linter, arg));
+          }
+          IdentVal::Index { expr, .. } => lint_expr(linter, expr),
+          IdentVal::Selector { .. } | IdentVal::Slice { .. } => {}
+        }
+      }
+    }
+    Primary::Grouping { group, .. } => lint_expr(linter, group),
+    Primary::Array { exprs, .. } => exprs.iter().for_each(|expr| lint_expr(linter, expr)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::check;
+  use crate::test_support::warnings;
+  use whistle_common::WarningKind;
+
+  #[test]
+  fn unused_shadowed_and_dead_stores() {
+    let (checker, _) = check(
+      "builtin @sys { printInt, printFloat }
+      fn helper(a: i32, _b: i32): i32 {
+        return 1
+      }
+      export fn main(x: i32) {
+        var y = 0
+        y = x
+        val z = 1
+        if true {
+          val x = y
+          printInt(x)
+        }
+        y = 2
+      }",
+    );
+    assert_eq!(
+      warnings(&checker.handler),
+      vec![
+        WarningKind::UnusedParameter(String::from("a")),
+        WarningKind::DeadStore(String::from("y")),
+        WarningKind::ShadowedBinding(String::from("x")),
+        WarningKind::DeadStore(String::from("y")),
+        WarningKind::UnusedVariable(String::from("z")),
+        WarningKind::UnusedImport(String::from("printFloat")),
+        WarningKind::UnusedFunction(String::from("helper")),
+      ]
+    );
+  }
+
+  #[test]
+  fn allow_tips() {
+    let (checker, _) = check(
+      "#(allow) unused_function
+      fn helper() {}
+      #(allow) unused
+      export fn main(a: i32) {
+        val b = 1
+        #(allow) shadowed_binding, no_such_lint
+        val a = 2
+      }",
+    );
+    assert_eq!(
+      warnings(&checker.handler),
+      vec![WarningKind::UnknownLint(String::from("no_such_lint"))]
+    );
+  }
+
+  #[test]
+  fn std_imports() {
+    let (checker, _) = check(
+      "import \"@std/io\"
+      export fn main() {}",
+    );
+    assert_eq!(warnings(&checker.handler), vec![]);
+  }
+}
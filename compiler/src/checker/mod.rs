@@ -1,29 +1,37 @@
 use whistle_ast::Grammar;
-use whistle_ast::Type;
 
 mod checker;
+mod consts;
 mod expr;
+mod flow;
+mod interface;
+mod lint;
 mod program;
 mod stmt;
 
 pub use checker::*;
+pub use consts::*;
 pub use expr::*;
+pub use flow::*;
+pub use interface::*;
+pub use lint::*;
 pub use program::*;
 pub use stmt::*;
 
 pub fn check_all(checker: &mut Checker, grammar: &mut Grammar) {
   checker.scope.enter_scope();
-  for program in grammar {
+  for program in grammar.iter_mut() {
     check_program(checker, program);
   }
   checker.scope.exit_scope();
   for constraint in checker.constraints.clone() {
     checker.unify(constraint)
   }
-  for (i, substitution) in checker.substitutions.clone().iter().enumerate() {
-    checker.substitutions[i] = Checker::coerce(checker.substitute(substitution.clone()));
-    if Type::Error == checker.substitutions[i] {
-      println!("Could not infer type!")
-    }
-  }
+  check_methods(checker);
+  checker.default_types();
+  check_instances(checker);
+  check_casts(checker);
+  check_bindings(checker);
+  lint_all(checker, grammar);
+  fold_consts(checker, grammar);
 }
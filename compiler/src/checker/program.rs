@@ -1,10 +1,22 @@
 use crate::check_expr;
+use crate::check_flow;
+use crate::check_ident_type;
+use crate::check_impl;
+use crate::check_interface;
 use crate::check_stmts;
+use crate::is_literal_expr;
+use crate::tip_str;
 use crate::Checker;
+use crate::Flow;
+use crate::GenericFn;
+use crate::IndexedSymbol;
+use crate::IntrinsicKind;
 use crate::Symbol;
 
 use whistle_ast::Expr;
 use whistle_ast::IdentExternFn;
+use whistle_ast::IdentGeneric;
+use whistle_ast::IdentImport;
 use whistle_ast::IdentType;
 use whistle_ast::IdentTyped;
 use whistle_ast::ProgramStmt;
@@ -12,20 +24,60 @@ use whistle_ast::Stmt;
 use whistle_ast::Type;
 use whistle_common::CompilerErrorKind;
 use whistle_common::CompilerHandler;
+use whistle_common::Primitive;
 use whistle_common::Span;
+use whistle_common::Tip;
+use whistle_common::WarningKind;
 
 pub fn check_program(checker: &mut Checker, program: &mut ProgramStmt) {
   match program {
-    ProgramStmt::Extern { idents, span, .. } => check_extern(checker, idents, span),
+    ProgramStmt::Extern {
+      idents, tips, span, ..
+    } => check_extern(checker, idents, tips, span),
+    ProgramStmt::Builtin {
+      module,
+      idents,
+      span,
+    } => check_builtin(checker, module, idents, span),
     ProgramStmt::FunctionDecl {
-      export,
+      tips,
       ident,
+      generics,
       params,
       ret_type,
       stmt,
       span,
       ..
-    } => check_fn(checker, export, ident, params, ret_type, stmt, span),
+    } => check_fn(
+      checker,
+      FnDeclRef {
+        tips,
+        ident,
+        generics,
+        params,
+        ret_type,
+        stmts: stmt,
+        span: *span,
+      },
+    ),
+    ProgramStmt::StructDecl {
+      tips,
+      ident,
+      params,
+      span,
+      ..
+    } => check_struct(checker, tips, ident, params, *span),
+    ProgramStmt::InterfaceDecl {
+      ident,
+      methods,
+      span,
+    } => check_interface(checker, ident, methods, *span),
+    ProgramStmt::ImplDecl {
+      interface,
+      types,
+      fns,
+      span,
+    } => check_impl(checker, interface, types, fns, *span),
     ProgramStmt::ValDecl {
       ident_typed,
       val,
@@ -36,43 +88,98 @@ pub fn check_program(checker: &mut Checker, program: &mut ProgramStmt) {
       val,
       span,
     } => check_var(checker, ident_typed, val, span),
+    ProgramStmt::Tip { tip, span } => check_decl_tip(checker, tip, *span),
     _ => checker
       .handler
       .throw(CompilerErrorKind::Unimplemented, program.span()),
   }
 }
 
-pub fn check_fn(
-  checker: &mut Checker,
-  _export: &mut bool,
-  ident: &mut str,
-  params: &mut Vec<IdentTyped>,
-  ret_type: &mut IdentType,
-  stmts: &mut Vec<Stmt>,
-  span: &mut Span,
-) {
+/// A function declaration, borrowed from its `ProgramStmt` to be checked.
+pub struct FnDeclRef<'a> {
+  pub tips: &'a [Tip],
+  pub ident: &'a str,
+  pub generics: &'a [IdentGeneric],
+  pub params: &'a mut Vec<IdentTyped>,
+  pub ret_type: &'a IdentType,
+  pub stmts: &'a mut Vec<Stmt>,
+  pub span: Span,
+}
+
+pub fn check_fn(checker: &mut Checker, decl: FnDeclRef) {
+  let FnDeclRef {
+    tips,
+    ident,
+    generics,
+    params,
+    ret_type,
+    stmts,
+    span,
+  } = decl;
   if let Err(err) = checker.scope.set_function_sym(
     ident,
     Symbol {
       global: true,
       mutable: false,
+      intrinsic: false,
       types: Type::Function {
         params: IdentTyped::vec_to_type(params),
         ret_type: Box::new(ret_type.to_type()),
       },
     },
   ) {
-    checker.handler.throw(err, span.clone());
+    checker.handler.throw(err, span);
   }
 
+  checker.decl = Some(ident.to_string());
+  for tip in tips {
+    match tip.ident.as_str() {
+      "export" | "cold" => {}
+      "start" => {
+        if !params.is_empty() || ret_type.to_type() != Type::Primitive(Primitive::None) {
+          checker
+            .handler
+            .throw(CompilerErrorKind::InvalidStartFunction, span);
+        }
+      }
+      _ => check_decl_tip(checker, tip, span),
+    }
+  }
+  checker.decl = None;
+
+  check_defaults(checker, ident, params);
+  if !generics.is_empty() {
+    for bound in generics.iter().flat_map(|generic| &generic.bounds) {
+      if !checker.interfaces.contains_key(bound) {
+        checker.handler.throw(
+          CompilerErrorKind::UnknownInterface {
+            ident: bound.clone(),
+          },
+          span,
+        );
+      }
+    }
+    checker.generics.insert(
+      ident.to_string(),
+      GenericFn {
+        params: generics.to_vec(),
+        scopes: 0,
+      },
+    );
+    checker.generic_fn = Some(ident.to_string());
+  }
+  let first_scope = checker.scope.scopes.len();
   checker.scope.enter_scope();
 
   for param in params {
+    let types = param.type_ident.to_type();
+    checker.bindings.push((types, param.span.unwrap_or(span)));
     if let Err(err) = checker.scope.set_local_sym(
       &param.ident,
       Symbol {
         global: false,
         mutable: true,
+        intrinsic: false,
         types: param.type_ident.to_type(),
       },
     ) {
@@ -80,20 +187,105 @@ pub fn check_fn(
     }
   }
 
+  checker.ret_type = Some(ret_type.to_type());
   let ret = check_stmts(checker, stmts);
-  let span = stmts[stmts.len() - 1].span();
-  checker.constraint(ret, ret_type.to_type(), Some(span));
+  checker.ret_type = None;
+
+  // A trailing expression is the value of the function.
+  match (check_flow(checker, stmts), stmts.last()) {
+    (Flow::Next, Some(Stmt::Expr { span, .. })) => {
+      checker.flow(ret, ret_type.to_type(), Some(*span))
+    }
+    (Flow::Next, _) if !returns_nothing(&ret_type.to_type()) => checker
+      .handler
+      .throw(CompilerErrorKind::MissingReturn, span),
+    _ => {}
+  }
 
   checker.scope.exit_scope();
+  if let Some(generic) = checker.generics.get_mut(ident) {
+    generic.scopes = checker.scope.scopes.len() - first_scope;
+  }
+  checker.generic_fn = None;
 }
 
-pub fn check_extern(checker: &mut Checker, idents: &mut Vec<IdentExternFn>, span: &mut Span) {
+pub fn check_struct(
+  checker: &mut Checker,
+  tips: &[Tip],
+  ident: &str,
+  params: &mut [IdentTyped],
+  span: Span,
+) {
+  for tip in tips {
+    check_decl_tip(checker, tip, span);
+  }
+  let props = params.iter().map(IdentTyped::to_type).collect();
+  if checker.structs.insert(ident.to_string(), props).is_some() {
+    checker
+      .handler
+      .throw(CompilerErrorKind::SymbolRedifinition, span);
+  }
+}
+
+/// Whether a function returning `types` may run off its end without a value.
+/// A function returning `none!E` then succeeds.
+pub fn returns_nothing(types: &Type) -> bool {
+  match types {
+    Type::Primitive(Primitive::None) => true,
+    Type::Result(ok, _) => **ok == Type::Primitive(Primitive::None),
+    _ => false,
+  }
+}
+
+/// Checks the default values of `params` and records them for calls to `ident`.
+/// They are copied into each call that leaves them out, so they may only be
+/// built from literals.
+pub fn check_defaults(checker: &mut Checker, ident: &str, params: &mut [IdentTyped]) {
+  if params.iter().all(|param| param.default.is_none()) {
+    return;
+  }
+  for param in params.iter_mut() {
+    if let Some(default) = &mut param.default {
+      let types = check_expr(checker, default);
+      checker.flow(types, param.type_ident.to_type(), Some(default.span()));
+      if !is_literal_expr(default) {
+        checker
+          .handler
+          .throw(CompilerErrorKind::ExpectedConstExpr, default.span());
+      }
+    }
+  }
+  let defaults = params.iter().map(|param| param.default.clone()).collect();
+  checker.defaults.insert(ident.to_string(), defaults);
+}
+
+pub fn check_extern(
+  checker: &mut Checker,
+  idents: &mut Vec<IdentExternFn>,
+  tips: &[Tip],
+  span: &mut Span,
+) {
+  for tip in tips {
+    match tip.ident.as_str() {
+      "import_module" if tip_str(tip).is_none() => checker.handler.throw(
+        CompilerErrorKind::MissingTipValue {
+          ident: tip.ident.clone(),
+        },
+        *span,
+      ),
+      "import_module" => {}
+      _ => check_decl_tip(checker, tip, *span),
+    }
+  }
+
   for external_fn in idents {
+    check_defaults(checker, &external_fn.ident, &mut external_fn.params);
     match checker.scope.set_function_sym(
       &external_fn.ident,
       Symbol {
         global: true,
         mutable: false,
+        intrinsic: false,
         types: Type::Function {
           params: IdentTyped::vec_to_type(&external_fn.params),
           ret_type: Box::new(external_fn.ret_type.to_type()),
@@ -109,30 +301,82 @@ pub fn check_extern(checker: &mut Checker, idents: &mut Vec<IdentExternFn>, span
   }
 }
 
+pub fn check_builtin(
+  checker: &mut Checker,
+  module: &mut str,
+  idents: &mut Vec<IdentImport>,
+  _span: &mut Span,
+) {
+  for import in idents {
+    let intrinsic = match checker.builtins.get(module, &import.ident) {
+      Some(intrinsic) => intrinsic.clone(),
+      None => {
+        checker
+          .handler
+          .throw(CompilerErrorKind::UnknownBuiltin, import.span);
+        continue;
+      }
+    };
+    let ident = import.as_ident.as_ref().unwrap_or(&import.ident);
+    let mut sym = Symbol {
+      global: true,
+      mutable: false,
+      intrinsic: false,
+      types: intrinsic.types(),
+    };
+    // Inline intrinsics are expanded at the call site and take no function
+    // index, their symbol indexes `checker.intrinsics` instead.
+    let res = match intrinsic.kind {
+      IntrinsicKind::Import { .. } => checker.scope.set_function_sym(ident, sym).map(|_| ()),
+      IntrinsicKind::Inline(instructions) => {
+        sym.intrinsic = true;
+        let idx = checker.intrinsics.len() as u32;
+        checker.intrinsics.push(instructions);
+        checker.scope.set_sym(ident, IndexedSymbol(idx, sym))
+      }
+    };
+    if let Err(err) = res {
+      checker.handler.throw(err, import.span);
+    }
+  }
+}
+
+pub fn check_decl_tip(checker: &mut Checker, tip: &Tip, span: Span) {
+  match checker.tips.get(&tip.ident) {
+    Some(handler) => handler.check(checker, tip, span),
+    None => checker
+      .handler
+      .warn(WarningKind::UnknownTip(tip.ident.clone()), span),
+  }
+}
+
 pub fn check_val(
   checker: &mut Checker,
   ident_typed: &mut IdentTyped,
   expr: &mut Expr,
   span: &mut Span,
 ) {
-  let ident_type = checker.new_type_val();
+  let ident_type = checker.new_type_val_at(*span);
 
   if let Err(err) = checker.scope.set_global_sym(
     &ident_typed.ident,
     Symbol {
       global: true,
       mutable: false,
+      intrinsic: false,
       types: ident_type.clone(),
     },
   ) {
     checker.handler.throw(err, span.clone());
   };
 
+  checker.bindings.push((ident_type.clone(), *span));
+  check_ident_type(checker, &mut ident_typed.type_ident);
   let expr_type = check_expr(checker, expr);
-  checker.constraint(ident_type.clone(), expr_type, Some(expr.span()));
   if Type::Default != ident_typed.type_ident.to_type() {
-    checker.constraint(ident_type, ident_typed.type_ident.to_type(), None);
+    checker.constraint(ident_type.clone(), ident_typed.type_ident.to_type(), None);
   }
+  checker.flow(expr_type, ident_type, Some(expr.span()));
 }
 
 pub fn check_var(
@@ -141,22 +385,25 @@ pub fn check_var(
   expr: &mut Expr,
   span: &mut Span,
 ) {
-  let ident_type = checker.new_type_val();
+  let ident_type = checker.new_type_val_at(*span);
 
   if let Err(err) = checker.scope.set_global_sym(
     &ident_typed.ident,
     Symbol {
       global: true,
       mutable: true,
+      intrinsic: false,
       types: ident_type.clone(),
     },
   ) {
     checker.handler.throw(err, span.clone());
   };
 
+  checker.bindings.push((ident_type.clone(), *span));
+  check_ident_type(checker, &mut ident_typed.type_ident);
   let expr_type = check_expr(checker, expr);
-  checker.constraint(ident_type.clone(), expr_type, Some(expr.span()));
   if Type::Default != ident_typed.type_ident.to_type() {
-    checker.constraint(ident_type, ident_typed.type_ident.to_type(), None);
+    checker.constraint(ident_type.clone(), ident_typed.type_ident.to_type(), None);
   }
+  checker.flow(expr_type, ident_type, Some(expr.span()));
 }
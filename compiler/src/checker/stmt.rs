@@ -1,18 +1,25 @@
 use crate::check_bool_expr;
 use crate::check_expr;
+use crate::check_ident_val;
+use crate::narrowed_ident;
 use crate::Checker;
 use crate::IndexedSymbol;
 use crate::Symbol;
 use whistle_common::CompilerErrorKind;
 
 use whistle_ast::Expr;
+use whistle_ast::IdentType;
 use whistle_ast::IdentTyped;
+use whistle_ast::IdentVal;
+use whistle_ast::Primary;
 use whistle_ast::Primitive;
 use whistle_ast::Stmt;
 use whistle_ast::Type;
+use whistle_ast::Unary;
 use whistle_common::CompilerHandler;
 use whistle_common::Span;
 use whistle_common::Tip;
+use whistle_common::WarningKind;
 
 pub fn check_stmt(checker: &mut Checker, stmt: &mut Stmt) -> Type {
   match stmt {
@@ -27,6 +34,12 @@ pub fn check_stmt(checker: &mut Checker, stmt: &mut Stmt) -> Type {
       val,
       span,
     } => check_var_decl(checker, ident_typed, val, span),
+    Stmt::TupleDecl {
+      mutable,
+      idents,
+      val,
+      span,
+    } => check_tuple_decl(checker, *mutable, idents, val, span),
     Stmt::If {
       cond,
       then_stmt,
@@ -34,16 +47,14 @@ pub fn check_stmt(checker: &mut Checker, stmt: &mut Stmt) -> Type {
       ..
     } => check_if(checker, cond, then_stmt, else_stmt),
     Stmt::Tip { tip, span } => check_tip(checker, tip, span),
-    Stmt::Assign { ident, rhs, span } => check_assign(checker, rhs, ident, span),
+    Stmt::Assign { lhs, rhs, .. } => {
+      check_assign(checker, lhs, rhs);
+      Type::Primitive(Primitive::None)
+    }
     Stmt::Expr { expr, .. } => check_expr_stmt(checker, expr),
     Stmt::Block { stmts, .. } => check_block(checker, stmts),
-    Stmt::Return { ret_type, .. } => check_return(checker, ret_type),
-    _ => {
-      checker
-        .handler
-        .throw(CompilerErrorKind::Unimplemented, stmt.span());
-      Type::Error
-    }
+    Stmt::Return { ret_type, span } => check_return(checker, ret_type, span),
+    Stmt::Break { span } | Stmt::Continue { span } => check_loop_control(checker, span),
   }
 }
 
@@ -59,7 +70,9 @@ pub fn check_stmts(checker: &mut Checker, stmts: &mut Vec<Stmt>) -> Type {
 
 pub fn check_while(checker: &mut Checker, cond: &mut Expr, do_stmt: &mut Vec<Stmt>) -> Type {
   check_bool_expr(checker, cond);
+  checker.loops += 1;
   check_stmts(checker, do_stmt);
+  checker.loops -= 1;
   Type::Primitive(Primitive::None)
 }
 
@@ -70,15 +83,53 @@ pub fn check_if(
   else_stmt: &mut Option<Vec<Stmt>>,
 ) -> Type {
   check_bool_expr(checker, cond);
-  check_stmts(checker, then_stmt);
+  let narrowed = narrowed_ident(cond);
+  match &narrowed {
+    Some((ident, id, true)) => check_narrowed(checker, ident, *id, then_stmt),
+    _ => check_stmts(checker, then_stmt),
+  };
 
   if let Some(stmt) = else_stmt {
-    check_stmts(checker, stmt);
+    match &narrowed {
+      Some((ident, id, false)) => check_narrowed(checker, ident, *id, stmt),
+      _ => check_stmts(checker, stmt),
+    };
   }
   Type::Primitive(Primitive::None)
 }
 
-pub fn check_tip(_checker: &mut Checker, _tip: &mut Tip, _span: &mut Span) -> Type {
+/// Checks a branch where the option `ident` is known to hold a value, which
+/// it is bound to instead. The binding is a read-only copy of the value.
+pub fn check_narrowed(
+  checker: &mut Checker,
+  ident: &str,
+  id: usize,
+  stmts: &mut Vec<Stmt>,
+) -> Type {
+  checker.scope.enter_scope();
+  if checker.scope.get_sym(ident).is_ok() {
+    let _ = checker.scope.set_local_sym(
+      ident,
+      Symbol {
+        global: false,
+        mutable: false,
+        intrinsic: false,
+        types: Type::Var(id + 1),
+      },
+    );
+  }
+  let ret_type = check_stmts(checker, stmts);
+  checker.scope.exit_scope();
+  ret_type
+}
+
+pub fn check_tip(checker: &mut Checker, tip: &mut Tip, span: &mut Span) -> Type {
+  match checker.tips.get(&tip.ident) {
+    Some(handler) => handler.check(checker, tip, *span),
+    None => checker
+      .handler
+      .warn(WarningKind::UnknownTip(tip.ident.clone()), *span),
+  }
   Type::Primitive(Primitive::None)
 }
 
@@ -88,23 +139,26 @@ pub fn check_val_decl(
   expr: &mut Expr,
   span: &mut Span,
 ) -> Type {
-  let ident_type = checker.new_type_val();
+  let ident_type = checker.new_type_val_at(*span);
   if let Err(err) = checker.scope.set_local_sym(
     &ident.ident,
     Symbol {
       global: false,
       mutable: false,
+      intrinsic: false,
       types: ident_type.clone(),
     },
   ) {
     checker.handler.throw(err, span.clone());
   };
 
+  checker.bindings.push((ident_type.clone(), *span));
+  check_ident_type(checker, &mut ident.type_ident);
   let expr_type = check_expr(checker, expr);
-  checker.constraint(ident_type.clone(), expr_type, Some(expr.span()));
   if Type::Default != ident.type_ident.to_type() {
-    checker.constraint(ident_type, ident.type_ident.to_type(), None);
+    checker.constraint(ident_type.clone(), ident.type_ident.to_type(), None);
   }
+  checker.flow(expr_type, ident_type, Some(expr.span()));
   Type::Primitive(Primitive::None)
 }
 
@@ -114,21 +168,103 @@ pub fn check_var_decl(
   expr: &mut Expr,
   span: &mut Span,
 ) -> Type {
-  let ident_type = checker.new_type_val();
+  let ident_type = checker.new_type_val_at(*span);
   if let Err(err) = checker.scope.set_local_sym(
     &ident.ident,
     Symbol {
       global: false,
       mutable: true,
+      intrinsic: false,
       types: ident_type.clone(),
     },
   ) {
     checker.handler.throw(err, span.clone());
   };
+  checker.bindings.push((ident_type.clone(), *span));
+  check_ident_type(checker, &mut ident.type_ident);
   let expr_type = check_expr(checker, expr);
-  checker.constraint(ident_type.clone(), expr_type, Some(expr.span()));
   if Type::Default != ident.type_ident.to_type() {
-    checker.constraint(ident_type, ident.type_ident.to_type(), None);
+    checker.constraint(ident_type.clone(), ident.type_ident.to_type(), None);
+  }
+  checker.flow(expr_type, ident_type, Some(expr.span()));
+  Type::Primitive(Primitive::None)
+}
+
+/// Reports the named values whose type can't be stored in a local or global,
+/// once types are known. Tuples are only returned by functions and have to be
+/// destructured, and `none` has no value to store.
+pub fn check_bindings(checker: &mut Checker) {
+  for (types, span) in checker.bindings.clone() {
+    let types = checker.substitute(types);
+    if matches!(
+      types,
+      Type::Tuple(_) | Type::Function { .. } | Type::Primitive(Primitive::None)
+    ) {
+      checker
+        .handler
+        .throw(CompilerErrorKind::UnstorableType { types }, span);
+    }
+  }
+}
+
+/// Checks the size expression of a `T[N]` annotation, which must be an integer.
+pub fn check_ident_type(checker: &mut Checker, ident_type: &mut IdentType) {
+  if let IdentType::Array {
+    size: Some(size), ..
+  } = ident_type
+  {
+    let size_type = check_expr(checker, size);
+    checker.constraint(
+      size_type,
+      Type::Primitive(Primitive::Int),
+      Some(size.span()),
+    );
+  }
+}
+
+pub fn check_tuple_decl(
+  checker: &mut Checker,
+  mutable: bool,
+  idents: &mut [IdentTyped],
+  expr: &mut Expr,
+  span: &mut Span,
+) -> Type {
+  let expr_type = check_expr(checker, expr);
+  let types = match checker.substitute(expr_type) {
+    Type::Tuple(types) if types.len() == idents.len() => types,
+    types => {
+      let err = CompilerErrorKind::TypeMismatch {
+        type1: Type::Tuple(
+          idents
+            .iter()
+            .map(|ident| ident.type_ident.to_type())
+            .collect(),
+        ),
+        type2: types,
+      };
+      checker.handler.throw(err, expr.span());
+      vec![Type::Error; idents.len()]
+    }
+  };
+
+  for (ident, types) in idents.iter().zip(types) {
+    checker
+      .bindings
+      .push((types.clone(), ident.span.unwrap_or(*span)));
+    if let Err(err) = checker.scope.set_local_sym(
+      &ident.ident,
+      Symbol {
+        global: false,
+        mutable,
+        intrinsic: false,
+        types: types.clone(),
+      },
+    ) {
+      checker.handler.throw(err, *span);
+    };
+    if Type::Default != ident.type_ident.to_type() {
+      checker.constraint(types, ident.type_ident.to_type(), ident.span);
+    }
   }
   Type::Primitive(Primitive::None)
 }
@@ -142,35 +278,78 @@ pub fn check_block(checker: &mut Checker, stmts: &mut Vec<Stmt>) -> Type {
   Type::Primitive(Primitive::None)
 }
 
-pub fn check_return(checker: &mut Checker, expr: &mut Option<Expr>) -> Type {
-  if let Some(expr) = expr {
-    return check_expr(checker, expr);
+pub fn check_return(checker: &mut Checker, expr: &mut Option<Expr>, span: &mut Span) -> Type {
+  let (types, span) = match expr {
+    Some(expr) => (check_expr(checker, expr), expr.span()),
+    None => (Type::Primitive(Primitive::None), *span),
+  };
+  match checker.ret_type.clone() {
+    Some(ret_type) => checker.flow(types.clone(), ret_type, Some(span)),
+    None => checker
+      .handler
+      .throw(CompilerErrorKind::ScopeNotInFunction, span),
   }
+  types
+}
 
+pub fn check_loop_control(checker: &mut Checker, span: &mut Span) -> Type {
+  if checker.loops == 0 {
+    checker
+      .handler
+      .throw(CompilerErrorKind::ScopeNotInLoop, *span);
+  }
   Type::Primitive(Primitive::None)
 }
 
-pub fn check_assign(
-  checker: &mut Checker,
-  expr: &mut Expr,
-  ident: &mut String,
-  span: &mut Span,
-) -> Type {
-  let sym = match checker.scope.get_sym(&ident) {
+/// Checks an assignment and returns the type of the assigned place, which is
+/// also the value of an assignment expression.
+pub fn check_assign(checker: &mut Checker, lhs: &mut Expr, rhs: &mut Expr) -> Type {
+  let expr_type = check_expr(checker, rhs);
+  let target = check_assign_target(checker, lhs);
+  checker.flow(expr_type, target.clone(), Some(rhs.span()));
+  target
+}
+
+/// Checks that `lhs` is a place that can be assigned: a variable, or a field or
+/// element reached from one. Fields and elements are only as mutable as the
+/// variable they are reached from.
+pub fn check_assign_target(checker: &mut Checker, lhs: &mut Expr) -> Type {
+  let lhs_span = lhs.span();
+  let (ident, prim, span) = match lhs {
+    Expr::Unary {
+      unary:
+        Unary::Primary {
+          prim: Primary::IdentVal { ident, prim, span },
+          ..
+        },
+      ..
+    } if prim
+      .iter()
+      .all(|val| matches!(val, IdentVal::Selector { .. } | IdentVal::Index { .. })) =>
+    {
+      (ident, prim, span)
+    }
+    _ => {
+      check_expr(checker, lhs);
+      checker
+        .handler
+        .throw(CompilerErrorKind::Unassignable, lhs_span);
+      return Type::Error;
+    }
+  };
+  let sym = match checker.scope.get_sym(ident) {
     Ok(sym) => sym.clone(),
     Err(err) => {
-      checker.handler.throw(err, span.clone());
+      checker.handler.throw(err, *span);
       IndexedSymbol(0, Symbol::default())
     }
   };
   if !sym.1.mutable {
     checker
       .handler
-      .throw(CompilerErrorKind::ImmutableAssign, span.clone())
+      .throw(CompilerErrorKind::ImmutableAssign, lhs_span);
   }
-  let expr_type = check_expr(checker, expr);
-  checker.constraint(expr_type, sym.1.types, Some(expr.span()));
-  Type::Primitive(Primitive::None)
+  check_ident_val(checker, ident, &sym, prim)
 }
 
 pub fn check_expr_stmt(checker: &mut Checker, expr: &mut Expr) -> Type {
@@ -1,37 +1,124 @@
+use crate::instantiate;
+use crate::Builtins;
 use crate::Checker;
+use crate::Function;
+use crate::GenericFn;
 use crate::IndexedSymbol;
+use crate::Inst;
 use crate::Memory;
 use crate::Module;
 use crate::ScopeContainer;
+use crate::TipHandler;
+use crate::TipHandlers;
 use whistle_common::CompilerErrorKind;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use whistle_ast::Type;
 use whistle_common::DiagnosticHandler;
+use whistle_common::Source;
+use whistle_common::TypedIdent;
+
+/// What a branch to an enclosing block does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+  /// Leaves the loop.
+  Break,
+  /// Starts the next iteration.
+  Continue,
+  Other,
+}
 
 pub struct Compiler {
   pub handler: DiagnosticHandler,
+  /// The files of the program, see [`whistle_common::Source`].
+  pub sources: Vec<Source>,
   pub scope: ScopeContainer,
   pub module: Module,
   pub memory: Memory,
   pub substitutions: Vec<Type>,
+  pub tips: TipHandlers,
+  pub builtins: Builtins,
+  pub intrinsics: Vec<Vec<Inst>>,
+  pub externs: HashSet<u32>,
+  pub validate: bool,
+  pub opt_level: u8,
+  /// Trap with a message on integer overflow and division by zero.
+  pub checked: bool,
+  pub panic_fn: Option<u32>,
+  /// Offsets of the panic messages written to memory.
+  pub panic_messages: HashMap<&'static str, u32>,
+  /// Keep each function's IR around after encoding, for `--emit=ir`.
+  pub emit_ir: bool,
+  pub ir: Vec<Function>,
+  /// The blocks enclosing the statement being compiled, innermost last.
+  pub labels: Vec<Label>,
+  /// The declared return type of the function being compiled.
+  pub ret_type: Option<Type>,
+  /// The properties of each struct, by name.
+  pub structs: HashMap<String, Vec<TypedIdent>>,
+  pub generic_fns: HashMap<String, GenericFn>,
+  /// The types each generic function is instantiated with, and the index of
+  /// each instance.
+  pub instances: HashMap<String, Vec<(Vec<Type>, u32)>>,
+  /// The types of the generic parameters of the instance being compiled.
+  pub generics: HashMap<String, Type>,
 }
 
 impl Compiler {
   pub fn new(checker: Checker) -> Self {
     Compiler {
       handler: checker.handler,
+      sources: checker.sources,
       scope: checker.scope,
       module: Module::new(),
       memory: Memory::new(),
       substitutions: checker.substitutions,
+      tips: checker.tips,
+      builtins: checker.builtins,
+      intrinsics: checker.intrinsics,
+      externs: HashSet::new(),
+      validate: true,
+      opt_level: 0,
+      checked: false,
+      panic_fn: None,
+      panic_messages: HashMap::new(),
+      emit_ir: false,
+      ir: Vec::new(),
+      labels: Vec::new(),
+      ret_type: None,
+      structs: checker.structs,
+      generic_fns: checker.generics,
+      instances: checker.instances,
+      generics: HashMap::new(),
     }
   }
 
+  pub fn register_tip<T: TipHandler + 'static>(&mut self, ident: &str, handler: T) {
+    self.tips.register(ident, handler)
+  }
+
   pub fn query_type(&self, types: Type) -> Type {
     if let Type::Var(i) = types {
-      return self.substitutions[i].clone();
+      return instantiate(self.substitutions[i].clone(), &self.generics);
     }
-    types
+    instantiate(types, &self.generics)
+  }
+
+  /// The properties of `types`, if it is a struct or names one.
+  pub fn props(&self, types: &Type) -> Option<Vec<TypedIdent>> {
+    match types {
+      Type::Struct(props) => Some(props.clone()),
+      Type::Ident(ident) => self.structs.get(ident).cloned(),
+      _ => None,
+    }
+  }
+
+  /// The relative depth of the innermost `label`, for `br`.
+  pub fn label_depth(&self, label: Label) -> Option<u32> {
+    let idx = self.labels.iter().rposition(|other| *other == label)?;
+    Some((self.labels.len() - 1 - idx) as u32)
   }
 
   pub fn get_sym(&self, ident: &str) -> Result<IndexedSymbol, CompilerErrorKind> {
@@ -0,0 +1,221 @@
+use crate::BinOp;
+use crate::Compiler;
+use crate::Function;
+use crate::FunctionBody;
+use crate::Inst;
+use crate::ModuleFunction;
+use crate::Symbol;
+use crate::Value;
+
+use wasm_encoder::ValType;
+use whistle_ast::Type;
+use whistle_common::Span;
+
+/// Whether `op` is emitted with a check when `compiler.checked` is set.
+pub fn is_checked(op: BinOp, val_type: ValType) -> bool {
+  matches!(val_type, ValType::I32 | ValType::I64)
+    && matches!(
+      op,
+      BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::DivS | BinOp::DivU | BinOp::RemS | BinOp::RemU
+    )
+}
+
+/// Applies `op` to the two operands on the stack, calling `sys.panic` with a
+/// message and trapping if it overflows or divides by zero.
+pub fn compile_checked(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  op: BinOp,
+  val_type: ValType,
+  unsigned: bool,
+) {
+  let int = |val: i64| match val_type {
+    ValType::I64 => Value::I64(val),
+    _ => Value::I32(val as i32),
+  };
+  let min = match val_type {
+    ValType::I64 => Value::I64(i64::MIN),
+    _ => Value::I32(i32::MIN),
+  };
+  let lhs = function.local("", val_type);
+  let rhs = function.local("", val_type);
+  function
+    .instruction(Inst::LocalSet(rhs))
+    .instruction(Inst::LocalSet(lhs));
+
+  if matches!(op, BinOp::DivS | BinOp::DivU | BinOp::RemS | BinOp::RemU) {
+    let message = match op {
+      BinOp::DivS | BinOp::DivU => "attempt to divide by zero",
+      _ => "attempt to calculate the remainder with a divisor of zero",
+    };
+    function
+      .instruction(Inst::LocalGet(rhs))
+      .instruction(Inst::Eqz(val_type))
+      .instruction(Inst::If);
+    compile_panic(compiler, function, message);
+    function.instruction(Inst::End);
+    if op == BinOp::DivS {
+      function
+        .instruction(Inst::LocalGet(lhs))
+        .instruction(Inst::Const(min))
+        .instruction(Inst::Binary(BinOp::Eq, val_type))
+        .instruction(Inst::LocalGet(rhs))
+        .instruction(Inst::Const(int(-1)))
+        .instruction(Inst::Binary(BinOp::Eq, val_type))
+        .instruction(Inst::Binary(BinOp::And, ValType::I32))
+        .instruction(Inst::If);
+      compile_panic(compiler, function, "attempt to divide with overflow");
+      function.instruction(Inst::End);
+    }
+    function
+      .instruction(Inst::LocalGet(lhs))
+      .instruction(Inst::LocalGet(rhs))
+      .instruction(Inst::Binary(op, val_type));
+    return;
+  }
+
+  let res = function.local("", val_type);
+  function
+    .instruction(Inst::LocalGet(lhs))
+    .instruction(Inst::LocalGet(rhs))
+    .instruction(Inst::Binary(op, val_type))
+    .instruction(Inst::LocalSet(res));
+
+  let message = match op {
+    BinOp::Add => "attempt to add with overflow",
+    BinOp::Sub => "attempt to subtract with overflow",
+    _ => "attempt to multiply with overflow",
+  };
+  match (op, unsigned) {
+    // The sum wrapped around if it is smaller than an operand.
+    (BinOp::Add, true) => {
+      function
+        .instruction(Inst::LocalGet(res))
+        .instruction(Inst::LocalGet(lhs))
+        .instruction(Inst::Binary(BinOp::LtU, val_type));
+    }
+    (BinOp::Sub, true) => {
+      function
+        .instruction(Inst::LocalGet(lhs))
+        .instruction(Inst::LocalGet(rhs))
+        .instruction(Inst::Binary(BinOp::LtU, val_type));
+    }
+    // Signed addition overflows when both operands differ in sign from the
+    // result, subtraction when the operands differ in sign and the result
+    // differs from `lhs`.
+    (BinOp::Add | BinOp::Sub, false) => {
+      let other = if op == BinOp::Add { rhs } else { lhs };
+      let (a, b) = if op == BinOp::Add {
+        (lhs, res)
+      } else {
+        (rhs, lhs)
+      };
+      function
+        .instruction(Inst::LocalGet(a))
+        .instruction(Inst::LocalGet(b))
+        .instruction(Inst::Binary(BinOp::Xor, val_type))
+        .instruction(Inst::LocalGet(other))
+        .instruction(Inst::LocalGet(res))
+        .instruction(Inst::Binary(BinOp::Xor, val_type))
+        .instruction(Inst::Binary(BinOp::And, val_type))
+        .instruction(Inst::Const(int(0)))
+        .instruction(Inst::Binary(BinOp::LtS, val_type));
+    }
+    // The product overflowed if dividing it by `lhs` does not give `rhs` back.
+    // `-1 * MIN` is checked first, as `MIN / -1` traps.
+    _ => {
+      if !unsigned {
+        function
+          .instruction(Inst::LocalGet(lhs))
+          .instruction(Inst::Const(int(-1)))
+          .instruction(Inst::Binary(BinOp::Eq, val_type))
+          .instruction(Inst::If)
+          .instruction(Inst::LocalGet(rhs))
+          .instruction(Inst::Const(min))
+          .instruction(Inst::Binary(BinOp::Eq, val_type))
+          .instruction(Inst::If);
+        compile_panic(compiler, function, message);
+        function.instruction(Inst::End).instruction(Inst::End);
+      }
+      let div = if unsigned { BinOp::DivU } else { BinOp::DivS };
+      function
+        .instruction(Inst::LocalGet(lhs))
+        .instruction(Inst::Eqz(val_type))
+        .instruction(Inst::Eqz(ValType::I32))
+        .instruction(Inst::If)
+        .instruction(Inst::LocalGet(res))
+        .instruction(Inst::LocalGet(lhs))
+        .instruction(Inst::Binary(div, val_type))
+        .instruction(Inst::LocalGet(rhs))
+        .instruction(Inst::Binary(BinOp::Ne, val_type))
+        .instruction(Inst::If);
+      compile_panic(compiler, function, message);
+      function
+        .instruction(Inst::End)
+        .instruction(Inst::End)
+        .instruction(Inst::LocalGet(res));
+      return;
+    }
+  }
+  function.instruction(Inst::If);
+  compile_panic(compiler, function, message);
+  function
+    .instruction(Inst::End)
+    .instruction(Inst::LocalGet(res));
+}
+
+/// Calls `sys.panic` with `message` and traps.
+pub fn compile_panic(compiler: &mut Compiler, function: &mut Function, message: &'static str) {
+  let idx = panic_fn(compiler);
+  let offset = match compiler.panic_messages.get(message) {
+    Some(offset) => *offset,
+    None => {
+      let start = compiler.memory.stack;
+      compiler.memory.buf.extend(message.as_bytes());
+      compiler.memory.stack += message.len() as u64;
+      // Kept regardless of which function used it first.
+      let owner = compiler.memory.owner.take();
+      compiler.memory.claim(start);
+      compiler.memory.owner = owner;
+      compiler.panic_messages.insert(message, start as u32);
+      start as u32
+    }
+  };
+  function
+    .instruction(Inst::Const(Value::I32(offset as i32)))
+    .instruction(Inst::Const(Value::I32(message.len() as i32)))
+    .instruction(Inst::Call(idx))
+    .instruction(Inst::Unreachable);
+}
+
+/// The index of the `sys.panic` import, added on first use.
+fn panic_fn(compiler: &mut Compiler) -> u32 {
+  if let Some(idx) = compiler.panic_fn {
+    return idx;
+  }
+  let sym = Symbol {
+    global: true,
+    mutable: false,
+    intrinsic: false,
+    types: Type::Error,
+  };
+  // The dot keeps the name from clashing with a Whistle identifier.
+  let idx = compiler
+    .scope
+    .set_function_sym_of(0, "sys.panic", sym)
+    .unwrap_or_default();
+  compiler.module.functions.push(ModuleFunction {
+    index: idx,
+    ident: String::from("panic"),
+    span: Span { start: 0, end: 0 },
+    params: vec![ValType::I32, ValType::I32],
+    results: vec![],
+    body: FunctionBody::Import {
+      namespace: String::from("sys"),
+      name: String::from("panic"),
+    },
+    cold: false,
+  });
+  compiler.panic_fn = Some(idx);
+  idx
+}
@@ -1,12 +1,29 @@
+use crate::compile_checked;
+use crate::compile_coalesce;
+use crate::compile_err;
+use crate::compile_none;
+use crate::compile_none_test;
+use crate::compile_try;
+use crate::compile_value;
+use crate::ident_type_to_val_type;
+use crate::instance_ident;
+use crate::is_checked;
+use crate::is_none;
+use crate::lower_extern_arg;
+use crate::method_ident;
 use crate::operator_to_ident_type;
-use crate::operator_to_instruction;
+use crate::operator_to_inst;
+use crate::BinOp;
 use crate::Compiler;
+use crate::Conversion;
 use crate::Function;
 use crate::IndexedSymbol;
-use crate::Symbol;
+use crate::Inst;
+use crate::Value;
 use whistle_common::CompilerErrorKind;
 
-use wasm_encoder::Instruction;
+use wasm_encoder::MemArg;
+use wasm_encoder::ValType;
 
 use whistle_ast::Expr;
 use whistle_ast::IdentVal;
@@ -18,6 +35,7 @@ use whistle_ast::Unary;
 use whistle_common::CompilerHandler;
 use whistle_common::Primitive;
 use whistle_common::Span;
+use whistle_common::TypedIdent;
 
 pub fn compile_expr(compiler: &mut Compiler, function: &mut Function, expr: Expr) -> Type {
   match expr {
@@ -29,7 +47,59 @@ pub fn compile_expr(compiler: &mut Compiler, function: &mut Function, expr: Expr
       else_expr,
       ..
     } => compile_cond(compiler, function, *cond, *then_expr, *else_expr),
+    Expr::Cast { expr, types, span } => {
+      let to = compiler.query_type(types.to_type());
+      compile_cast(compiler, function, *expr, to, span)
+    }
+    Expr::Try { expr, span } => compile_try(compiler, function, *expr, span),
+    Expr::Err {
+      expr,
+      meta_id,
+      span,
+    } => compile_err(compiler, function, *expr, meta_id, span),
+  }
+}
+
+pub fn compile_cast(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  expr: Expr,
+  to: Type,
+  span: Span,
+) -> Type {
+  let from = compile_expr(compiler, function, expr);
+  let (from_prim, to_prim) = match (&from, &to) {
+    (Type::Primitive(from), Type::Primitive(to)) => (from.clone(), to.clone()),
+    _ => return to,
+  };
+  let from_val = ident_type_to_val_type(from.clone());
+  let to_val = ident_type_to_val_type(to.clone());
+  if to_prim == Primitive::Bool && from_prim != Primitive::Bool {
+    function
+      .instruction(Inst::Eqz(from_val))
+      .instruction(Inst::Eqz(ValType::I32));
+    return to;
+  }
+  // Float to integer conversions take the signedness of the integer.
+  let signed = match from_val {
+    ValType::F32 | ValType::F64 => matches!(to_prim, Primitive::I32 | Primitive::I64),
+    _ => matches!(from_prim, Primitive::I32 | Primitive::I64),
+  };
+  if from_val != to_val {
+    match Conversion::new(from_val, to_val, signed) {
+      Some(conversion) => {
+        function.instruction(Inst::Convert(conversion));
+      }
+      None => compiler.handler.throw(
+        CompilerErrorKind::InvalidCast {
+          from,
+          to: to.clone(),
+        },
+        span,
+      ),
+    }
   }
+  to
 }
 
 pub fn compile_bin_expr(
@@ -40,40 +110,26 @@ pub fn compile_bin_expr(
   lhs: Expr,
 ) -> Type {
   if op == Operator::Assign {
-    if let Expr::Unary {
-      unary: Unary::Primary {
-        prim: Primary::IdentVal { ident, .. },
-        ..
-      },
-      span,
-    } = lhs
-    {
-      let type1 = compile_expr(compiler, function, rhs);
-      let sym = match compiler.get_sym(&ident) {
-        Ok(sym) => sym.clone(),
-        Err(err) => {
-          compiler.handler.throw(err, span);
-          IndexedSymbol(0, Symbol::default())
-        }
-      };
-
-      if sym.1.global {
-        function.instruction(Instruction::GlobalSet(sym.0));
-      } else {
-        function.instruction(Instruction::LocalSet(sym.0));
-      }
-
-      type1
-    } else {
-      Type::Error
-    }
+    compile_assign(compiler, function, lhs, rhs, true)
+  } else if op == Operator::Coalesce {
+    compile_coalesce(compiler, function, lhs, rhs)
+  } else if matches!(op, Operator::Eq | Operator::NotEq) && (is_none(&lhs) || is_none(&rhs)) {
+    let expr = if is_none(&lhs) { rhs } else { lhs };
+    compile_none_test(compiler, function, expr, op == Operator::Eq)
   } else {
     let type1 = compile_expr(compiler, function, lhs.clone());
     compile_expr(compiler, function, rhs);
 
-    match operator_to_instruction(&op, &type1) {
-      Ok(instruction) => {
-        function.instruction(instruction);
+    match operator_to_inst(&op, &type1) {
+      Ok(Inst::Binary(bin_op, val_type)) if compiler.checked && is_checked(bin_op, val_type) => {
+        let unsigned = matches!(
+          type1,
+          Type::Primitive(Primitive::U32) | Type::Primitive(Primitive::U64)
+        );
+        compile_checked(compiler, function, bin_op, val_type, unsigned);
+      }
+      Ok(inst) => {
+        function.instruction(inst);
       }
       Err(_) => return Type::Error,
     }
@@ -94,10 +150,16 @@ pub fn compile_unary(compiler: &mut Compiler, function: &mut Function, expr: Una
 
 pub fn compile_primary(compiler: &mut Compiler, function: &mut Function, expr: Primary) -> Type {
   match expr {
-    Primary::Literal { lit, meta_id, .. } => compile_literal(compiler, function, lit, meta_id),
+    Primary::Literal { lit, meta_id, span } => {
+      compile_literal(compiler, function, lit, meta_id, span)
+    }
     Primary::IdentVal { ident, prim, span } => compile_ident(compiler, function, ident, prim, span),
     Primary::Grouping { group, .. } => compile_expr(compiler, function, *group),
-    Primary::Array { exprs, meta_id, .. } => compile_array(compiler, function, exprs, meta_id),
+    Primary::Array {
+      exprs,
+      meta_id,
+      span,
+    } => compile_array(compiler, function, exprs, meta_id, span),
   }
 }
 
@@ -106,64 +168,104 @@ pub fn compile_literal(
   function: &mut Function,
   lit: Literal,
   id: usize,
+  span: Span,
 ) -> Type {
   match lit {
     Literal::Bool(val) => {
-      function.instruction(Instruction::I32Const(if val { 1 } else { 0 }));
+      function.instruction(Inst::Const(Value::I32(if val { 1 } else { 0 })));
       Type::Primitive(Primitive::Bool)
     }
     Literal::Char(val) => {
-      function.instruction(Instruction::I32Const(val as i32));
+      function.instruction(Inst::Const(Value::I32(val as i32)));
       Type::Primitive(Primitive::Char)
     }
     Literal::Int(val) => {
-      match &compiler.substitutions[id] {
-        Type::Primitive(Primitive::I32) => function.instruction(Instruction::I32Const(val as i32)),
-        Type::Primitive(Primitive::I64) => function.instruction(Instruction::I64Const(val as i64)),
-        _ => unreachable!(),
+      let types = compiler.query_type(Type::Var(id));
+      let (value, max) = match types {
+        Type::Primitive(Primitive::I32) => (Value::I32(val as i32), i32::MAX as u64),
+        Type::Primitive(Primitive::U32) => (Value::I32(val as i32), u32::MAX as u64),
+        Type::Primitive(Primitive::I64) => (Value::I64(val as i64), i64::MAX as u64),
+        Type::Primitive(Primitive::U64) => (Value::I64(val as i64), u64::MAX),
+        _ => {
+          compiler
+            .handler
+            .throw(CompilerErrorKind::NoImplicitAny, span);
+          return Type::Error;
+        }
       };
-      return compiler.substitutions[id].clone();
+      if val as u64 > max {
+        let err = CompilerErrorKind::LiteralOutOfRange {
+          types: types.clone(),
+        };
+        compiler.handler.throw(err, span);
+      }
+      function.instruction(Inst::Const(value));
+      types
     }
     Literal::Float(val) => {
-      match &compiler.substitutions[id] {
-        Type::Primitive(Primitive::F32) => function.instruction(Instruction::F32Const(val as f32)),
-        Type::Primitive(Primitive::F64) => function.instruction(Instruction::F64Const(val as f64)),
-        _ => unreachable!(),
+      let types = compiler.query_type(Type::Var(id));
+      match types {
+        Type::Primitive(Primitive::F32) => {
+          if val.is_finite() && (val as f32).is_infinite() {
+            let err = CompilerErrorKind::LiteralOutOfRange {
+              types: types.clone(),
+            };
+            compiler.handler.throw(err, span);
+          }
+          function.instruction(Inst::Const(Value::F32(val as f32)))
+        }
+        Type::Primitive(Primitive::F64) => function.instruction(Inst::Const(Value::F64(val))),
+        _ => {
+          compiler
+            .handler
+            .throw(CompilerErrorKind::NoImplicitAny, span);
+          return Type::Error;
+        }
       };
-      return compiler.substitutions[id].clone();
+      types
     }
     Literal::F32(val) => {
-      function.instruction(Instruction::F32Const(val as f32));
+      function.instruction(Inst::Const(Value::F32(val as f32)));
       Type::Primitive(Primitive::F32)
     }
     Literal::F64(val) => {
-      function.instruction(Instruction::F64Const(val as f64));
+      function.instruction(Inst::Const(Value::F64(val as f64)));
       Type::Primitive(Primitive::F64)
     }
     Literal::I32(val) => {
-      function.instruction(Instruction::I32Const(val as i32));
+      function.instruction(Inst::Const(Value::I32(val as i32)));
       Type::Primitive(Primitive::I32)
     }
     Literal::I64(val) => {
-      function.instruction(Instruction::I64Const(val as i64));
+      function.instruction(Inst::Const(Value::I64(val as i64)));
       Type::Primitive(Primitive::I64)
     }
     Literal::U32(val) => {
-      function.instruction(Instruction::I32Const(val as i32));
+      function.instruction(Inst::Const(Value::I32(val as i32)));
       Type::Primitive(Primitive::I32)
     }
     Literal::U64(val) => {
-      function.instruction(Instruction::I64Const(val as i64));
+      function.instruction(Inst::Const(Value::I64(val as i64)));
       Type::Primitive(Primitive::I64)
     }
     Literal::Str(string) => {
-      function.instruction(Instruction::I32Const(compiler.memory.stack as i32));
+      let start = compiler.memory.stack;
+      function.instruction(Inst::Const(Value::I32(start as i32)));
       let bytes = string.as_bytes();
+      // Strings are NUL-terminated so the std library can find their length.
       compiler.memory.buf.extend(bytes);
-      compiler.memory.stack += bytes.len() as u64;
+      compiler.memory.buf.push(0);
+      compiler.memory.stack += bytes.len() as u64 + 1;
+      compiler.memory.claim(start);
       Type::Primitive(Primitive::Str)
     }
-    Literal::None => Type::Primitive(Primitive::None),
+    Literal::None => {
+      let types = compiler.query_type(Type::Var(id));
+      if let Type::Option(inner) = &types {
+        compile_none(compiler, function, inner, span);
+      }
+      types
+    }
   }
 }
 
@@ -181,98 +283,317 @@ pub fn compile_ident(
       return Type::Error;
     }
   };
-  compile_ident_val(compiler, function, sym, prim, 0)
+  if let (true, Some(IdentVal::Arguments { args, .. })) = (sym.1.intrinsic, prim.first()) {
+    if let Type::Function { ret_type, .. } = sym.1.types.clone() {
+      let instructions = compiler.intrinsics[sym.0 as usize].clone();
+      for arg in args {
+        compile_expr(compiler, function, arg.clone());
+      }
+      for instruction in instructions {
+        function.instruction(instruction);
+      }
+      if prim.len() > 1 {
+        compiler
+          .handler
+          .throw(CompilerErrorKind::Unimplemented, prim[1].span());
+      }
+      return *ret_type;
+    }
+  }
+  compile_ident_val(compiler, function, &ident, sym, prim)
 }
 
 pub fn compile_ident_val(
   compiler: &mut Compiler,
   function: &mut Function,
+  ident: &str,
   sym: IndexedSymbol,
   prim: Vec<IdentVal>,
-  index: usize,
 ) -> Type {
-  if prim.is_empty() {
+  let mut types = sym.1.types.clone();
+  if !matches!(prim.first(), Some(IdentVal::Arguments { .. })) {
     function.instruction(if sym.1.global {
-      Instruction::GlobalGet(sym.0)
+      Inst::GlobalGet(sym.0)
     } else {
-      Instruction::LocalGet(sym.0)
+      Inst::LocalGet(sym.0)
     });
-
-    sym.1.types
-  } else {
-    let types = match &prim[index] {
-      IdentVal::Arguments { args, .. } => {
-        compile_arguments(compiler, function, sym.clone(), args.clone())
+  }
+  let mut prim = prim.into_iter().peekable();
+  while let Some(val) = prim.next() {
+    // The checker resolved a selector followed by arguments to a method,
+    // unless it selects a property of a struct.
+    if let (IdentVal::Selector { ident, span }, Some(IdentVal::Arguments { .. })) =
+      (&val, prim.peek())
+    {
+      if !is_property(compiler, &types, ident) {
+        let (ident, span) = (ident.clone(), *span);
+        if let Some(IdentVal::Arguments { args, .. }) = prim.next() {
+          types = compile_method(compiler, function, types, &ident, args, span);
+        }
+        continue;
+      }
+    }
+    types = match val {
+      IdentVal::Arguments { args, meta_id, .. } => {
+        compile_arguments(compiler, function, ident, sym.clone(), args, meta_id)
       }
       IdentVal::Selector { ident, span } => {
-        compile_selector(compiler, function, sym.clone(), ident.clone(), span)
+        compile_selector(compiler, function, types, ident, &span)
+      }
+      IdentVal::Index { expr, span } => {
+        match compile_element(compiler, function, types, *expr, span) {
+          Some((elem_type, val_type, memarg)) => {
+            function.instruction(Inst::Load(val_type, memarg));
+            elem_type
+          }
+          None => return Type::Error,
+        }
       }
-      _ => {
+      IdentVal::Slice { span, .. } => {
         compiler
           .handler
-          .throw(CompilerErrorKind::Unimplemented, prim[index].span());
+          .throw(CompilerErrorKind::Unimplemented, span);
         return Type::Error;
       }
     };
-    if prim.len() > index + 1 {
-      compile_ident_val(compiler, function, sym, prim, index + 1)
-    } else {
-      types
+  }
+  types
+}
+
+/// The size in bytes and alignment exponent of an array element.
+pub fn element_layout(val_type: ValType) -> (u64, u32) {
+  match val_type {
+    ValType::I64 | ValType::F64 => (8, 3),
+    _ => (4, 2),
+  }
+}
+
+/// Turns the array pointer on the stack into the address of the element at
+/// `index`. Returns the element type and how to load or store it.
+pub fn compile_element(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  types: Type,
+  index: Expr,
+  span: Span,
+) -> Option<(Type, ValType, MemArg)> {
+  let elem_type = match types {
+    Type::Array(elem_type) => *elem_type,
+    _ => {
+      compiler
+        .handler
+        .throw(CompilerErrorKind::NoProperties, span);
+      return None;
     }
+  };
+  let val_type = ident_type_to_val_type(elem_type.clone());
+  let (size, align) = element_layout(val_type);
+  let index_type = compile_expr(compiler, function, index);
+  if ident_type_to_val_type(index_type) == ValType::I64 {
+    function.instruction(Inst::Convert(Conversion::WRAP));
   }
+  function
+    .instruction(Inst::Const(Value::I32(size as i32)))
+    .instruction(Inst::Binary(BinOp::Mul, ValType::I32))
+    .instruction(Inst::Binary(BinOp::Add, ValType::I32));
+  let memarg = MemArg {
+    offset: 0,
+    align,
+    memory_index: 0,
+  };
+  Some((elem_type, val_type, memarg))
 }
 
-pub fn compile_array(
+/// Compiles an assignment, leaving the assigned value on the stack if `keep`
+/// is set, as for an assignment expression.
+pub fn compile_assign(
   compiler: &mut Compiler,
   function: &mut Function,
-  exprs: Vec<Expr>,
-  id: usize,
+  lhs: Expr,
+  rhs: Expr,
+  keep: bool,
 ) -> Type {
-  let idx = compiler.memory.stack;
-  if let Type::Array(expr_type) = compiler.substitutions[id].clone() {
-    for (_, expr) in exprs.into_iter().enumerate() {
-      let span = expr.span();
-      compile_expr(compiler, function, expr);
-      let memarg = compiler.memory.index_stack();
-      let instruction = match &*expr_type {
-        Type::Primitive(prim) => match prim {
-          Primitive::I32 => Instruction::I32Store(memarg),
-          Primitive::F32 => Instruction::F32Store(memarg),
-          Primitive::I64 => Instruction::I64Store(memarg),
-          Primitive::F64 => Instruction::F64Store(memarg),
-          _ => {
-            compiler
-              .handler
-              .throw(CompilerErrorKind::Unimplemented, span);
-            return Type::Error;
-          }
+  let lhs_span = lhs.span();
+  let (ident, mut prim, span) = match lhs {
+    Expr::Unary {
+      unary:
+        Unary::Primary {
+          prim: Primary::IdentVal { ident, prim, span },
+          ..
         },
-        _ => {
+      ..
+    } => (ident, prim, span),
+    _ => {
+      compiler
+        .handler
+        .throw(CompilerErrorKind::Unassignable, lhs_span);
+      return Type::Error;
+    }
+  };
+  let sym = match compiler.get_sym(&ident) {
+    Ok(sym) => sym,
+    Err(err) => {
+      compiler.handler.throw(err, span);
+      return Type::Error;
+    }
+  };
+  match prim.pop() {
+    None => {
+      let types = compile_value(compiler, function, rhs, &sym.1.types);
+      match (sym.1.global, keep) {
+        (true, true) => function
+          .instruction(Inst::GlobalSet(sym.0))
+          .instruction(Inst::GlobalGet(sym.0)),
+        (true, false) => function.instruction(Inst::GlobalSet(sym.0)),
+        (false, true) => function.instruction(Inst::LocalTee(sym.0)),
+        (false, false) => function.instruction(Inst::LocalSet(sym.0)),
+      };
+      types
+    }
+    Some(val) => {
+      let types = compile_ident_val(compiler, function, &ident, sym, prim);
+      let place = match val {
+        IdentVal::Index { expr, span } => compile_element(compiler, function, types, *expr, span),
+        IdentVal::Selector { ident, span } => compile_property(compiler, types, &ident, span),
+        val => {
           compiler
             .handler
-            .throw(CompilerErrorKind::Unimplemented, span);
-          return Type::Error;
+            .throw(CompilerErrorKind::Unimplemented, val.span());
+          None
         }
       };
-      function.instruction(instruction);
+      let (elem_type, val_type, memarg) = match place {
+        Some(place) => place,
+        None => return Type::Error,
+      };
+      compile_value(compiler, function, rhs, &elem_type);
+      if keep {
+        let tmp = function.local("", val_type);
+        function
+          .instruction(Inst::LocalTee(tmp))
+          .instruction(Inst::Store(val_type, memarg))
+          .instruction(Inst::LocalGet(tmp));
+      } else {
+        function.instruction(Inst::Store(val_type, memarg));
+      }
+      elem_type
     }
-    function.instruction(Instruction::I64Const(idx as i64));
+  }
+}
+
+pub fn compile_array(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  exprs: Vec<Expr>,
+  id: usize,
+  span: Span,
+) -> Type {
+  if let Type::Array(expr_type) = compiler.query_type(Type::Var(id)) {
+    if *expr_type == Type::Error {
+      compiler
+        .handler
+        .throw(CompilerErrorKind::NoImplicitAny, span);
+      return Type::Error;
+    }
+    let val_type = ident_type_to_val_type(*expr_type.clone());
+    let (size, align) = element_layout(val_type);
+    // The element count is stored in the 4 bytes before the first element.
+    let header = compiler.memory.stack;
+    let start = (compiler.memory.stack + 4).div_ceil(8) * 8;
+    let len = exprs.len() as u64;
+    compiler.memory.buf.resize(start as usize, 0);
+    compiler.memory.buf[start as usize - 4..].copy_from_slice(&(len as u32).to_le_bytes());
+    compiler.memory.stack = start + len * size;
+    compiler
+      .memory
+      .buf
+      .resize(compiler.memory.stack as usize, 0);
+    compiler.memory.claim(header);
+
+    for (i, expr) in exprs.into_iter().enumerate() {
+      let memarg = MemArg {
+        offset: 0,
+        align,
+        memory_index: 0,
+      };
+      function.instruction(Inst::Const(Value::I32((start + i as u64 * size) as i32)));
+      compile_expr(compiler, function, expr);
+      function.instruction(Inst::Store(val_type, memarg));
+    }
+    function.instruction(Inst::Const(Value::I32(start as i32)));
     return Type::Array(expr_type);
   }
-  unreachable!()
+  compiler
+    .handler
+    .throw(CompilerErrorKind::NoImplicitAny, span);
+  Type::Error
+}
+
+/// Whether `types` is a struct with the property `ident`.
+fn is_property(compiler: &Compiler, types: &Type, ident: &str) -> bool {
+  compiler
+    .props(types)
+    .is_some_and(|props| props.iter().any(|prop| prop.ident == ident))
+}
+
+/// Compiles `receiver.ident(args)`, with the receiver on the stack as the
+/// `self` argument.
+pub fn compile_method(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  receiver: Type,
+  ident: &str,
+  args: Vec<Expr>,
+  span: Span,
+) -> Type {
+  let sym = match compiler.get_sym(&method_ident(&receiver, ident)) {
+    Ok(sym) => sym,
+    Err(err) => {
+      compiler.handler.throw(err, span);
+      return Type::Error;
+    }
+  };
+  if let Type::Function { params, ret_type } = sym.1.types {
+    for (arg, param) in args.into_iter().zip(params.into_iter().skip(1)) {
+      compile_value(compiler, function, arg, &param.type_ident);
+    }
+    function.instruction(Inst::Call(sym.0));
+    *ret_type
+  } else {
+    Type::Error
+  }
 }
 
 pub fn compile_arguments(
   compiler: &mut Compiler,
   function: &mut Function,
+  ident: &str,
   sym: IndexedSymbol,
   args: Vec<Expr>,
+  meta_id: usize,
 ) -> Type {
+  // A generic function calls the instance for the types it is called with.
+  let sym = match compiler.generic_fns.get(ident) {
+    Some(generic) if sym.1.global => {
+      let types: Vec<Type> = (meta_id..meta_id + generic.params.len())
+        .map(|id| compiler.query_type(Type::Var(id)))
+        .collect();
+      match compiler.get_sym(&instance_ident(ident, &types)) {
+        Ok(sym) => sym,
+        Err(_) => return Type::Error,
+      }
+    }
+    _ => sym,
+  };
   if let Type::Function { params, ret_type } = sym.1.types {
-    for arg in args.iter().take(params.len()) {
-      compile_expr(compiler, function, arg.clone());
+    let is_extern = compiler.externs.contains(&sym.0);
+    for (arg, param) in args.iter().zip(params) {
+      compile_value(compiler, function, arg.clone(), &param.type_ident);
+      if is_extern {
+        lower_extern_arg(function, param.type_ident);
+      }
     }
-    function.instruction(Instruction::Call(sym.0));
+    function.instruction(Inst::Call(sym.0));
 
     *ret_type
   } else {
@@ -282,26 +603,69 @@ pub fn compile_arguments(
 
 pub fn compile_selector(
   compiler: &mut Compiler,
-  _function: &mut Function,
-  sym: IndexedSymbol,
+  function: &mut Function,
+  types: Type,
   ident: String,
   span: &Span,
 ) -> Type {
-  if let Type::Struct(props) = sym.1.types {
-    for prop in props {
-      if prop.ident == ident {
-        return prop.type_ident;
-      }
+  match compile_property(compiler, types, &ident, *span) {
+    Some((prop_type, val_type, memarg)) => {
+      function.instruction(Inst::Load(val_type, memarg));
+      prop_type
     }
-
-    compiler
-      .handler
-      .throw(CompilerErrorKind::MissingProperty, span.clone());
+    None => Type::Error,
   }
-  compiler
-    .handler
-    .throw(CompilerErrorKind::NoProperties, span.clone());
-  Type::Error
+}
+
+/// The offset of each property of a struct, which are stored in order, each
+/// aligned to its size.
+pub fn struct_layout(props: &[TypedIdent]) -> Vec<u64> {
+  let mut end: u64 = 0;
+  props
+    .iter()
+    .map(|prop| {
+      let (size, _) = element_layout(ident_type_to_val_type(prop.type_ident.clone()));
+      let offset = end.div_ceil(size) * size;
+      end = offset + size;
+      offset
+    })
+    .collect()
+}
+
+/// The property `ident` of the struct pointed to by the value on the stack.
+/// Returns its type and how to load or store it.
+pub fn compile_property(
+  compiler: &mut Compiler,
+  types: Type,
+  ident: &str,
+  span: Span,
+) -> Option<(Type, ValType, MemArg)> {
+  let props = match compiler.props(&types) {
+    Some(props) => props,
+    None => {
+      compiler
+        .handler
+        .throw(CompilerErrorKind::NoProperties, span);
+      return None;
+    }
+  };
+  let idx = match props.iter().position(|prop| prop.ident == ident) {
+    Some(idx) => idx,
+    None => {
+      compiler
+        .handler
+        .throw(CompilerErrorKind::MissingProperty, span);
+      return None;
+    }
+  };
+  let prop_type = props[idx].type_ident.clone();
+  let val_type = ident_type_to_val_type(prop_type.clone());
+  let memarg = MemArg {
+    offset: struct_layout(&props)[idx],
+    align: element_layout(val_type).1,
+    memory_index: 0,
+  };
+  Some((prop_type, val_type, memarg))
 }
 
 pub fn compile_cond(
@@ -315,7 +679,7 @@ pub fn compile_cond(
   compile_expr(compiler, function, else_expr);
   compile_expr(compiler, function, cond);
 
-  function.instruction(Instruction::Select);
+  function.instruction(Inst::Select);
 
   type1
 }
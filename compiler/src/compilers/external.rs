@@ -1,28 +1,153 @@
 use crate::ident_type_to_val_type;
+use crate::BinOp;
 use crate::Compiler;
-use wasm_encoder::EntityType;
+use crate::Function;
+use crate::FunctionBody;
+use crate::Inst;
+use crate::ModuleFunction;
+use crate::Value;
+use wasm_encoder::MemArg;
 use wasm_encoder::ValType;
+use whistle_ast::Primitive;
 use whistle_ast::Type;
+use whistle_common::Span;
 
-pub fn setup_extern(compiler: &mut Compiler, namespace: &str, fn_name: &str, types: Type) {
-  let sym = compiler.get_sym(fn_name).unwrap();
-  compiler
-    .module
-    .imports
-    .import(namespace, fn_name, EntityType::Function(sym.0));
+pub fn setup_extern(
+  compiler: &mut Compiler,
+  namespace: &str,
+  fn_name: &str,
+  ident: &str,
+  types: Type,
+) {
+  let sym = compiler.get_sym(ident).unwrap();
+  compiler.externs.insert(sym.0);
   if let Type::Function { params, ret_type } = types {
     let mut param_types = Vec::new();
     for param in params {
-      param_types.push(ident_type_to_val_type(param.type_ident));
+      param_types.append(&mut extern_param_types(param.type_ident));
     }
-    let encoded_ret_type: Vec<ValType> = if let Type::Primitive(..) = *ret_type {
-      vec![]
-    } else {
-      vec![ident_type_to_val_type(*ret_type)]
-    };
-    compiler
-      .module
-      .types
-      .function(param_types, encoded_ret_type);
+    compiler.module.functions.push(ModuleFunction {
+      index: sym.0,
+      ident: ident.to_string(),
+      span: Span { start: 0, end: 0 },
+      params: param_types,
+      results: result_types(*ret_type),
+      body: FunctionBody::Import {
+        namespace: namespace.to_string(),
+        name: fn_name.to_string(),
+      },
+      cold: false,
+    });
+  }
+}
+
+/// The values an extern parameter is passed as.
+///
+/// Numbers, `bool` and `char` are passed as a single value. `str` is passed
+/// as a pointer and its length in bytes, without the NUL terminator. Arrays
+/// are passed as a pointer to the first element and the element count.
+/// Structs are passed as a single pointer.
+pub fn extern_param_types(types: Type) -> Vec<ValType> {
+  match types {
+    Type::Primitive(Primitive::None) => vec![],
+    Type::Primitive(Primitive::Str) | Type::Array(_) => vec![ValType::I32, ValType::I32],
+    Type::Tuple(types) => types.into_iter().flat_map(extern_param_types).collect(),
+    _ => vec![ident_type_to_val_type(types)],
+  }
+}
+
+/// The values a function returns. Tuples return one value per element,
+/// everything else is returned like a Whistle local, so strings, arrays and
+/// structs come back as a single pointer.
+pub fn result_types(types: Type) -> Vec<ValType> {
+  match types {
+    Type::Primitive(Primitive::None) => vec![],
+    Type::Tuple(types) => types.into_iter().flat_map(result_types).collect(),
+    _ => vec![ident_type_to_val_type(types)],
+  }
+}
+
+/// Turns the pointer on top of the stack into the pointer and length an extern
+/// expects for an argument of type `types`.
+pub fn lower_extern_arg(function: &mut Function, types: Type) {
+  let memarg = |align| MemArg {
+    offset: 0,
+    align,
+    memory_index: 0,
+  };
+  // The temporaries are dead once the values are on the stack, so they are
+  // freed for the next argument or call to reuse.
+  function.enter_scope();
+  match types {
+    Type::Primitive(Primitive::Str) => {
+      let ptr = function.local("", ValType::I32);
+      let end = function.local("", ValType::I32);
+      function
+        .instruction(Inst::LocalTee(ptr))
+        .instruction(Inst::LocalGet(ptr))
+        .instruction(Inst::LocalSet(end))
+        .instruction(Inst::Block)
+        .instruction(Inst::Loop)
+        .instruction(Inst::LocalGet(end))
+        .instruction(Inst::Load8U(memarg(0)))
+        .instruction(Inst::Eqz(ValType::I32))
+        .instruction(Inst::BrIf(1))
+        .instruction(Inst::LocalGet(end))
+        .instruction(Inst::Const(Value::I32(1)))
+        .instruction(Inst::Binary(BinOp::Add, ValType::I32))
+        .instruction(Inst::LocalSet(end))
+        .instruction(Inst::Br(0))
+        .instruction(Inst::End)
+        .instruction(Inst::End)
+        .instruction(Inst::LocalGet(end))
+        .instruction(Inst::LocalGet(ptr))
+        .instruction(Inst::Binary(BinOp::Sub, ValType::I32));
+    }
+    Type::Array(_) => {
+      // The element count is stored right before the first element.
+      let ptr = function.local("", ValType::I32);
+      function
+        .instruction(Inst::LocalTee(ptr))
+        .instruction(Inst::LocalGet(ptr))
+        .instruction(Inst::Const(Value::I32(4)))
+        .instruction(Inst::Binary(BinOp::Sub, ValType::I32))
+        .instruction(Inst::Load(ValType::I32, memarg(2)));
+    }
+    _ => {}
+  }
+  function.exit_scope();
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::extern_param_types;
+  use crate::result_types;
+  use wasm_encoder::ValType;
+  use whistle_ast::Primitive;
+  use whistle_ast::Type;
+
+  #[test]
+  fn pointer_length_params() {
+    assert_eq!(
+      extern_param_types(Type::Primitive(Primitive::Str)),
+      vec![ValType::I32, ValType::I32]
+    );
+    assert_eq!(
+      extern_param_types(Type::Array(Box::new(Type::Primitive(Primitive::F64)))),
+      vec![ValType::I32, ValType::I32]
+    );
+    assert_eq!(extern_param_types(Type::Struct(vec![])), vec![ValType::I32]);
+  }
+
+  #[test]
+  fn multi_value_results() {
+    assert_eq!(result_types(Type::Primitive(Primitive::None)), vec![]);
+    assert_eq!(
+      result_types(Type::Tuple(vec![
+        Type::Primitive(Primitive::I32),
+        Type::Primitive(Primitive::F64)
+      ])),
+      vec![ValType::I32, ValType::F64]
+    );
   }
 }
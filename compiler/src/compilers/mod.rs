@@ -1,40 +1,228 @@
 use crate::Compiler;
+use crate::DataChunk;
 
-use wasm_encoder::ConstExpr;
-use wasm_encoder::DataSegment;
-use wasm_encoder::DataSegmentMode;
 use wasm_encoder::ExportKind;
 use whistle_ast::Grammar;
 
+mod checked;
 mod expr;
 mod external;
+mod option;
 mod program;
+mod result;
 mod stmt;
 mod tip;
 mod types;
+mod validate;
 
+pub use checked::*;
 pub use expr::*;
 pub use external::*;
+pub use option::*;
 pub use program::*;
+pub use result::*;
 pub use stmt::*;
 pub use tip::*;
 pub use types::*;
+pub use validate::*;
 
-pub fn compile_all(compiler: &mut Compiler, grammar: Grammar) -> Vec<u8> {
+/// Lowers the checked program into the IR functions, globals and data of
+/// `compiler.module`, without encoding them.
+pub fn lower_all(compiler: &mut Compiler, grammar: Grammar) {
   compiler.module.memories.memory(compiler.memory.alloc());
   for program in grammar {
     compile_program(compiler, program);
   }
-  compiler.module.data.segment(DataSegment {
-    data: compiler.memory.buf.clone(),
-    mode: DataSegmentMode::Active {
-      memory_index: 0,
-      offset: &ConstExpr::i32_const(0),
-    },
-  });
+  for (start, end, owner) in compiler.memory.chunks.clone() {
+    compiler.module.data.push(DataChunk {
+      offset: start as u32,
+      bytes: compiler.memory.buf[start as usize..end as usize].to_vec(),
+      owner,
+    });
+  }
   compiler
     .module
     .exports
-    .export("memory", ExportKind::Memory, 0);
-  compiler.module.finish()
+    .push((String::from("memory"), ExportKind::Memory, 0));
+}
+
+/// Lowers the checked program to IR and encodes it as a wasm module.
+pub fn compile_all(compiler: &mut Compiler, grammar: Grammar) -> Vec<u8> {
+  lower_all(compiler, grammar);
+  let bytes = compiler.module.finish();
+  if compiler.validate {
+    validate_module(compiler, &bytes);
+  }
+  bytes
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::compile;
+  use crate::test_support::errors;
+  use whistle_ast::Primitive;
+  use whistle_ast::Type;
+  use whistle_common::CompilerErrorKind;
+  use whistle_preprocessor::STD_MODULES;
+
+  use wasmparser::Payload;
+
+  #[test]
+  fn declaration_tips() {
+    let (compiler, bytes) = compile(
+      "extern \"sys\" {
+        fn report(code: i32): none
+      }
+      #(import_module) \"env\"
+      extern \"sys\" {
+        fn trace(code: i32): none
+      }
+      #(cold)
+      fn fail(): none {
+        report(1)
+      }
+      #(export) \"run\"
+      fn main(): none {
+        fail()
+        trace(2)
+      }
+      #(start)
+      fn init(): none {}",
+    );
+    assert_eq!(compiler.handler.errors, vec![]);
+
+    let mut imports = Vec::new();
+    let mut exports = Vec::new();
+    let mut start = None;
+    for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+      match payload.unwrap() {
+        Payload::ImportSection(reader) => {
+          for import in reader {
+            let import = import.unwrap();
+            imports.push(format!("{}.{}", import.module, import.name));
+          }
+        }
+        Payload::ExportSection(reader) => {
+          for export in reader {
+            exports.push(export.unwrap().name.to_string());
+          }
+        }
+        Payload::StartSection { func, .. } => start = Some(func),
+        _ => {}
+      }
+    }
+    assert_eq!(imports, vec!["sys.report", "env.trace"]);
+    assert_eq!(exports, vec!["run", "memory"]);
+    // Imports come first, then the code in declaration order with cold
+    // functions last.
+    let code: Vec<&str> = compiler
+      .module
+      .code
+      .iter()
+      .map(|(ident, _)| ident.as_str())
+      .collect();
+    assert_eq!(code, vec!["main", "init", "fail"]);
+    assert_eq!(start, Some(3));
+  }
+
+  #[test]
+  fn extern_string_args() {
+    let (compiler, bytes) = compile(
+      "extern \"env\" {
+        fn report(a: str, b: str): none,
+        fn trace(a: str, b: str): none
+      }
+      export fn main(): none {
+        report(\"a\", \"b\")
+        report(\"c\", \"d\")
+        trace(\"e\", \"f\")
+      }",
+    );
+    assert_eq!(compiler.handler.errors, vec![]);
+
+    let mut types = 0;
+    let mut locals = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+      match payload.unwrap() {
+        Payload::TypeSection(reader) => types = reader.count(),
+        Payload::CodeSectionEntry(body) => {
+          for local in body.get_locals_reader().unwrap() {
+            locals += local.unwrap().0;
+          }
+        }
+        _ => {}
+      }
+    }
+    // Both externs share a type, and every argument reuses the pointer and
+    // end locals of the first.
+    assert_eq!(types, 2);
+    assert_eq!(locals, 2);
+  }
+
+  #[test]
+  fn shake_wasm_bytes() {
+    // `helper` is only called from the raw body of `main`, as function 1.
+    let (compiler, _) = compile(
+      "export fn main(): none {
+        #(wasm_bytes) { 16, 1, 11 }
+      }
+      fn helper(): none {}",
+    );
+    assert_eq!(compiler.handler.errors, vec![]);
+  }
+
+  #[test]
+  fn std_modules() {
+    for module in STD_MODULES {
+      let (compiler, _) = compile(&format!("import \"@std/{}\"", module));
+      assert!(
+        compiler.handler.errors.is_empty(),
+        "@std/{}: {:?}",
+        module,
+        compiler.handler.errors
+      );
+    }
+  }
+
+  #[test]
+  fn intrinsics() {
+    let (compiler, _) = compile(
+      "builtin @core { memory_size, memory_grow }
+      export fn main(): i32 {
+        val pages = memory_grow(1)
+        return memory_size() + pages
+      }
+      export fn shadow(memory_size: i32): i32 {
+        return memory_size
+      }",
+    );
+    assert_eq!(compiler.handler.errors, vec![]);
+
+    let (compiler, _) = compile(
+      "builtin @core { memory_size }
+      export fn main(): i32 {
+        val pages = memory_size(1)
+        return pages
+      }
+      export fn size() {
+        memory_size
+      }",
+    );
+    assert_eq!(
+      errors(&compiler.handler),
+      vec![
+        CompilerErrorKind::UnexpectedArgument,
+        CompilerErrorKind::UncalledIntrinsic {
+          ident: String::from("memory_size")
+        },
+        CompilerErrorKind::TypeMismatch {
+          type1: Type::Function {
+            params: vec![],
+            ret_type: Box::new(Type::Primitive(Primitive::I32)),
+          },
+          type2: Type::Primitive(Primitive::None),
+        },
+      ]
+    );
+  }
 }
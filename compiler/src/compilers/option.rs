@@ -0,0 +1,213 @@
+use crate::compile_expr;
+use crate::compile_ok;
+use crate::BinOp;
+use crate::Compiler;
+use crate::Conversion;
+use crate::Function;
+use crate::Inst;
+use crate::Value;
+use whistle_common::CompilerErrorKind;
+
+use wasm_encoder::ValType;
+
+use whistle_ast::Expr;
+use whistle_ast::Primitive;
+use whistle_ast::Type;
+use whistle_common::CompilerHandler;
+use whistle_common::Span;
+
+/// The bits of a present `i32?`, `u32?`, `char?` or `bool?` above the value.
+const TAG: i64 = 1 << 32;
+/// Signaling NaNs, which arithmetic never produces, stand for an empty `f32?`
+/// and `f64?`.
+const NONE_F32: u32 = 0x7f80_0001;
+const NONE_F64: u64 = 0x7ff0_0000_0000_0001;
+
+/// How an option is stored in a single wasm value, without a separate flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionRepr {
+  /// A pointer, null when empty.
+  Pointer,
+  /// A 32-bit value widened to an `i64`, with `TAG` set when present.
+  Tagged,
+  /// A float, a reserved NaN when empty.
+  Nan(ValType),
+}
+
+impl OptionRepr {
+  /// The representation of a `T?`. `i64?`, `u64?` and nested options have no
+  /// spare bits and are not supported yet.
+  pub fn of(inner: &Type) -> Option<OptionRepr> {
+    match inner {
+      Type::Primitive(Primitive::I32 | Primitive::U32 | Primitive::Char | Primitive::Bool) => {
+        Some(OptionRepr::Tagged)
+      }
+      Type::Primitive(Primitive::F32) => Some(OptionRepr::Nan(ValType::F32)),
+      Type::Primitive(Primitive::F64) => Some(OptionRepr::Nan(ValType::F64)),
+      Type::Primitive(Primitive::Str)
+      | Type::Array(_)
+      | Type::Struct(_)
+      | Type::Ident(_)
+      | Type::IdentType { .. } => Some(OptionRepr::Pointer),
+      _ => None,
+    }
+  }
+
+  pub fn val_type(self) -> ValType {
+    match self {
+      OptionRepr::Pointer => ValType::I32,
+      OptionRepr::Tagged => ValType::I64,
+      OptionRepr::Nan(val_type) => val_type,
+    }
+  }
+
+  /// The value of an empty option.
+  pub fn none(self) -> Value {
+    match self {
+      OptionRepr::Pointer => Value::I32(0),
+      OptionRepr::Tagged => Value::I64(0),
+      OptionRepr::Nan(ValType::F32) => Value::F32(f32::from_bits(NONE_F32)),
+      OptionRepr::Nan(_) => Value::F64(f64::from_bits(NONE_F64)),
+    }
+  }
+
+  /// `value` as a present option.
+  pub fn wrap(self, value: Value) -> Value {
+    match (self, value) {
+      (OptionRepr::Tagged, Value::I32(val)) => Value::I64(val as u32 as i64 | TAG),
+      (_, value) => value,
+    }
+  }
+}
+
+/// The representation of `T?`, reporting the ones that aren't supported.
+fn option_repr(compiler: &mut Compiler, inner: &Type, span: Span) -> Option<OptionRepr> {
+  let repr = OptionRepr::of(inner);
+  if repr.is_none() && *inner != Type::Error {
+    compiler
+      .handler
+      .throw(CompilerErrorKind::Unimplemented, span);
+  }
+  repr
+}
+
+/// Pushes an empty `T?`.
+pub fn compile_none(compiler: &mut Compiler, function: &mut Function, inner: &Type, span: Span) {
+  if let Some(repr) = option_repr(compiler, inner, span) {
+    function.instruction(Inst::Const(repr.none()));
+  }
+}
+
+/// Turns the `T` on the stack into a present `T?`.
+pub fn compile_wrap(compiler: &mut Compiler, function: &mut Function, inner: &Type, span: Span) {
+  if let Some(OptionRepr::Tagged) = option_repr(compiler, inner, span) {
+    function
+      .instruction(Inst::Convert(Conversion::EXTEND_U))
+      .instruction(Inst::Const(Value::I64(TAG)))
+      .instruction(Inst::Binary(BinOp::Or, ValType::I64));
+  }
+}
+
+/// Turns the `T?` on the stack into its `T`, which is meaningless if empty.
+pub fn compile_unwrap(compiler: &mut Compiler, function: &mut Function, inner: &Type, span: Span) {
+  if let Some(OptionRepr::Tagged) = option_repr(compiler, inner, span) {
+    function.instruction(Inst::Convert(Conversion::WRAP));
+  }
+}
+
+/// Turns the `T?` on the stack into whether it holds a value.
+pub fn compile_is_some(compiler: &mut Compiler, function: &mut Function, inner: &Type, span: Span) {
+  match option_repr(compiler, inner, span) {
+    Some(OptionRepr::Pointer) => {
+      function
+        .instruction(Inst::Const(Value::I32(0)))
+        .instruction(Inst::Binary(BinOp::Ne, ValType::I32));
+    }
+    Some(OptionRepr::Tagged) => {
+      function
+        .instruction(Inst::Const(Value::I64(32)))
+        .instruction(Inst::Binary(BinOp::ShrU, ValType::I64))
+        .instruction(Inst::Convert(Conversion::WRAP));
+    }
+    Some(OptionRepr::Nan(ValType::F32)) => {
+      function
+        .instruction(Inst::Reinterpret(ValType::F32))
+        .instruction(Inst::Const(Value::I32(NONE_F32 as i32)))
+        .instruction(Inst::Binary(BinOp::Ne, ValType::I32));
+    }
+    Some(OptionRepr::Nan(_)) => {
+      function
+        .instruction(Inst::Reinterpret(ValType::F64))
+        .instruction(Inst::Const(Value::I64(NONE_F64 as i64)))
+        .instruction(Inst::Binary(BinOp::Ne, ValType::I64));
+    }
+    None => {}
+  }
+}
+
+/// Compiles `expr` to be stored in a `slot`, wrapping it if the slot is an
+/// option or result and the value is not, the same way the checker let it flow
+/// there.
+pub fn compile_value(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  expr: Expr,
+  slot: &Type,
+) -> Type {
+  let span = expr.span();
+  let types = compile_expr(compiler, function, expr);
+  match slot {
+    Type::Option(inner) if !matches!(types, Type::Option(_)) => {
+      compile_wrap(compiler, function, inner, span);
+      slot.clone()
+    }
+    Type::Result(ok, err) if !matches!(types, Type::Result(..)) => {
+      compile_ok(compiler, function, ok, err, span);
+      slot.clone()
+    }
+    _ => types,
+  }
+}
+
+/// Compiles a comparison of `expr` with `none`, negated for `==`.
+pub fn compile_none_test(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  expr: Expr,
+  eq: bool,
+) -> Type {
+  let span = expr.span();
+  if let Type::Option(inner) = compile_expr(compiler, function, expr) {
+    compile_is_some(compiler, function, &inner, span);
+    if eq {
+      function.instruction(Inst::Eqz(ValType::I32));
+    }
+  }
+  Type::Primitive(Primitive::Bool)
+}
+
+/// Compiles `lhs ?? rhs`. Like a conditional, both sides are evaluated.
+pub fn compile_coalesce(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  lhs: Expr,
+  rhs: Expr,
+) -> Type {
+  let span = lhs.span();
+  let inner = match compile_expr(compiler, function, lhs) {
+    Type::Option(inner) => *inner,
+    _ => return Type::Error,
+  };
+  let repr = match option_repr(compiler, &inner, span) {
+    Some(repr) => repr,
+    None => return Type::Error,
+  };
+  let tmp = function.local("", repr.val_type());
+  function.instruction(Inst::LocalTee(tmp));
+  compile_unwrap(compiler, function, &inner, span);
+  compile_expr(compiler, function, rhs);
+  function.instruction(Inst::LocalGet(tmp));
+  compile_is_some(compiler, function, &inner, span);
+  function.instruction(Inst::Select);
+  inner
+}
@@ -1,37 +1,84 @@
+use crate::compile_ok;
 use crate::compile_stmts;
 use crate::ident_type_to_val_type;
+use crate::instance_ident;
+use crate::optimize;
+use crate::result_types;
+use crate::result_value;
+use crate::returns_nothing;
 use crate::setup_extern;
 use crate::Compiler;
 use crate::Function;
+use crate::FunctionBody;
+use crate::Inst;
+use crate::IntrinsicKind;
+use crate::ModuleFunction;
+use crate::ModuleGlobal;
+use crate::OptionRepr;
+use crate::Value;
+use crate::Word;
 
-use wasm_encoder::ConstExpr;
 use wasm_encoder::ExportKind;
 use wasm_encoder::GlobalType;
-use wasm_encoder::Instruction;
+use wasm_encoder::ValType;
 use whistle_ast::Expr;
 use whistle_ast::IdentExternFn;
+use whistle_ast::IdentImport;
 use whistle_ast::IdentType;
 use whistle_ast::IdentTyped;
+use whistle_ast::Literal;
+use whistle_ast::Primary;
 use whistle_ast::ProgramStmt;
 use whistle_ast::Stmt;
 use whistle_ast::Type;
+use whistle_ast::Unary;
 use whistle_common::CompilerErrorKind;
 use whistle_common::CompilerHandler;
+use whistle_common::Span;
+use whistle_common::Tip;
 
 pub fn compile_program(compiler: &mut Compiler, program: ProgramStmt) {
   match program {
+    ProgramStmt::FunctionDecl { ref generics, .. } if !generics.is_empty() => {
+      compile_generic_fn(compiler, program)
+    }
     ProgramStmt::Extern {
-      idents, namespace, ..
-    } => compile_extern(compiler, idents, namespace),
+      idents,
+      namespace,
+      tips,
+      ..
+    } => compile_extern(compiler, idents, namespace, tips),
+    ProgramStmt::Builtin { module, idents, .. } => compile_builtin(compiler, module, idents),
     ProgramStmt::FunctionDecl {
+      tips,
       export,
       inline,
       ident,
       params,
       ret_type,
       stmt,
+      span,
       ..
-    } => compile_fn(compiler, export, inline, ident, params, ret_type, stmt),
+    } => compile_fn(
+      compiler,
+      FnDecl {
+        tips,
+        export,
+        inline,
+        ident,
+        params,
+        ret_type,
+        stmts: stmt,
+        span,
+      },
+    ),
+    ProgramStmt::StructDecl { .. } | ProgramStmt::InterfaceDecl { .. } => {}
+    // The checker named the methods after the type they are implemented for.
+    ProgramStmt::ImplDecl { fns, .. } => {
+      for program in fns {
+        compile_program(compiler, program);
+      }
+    }
     ProgramStmt::ValDecl {
       ident_typed, val, ..
     } => compile_val(compiler, ident_typed, val),
@@ -44,85 +91,296 @@ pub fn compile_program(compiler: &mut Compiler, program: ProgramStmt) {
       imp_type: _imp_type,
       ..
     } => {}
+    ProgramStmt::Tip { .. } => {}
     _ => compiler
       .handler
       .throw(CompilerErrorKind::Unimplemented, program.span()),
   }
 }
 
-pub fn compile_fn(
-  compiler: &mut Compiler,
-  export: bool,
-  _inline: bool,
-  ident: String,
-  params: Vec<IdentTyped>,
-  ret_type: IdentType,
-  stmts: Vec<Stmt>,
-) {
+/// A function declaration, taken out of its `ProgramStmt` to be compiled.
+pub struct FnDecl {
+  pub tips: Vec<Tip>,
+  pub export: bool,
+  pub inline: bool,
+  pub ident: String,
+  pub params: Vec<IdentTyped>,
+  pub ret_type: IdentType,
+  pub stmts: Vec<Stmt>,
+  pub span: Span,
+}
+
+pub fn compile_fn(compiler: &mut Compiler, decl: FnDecl) {
+  let FnDecl {
+    tips,
+    export,
+    inline: _inline,
+    ident,
+    params,
+    ret_type,
+    stmts,
+    span,
+  } = decl;
   // TODO: Inline functions, would be done with a new field in the Compiler struct
   compiler.scope.enter_curr_scope();
 
+  let mut fun = Function::new(ident.clone());
   let mut types = Vec::new();
   for param in params {
-    types.push(ident_type_to_val_type(param.type_ident.to_type()));
+    let val_type = ident_type_to_val_type(compiler.query_type(param.type_ident.to_type()));
+    let idx = fun.param(&param.ident, val_type);
+    compiler.scope.get_sym_mut(&param.ident).unwrap().0 = idx;
+    types.push(val_type);
   }
 
-  let ret_type = ret_type.to_type();
+  let ret_type = compiler.query_type(ret_type.to_type());
 
-  let encoded_ret_type = if ret_type == Type::Primitive(whistle_common::Primitive::None) {
-    vec![]
-  } else {
-    vec![ident_type_to_val_type(ret_type)]
-  };
+  let encoded_ret_type = result_types(ret_type.clone());
+  fun.results = encoded_ret_type.clone();
 
   let sym = compiler.get_sym(&ident).unwrap().clone();
-  compiler.module.fns.function(sym.0);
-  compiler.module.types.function(types, encoded_ret_type);
-  if export {
-    compiler.module.exports.export(
-      if &ident == "main" { "_start" } else { &ident },
-      ExportKind::Func,
-      sym.0,
-    );
+  let mut export_name = if export {
+    Some(if &ident == "main" { "_start" } else { &ident }.to_string())
+  } else {
+    None
+  };
+  let mut cold = false;
+  for tip in tips {
+    match tip.ident.as_str() {
+      "export" => export_name = Some(tip_str(&tip).unwrap_or(ident.clone())),
+      "start" => compiler.module.start = Some(sym.0),
+      "cold" => cold = true,
+      _ => {}
+    }
+  }
+  if let Some(export_name) = export_name {
+    compiler
+      .module
+      .exports
+      .push((export_name, ExportKind::Func, sym.0));
   }
 
-  let mut fun = Function::new(ident);
+  // The checker made sure every path without a trailing value returns.
+  let tail = matches!(stmts.last(), Some(Stmt::Expr { .. }));
+  // A trailing value is stored in the result the same way as a returned one.
+  let mut stmts = stmts;
+  if let (Type::Option(_) | Type::Result(..), Some(Stmt::Expr { expr, span })) =
+    (&ret_type, stmts.last())
+  {
+    let ret = Stmt::Return {
+      ret_type: Some(expr.clone()),
+      span: *span,
+    };
+    *stmts.last_mut().unwrap() = ret;
+  }
+  compiler.memory.owner = Some(sym.0);
+  compiler.ret_type = Some(ret_type.clone());
   compile_stmts(compiler, &mut fun, stmts);
-  fun.instruction(Instruction::End);
-  compiler.module.code.function(&fun.into());
+  compiler.ret_type = None;
+  compiler.memory.owner = None;
+  match ret_type {
+    // Falling off the end of a function returning `none!E` succeeds.
+    Type::Result(ok, err) if !tail && returns_nothing(&ret_type) => {
+      compile_ok(compiler, &mut fun, &ok, &err, span)
+    }
+    _ if !tail && !fun.results.is_empty() => {
+      fun.instruction(Inst::Unreachable);
+    }
+    _ => {}
+  }
+  fun.instruction(Inst::End);
+  optimize(&mut fun, compiler.opt_level);
+  if compiler.emit_ir {
+    compiler.ir.push(fun.clone());
+  }
+  compiler.module.functions.push(ModuleFunction {
+    index: sym.0,
+    ident,
+    span,
+    params: types,
+    results: encoded_ret_type,
+    body: FunctionBody::Code(fun),
+    cold,
+  });
   compiler.scope.exit_scope();
 }
 
-pub fn compile_extern(compiler: &mut Compiler, idents: Vec<IdentExternFn>, namespace: String) {
+/// Compiles an instance of a generic function for each set of types it is
+/// called with. Every instance walks the same scopes.
+pub fn compile_generic_fn(compiler: &mut Compiler, program: ProgramStmt) {
+  let ident = match &program {
+    ProgramStmt::FunctionDecl { ident, .. } => ident.clone(),
+    _ => return,
+  };
+  let generic = match compiler.generic_fns.get(&ident) {
+    Some(generic) => generic.clone(),
+    None => return,
+  };
+  let idx = compiler.scope.idx;
+  for (types, _) in compiler.instances.get(&ident).cloned().unwrap_or_default() {
+    compiler.scope.idx = idx;
+    compiler.generics = generic
+      .params
+      .iter()
+      .map(|param| param.ident.clone())
+      .zip(types.iter().cloned())
+      .collect();
+    if let ProgramStmt::FunctionDecl {
+      tips,
+      export,
+      inline,
+      params,
+      ret_type,
+      stmt,
+      span,
+      ..
+    } = program.clone()
+    {
+      let decl = FnDecl {
+        tips,
+        export,
+        inline,
+        ident: instance_ident(&ident, &types),
+        params,
+        ret_type,
+        stmts: stmt,
+        span,
+      };
+      compile_fn(compiler, decl);
+    }
+  }
+  compiler.generics.clear();
+  compiler.scope.idx = idx + generic.scopes;
+}
+
+/// The value of a tip such as `#(export) "name"`, without its quotes.
+pub fn tip_str(tip: &Tip) -> Option<String> {
+  let value = tip.value.trim();
+  let value = value
+    .strip_prefix('"')
+    .and_then(|value| value.strip_suffix('"'))
+    .unwrap_or(value);
+  if value.is_empty() {
+    None
+  } else {
+    Some(value.to_string())
+  }
+}
+
+pub fn compile_extern(
+  compiler: &mut Compiler,
+  idents: Vec<IdentExternFn>,
+  namespace: String,
+  tips: Vec<Tip>,
+) {
+  // `#(import_module) "name"` imports the functions from another module.
+  let namespace = tips
+    .iter()
+    .filter(|tip| tip.ident == "import_module")
+    .find_map(tip_str)
+    .unwrap_or(namespace);
   for external_fn in &idents {
     let types = Type::Function {
       params: IdentTyped::vec_to_type(&external_fn.params),
       ret_type: Box::new(external_fn.ret_type.to_type()),
     };
-    setup_extern(compiler, &namespace, external_fn.ident.as_str(), types)
+    setup_extern(
+      compiler,
+      &namespace,
+      &external_fn.ident,
+      &external_fn.ident,
+      types,
+    )
+  }
+}
+
+pub fn compile_builtin(compiler: &mut Compiler, module: String, idents: Vec<IdentImport>) {
+  for import in idents {
+    let intrinsic = match compiler.builtins.get(&module, &import.ident) {
+      Some(intrinsic) => intrinsic.clone(),
+      None => continue,
+    };
+    let ident = import.as_ident.unwrap_or(import.ident);
+    // Inline intrinsics were collected by `check_builtin`.
+    if let IntrinsicKind::Import {
+      namespace,
+      ident: name,
+    } = intrinsic.kind.clone()
+    {
+      setup_extern(compiler, &namespace, &name, &ident, intrinsic.types())
+    }
   }
 }
 
-pub fn compile_val(compiler: &mut Compiler, ident_typed: IdentTyped, _val: Expr) {
+pub fn compile_val(compiler: &mut Compiler, ident_typed: IdentTyped, val: Expr) {
   let ident_type = compiler.get_sym(&ident_typed.ident).unwrap();
   let val_type = ident_type_to_val_type(ident_type.1.types.clone());
-  compiler.module.globals.global(
-    GlobalType {
+  compiler.module.globals.push(ModuleGlobal {
+    global_type: GlobalType {
       val_type,
       mutable: false,
     },
-    &ConstExpr::i32_const(0),
-  );
+    init: global_init(&val, &ident_type.1.types),
+  });
 }
 
-pub fn compile_var(compiler: &mut Compiler, ident_typed: IdentTyped, _val: Expr) {
+pub fn compile_var(compiler: &mut Compiler, ident_typed: IdentTyped, val: Expr) {
   let ident_type = compiler.get_sym(&ident_typed.ident).unwrap();
   let val_type = ident_type_to_val_type(ident_type.1.types.clone());
-  compiler.module.globals.global(
-    GlobalType {
+  compiler.module.globals.push(ModuleGlobal {
+    global_type: GlobalType {
       val_type,
       mutable: true,
     },
-    &ConstExpr::i32_const(0),
-  );
+    init: global_init(&val, &ident_type.1.types),
+  });
+}
+
+/// The value of a global initializer, which the checker folds to a literal.
+fn global_init(val: &Expr, types: &Type) -> Value {
+  let val_type = ident_type_to_val_type(types.clone());
+  let (val, err) = match val {
+    Expr::Err { expr, .. } => (&**expr, true),
+    val => (val, false),
+  };
+  let lit = match val {
+    Expr::Unary {
+      unary: Unary::Primary {
+        prim: Primary::Literal { lit, .. },
+        ..
+      },
+      ..
+    } => lit,
+    _ => return Value::zero(val_type),
+  };
+  if let Type::Option(inner) = types {
+    return match (OptionRepr::of(inner), lit) {
+      (Some(repr), Literal::None) => repr.none(),
+      (Some(repr), lit) => repr.wrap(literal_value(lit, ident_type_to_val_type(*inner.clone()))),
+      (None, _) => Value::zero(val_type),
+    };
+  }
+  if let Type::Result(ok, err_type) = types {
+    let word_type = if err { err_type } else { ok };
+    return match Word::of(word_type) {
+      Some(_) => result_value(
+        literal_value(lit, ident_type_to_val_type(*word_type.clone())),
+        err,
+      ),
+      None => Value::zero(val_type),
+    };
+  }
+  literal_value(lit, val_type)
+}
+
+fn literal_value(lit: &Literal, val_type: ValType) -> Value {
+  match (lit, val_type) {
+    (Literal::I32(val) | Literal::U32(val), ValType::I32) => Value::I32(*val as i32),
+    (Literal::I64(val) | Literal::U64(val), ValType::I64) => Value::I64(*val as i64),
+    (Literal::F32(val), ValType::F32) => Value::F32(*val as f32),
+    (Literal::F64(val), ValType::F64) => Value::F64(*val),
+    (Literal::Bool(val), ValType::I32) => Value::I32(*val as i32),
+    (Literal::Char(val), ValType::I32) => Value::I32(*val as i32),
+    _ => Value::zero(val_type),
+  }
 }
@@ -0,0 +1,177 @@
+use crate::compile_expr;
+use crate::BinOp;
+use crate::Compiler;
+use crate::Conversion;
+use crate::Function;
+use crate::Inst;
+use crate::Value;
+use whistle_common::CompilerErrorKind;
+
+use wasm_encoder::ValType;
+
+use whistle_ast::Expr;
+use whistle_ast::Primitive;
+use whistle_ast::Type;
+use whistle_common::CompilerHandler;
+use whistle_common::Span;
+
+/// The bit of a result that is set when it holds an error.
+const ERR: i64 = 1 << 32;
+
+/// How the value or error of a result is stored in the low 32 bits of the
+/// `i64` a result is packed into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Word {
+  /// 32-bit integers, `bool`, `char` and pointers, as they are.
+  Int,
+  /// `f32`, as its bits.
+  Float,
+  /// `none`, as zero.
+  Nothing,
+}
+
+impl Word {
+  /// How `types` is stored in a result. 64-bit values don't fit next to the
+  /// error bit and are not supported yet.
+  pub fn of(types: &Type) -> Option<Word> {
+    match types {
+      Type::Primitive(Primitive::F32) => Some(Word::Float),
+      Type::Primitive(Primitive::None) => Some(Word::Nothing),
+      Type::Primitive(
+        Primitive::I32 | Primitive::U32 | Primitive::Char | Primitive::Bool | Primitive::Str,
+      )
+      | Type::Array(_)
+      | Type::Struct(_)
+      | Type::Ident(_)
+      | Type::IdentType { .. } => Some(Word::Int),
+      _ => None,
+    }
+  }
+}
+
+/// `value` as a packed result, holding an error if `err`.
+pub fn result_value(value: Value, err: bool) -> Value {
+  let word = match value {
+    Value::I32(val) => val as u32 as i64,
+    Value::F32(val) => val.to_bits() as i64,
+    _ => 0,
+  };
+  Value::I64(if err { word | ERR } else { word })
+}
+
+/// The words of a result's value and error, reporting the ones that aren't
+/// supported.
+fn result_words(
+  compiler: &mut Compiler,
+  ok: &Type,
+  err: &Type,
+  span: Span,
+) -> Option<(Word, Word)> {
+  match (Word::of(ok), Word::of(err)) {
+    (Some(ok), Some(err)) => Some((ok, err)),
+    _ => {
+      if *ok != Type::Error && *err != Type::Error {
+        compiler
+          .handler
+          .throw(CompilerErrorKind::Unimplemented, span);
+      }
+      None
+    }
+  }
+}
+
+/// Widens the value on the stack to the low bits of an `i64`.
+fn compile_pack(function: &mut Function, word: Word) {
+  match word {
+    Word::Int => {}
+    Word::Float => {
+      function.instruction(Inst::Reinterpret(ValType::F32));
+    }
+    Word::Nothing => {
+      function.instruction(Inst::Const(Value::I32(0)));
+    }
+  }
+  function.instruction(Inst::Convert(Conversion::EXTEND_U));
+}
+
+/// Narrows the `i64` on the stack back to the value in its low bits.
+fn compile_unpack(function: &mut Function, word: Word) {
+  match word {
+    Word::Nothing => {
+      function.instruction(Inst::Drop);
+    }
+    Word::Int => {
+      function.instruction(Inst::Convert(Conversion::WRAP));
+    }
+    Word::Float => {
+      function
+        .instruction(Inst::Convert(Conversion::WRAP))
+        .instruction(Inst::Reinterpret(ValType::I32));
+    }
+  }
+}
+
+/// Turns the `T` on the stack, nothing for `none`, into a successful `T!E`.
+pub fn compile_ok(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  ok: &Type,
+  err: &Type,
+  span: Span,
+) {
+  if let Some((ok, _)) = result_words(compiler, ok, err, span) {
+    compile_pack(function, ok);
+  }
+}
+
+/// Compiles `err(expr)`, a `T!E` holding the error `expr`.
+pub fn compile_err(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  expr: Expr,
+  id: usize,
+  span: Span,
+) -> Type {
+  let types = compiler.query_type(Type::Var(id));
+  compile_expr(compiler, function, expr);
+  if let Type::Result(ok, err) = &types {
+    if let Some((_, err)) = result_words(compiler, ok, err, span) {
+      compile_pack(function, err);
+      function
+        .instruction(Inst::Const(Value::I64(ERR)))
+        .instruction(Inst::Binary(BinOp::Or, ValType::I64));
+    }
+  }
+  types
+}
+
+/// Compiles `expr?`. An error is returned as it is, since the function returns
+/// a result with the same error type and every result is packed the same way.
+pub fn compile_try(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  expr: Expr,
+  span: Span,
+) -> Type {
+  let (ok, err) = match compile_expr(compiler, function, expr) {
+    Type::Result(ok, err) => (*ok, *err),
+    _ => return Type::Error,
+  };
+  let ok_word = match result_words(compiler, &ok, &err, span) {
+    Some((ok, _)) => ok,
+    None => return Type::Error,
+  };
+  let tmp = function.local("", ValType::I64);
+  function
+    .instruction(Inst::LocalTee(tmp))
+    .instruction(Inst::Const(Value::I64(32)))
+    .instruction(Inst::Binary(BinOp::ShrU, ValType::I64))
+    .instruction(Inst::Convert(Conversion::WRAP))
+    .instruction(Inst::If)
+    .instruction(Inst::LocalGet(tmp))
+    .instruction(Inst::Return)
+    .instruction(Inst::End)
+    .instruction(Inst::LocalGet(tmp));
+  compile_unpack(function, ok_word);
+  ok
+}
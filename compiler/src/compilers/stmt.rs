@@ -1,32 +1,42 @@
+use crate::compile_assign;
 use crate::compile_expr;
-use crate::compile_tip_wasm_bytes;
+use crate::compile_ok;
+use crate::compile_unwrap;
+use crate::compile_value;
 use crate::ident_type_to_val_type;
+use crate::narrowed_ident;
 use crate::Compiler;
 use crate::Function;
 use crate::IndexedSymbol;
-use crate::Symbol;
+use crate::Inst;
+use crate::Label;
 use whistle_common::CompilerErrorKind;
 use whistle_common::CompilerHandler;
 use whistle_common::Span;
-
-use wasm_encoder::BlockType;
-use wasm_encoder::Instruction;
+use whistle_common::DUMMY;
 
 use whistle_ast::Expr;
 use whistle_ast::IdentTyped;
 use whistle_ast::Stmt;
+use whistle_ast::Type;
 use whistle_common::Tip;
 
+use wasm_encoder::ValType;
+
 pub fn compile_stmt(compiler: &mut Compiler, function: &mut Function, stmt: Stmt) {
+  function.mark(stmt.span());
   match stmt {
     Stmt::While { cond, do_stmt, .. } => compile_while(compiler, function, cond, do_stmt),
     Stmt::ValDecl {
       ident_typed, val, ..
     } => compile_val_decl(compiler, function, ident_typed, val),
-    Stmt::Assign { rhs, ident, span } => compile_assign(compiler, function, rhs, ident, span),
+    Stmt::Assign { lhs, rhs, .. } => {
+      compile_assign(compiler, function, lhs, rhs, false);
+    }
     Stmt::VarDecl {
       ident_typed, val, ..
     } => compile_var_decl(compiler, function, ident_typed, val),
+    Stmt::TupleDecl { idents, val, .. } => compile_tuple_decl(compiler, function, idents, val),
     Stmt::If {
       cond,
       then_stmt,
@@ -36,18 +46,19 @@ pub fn compile_stmt(compiler: &mut Compiler, function: &mut Function, stmt: Stmt
     Stmt::Tip { tip, span } => compile_tip(compiler, function, tip, span),
     Stmt::Expr { expr, .. } => compile_expr_stmt(compiler, function, expr),
     Stmt::Block { stmts, .. } => compile_block(compiler, function, stmts),
-    Stmt::Return { ret_type, .. } => compile_return(compiler, function, ret_type),
-    _ => compiler
-      .handler
-      .throw(CompilerErrorKind::Unimplemented, stmt.span()),
+    Stmt::Return { ret_type, span } => compile_return(compiler, function, ret_type, span),
+    Stmt::Break { span } => compile_loop_control(compiler, function, Label::Break, span),
+    Stmt::Continue { span } => compile_loop_control(compiler, function, Label::Continue, span),
   }
 }
 
 pub fn compile_stmts(compiler: &mut Compiler, function: &mut Function, stmts: Vec<Stmt>) {
   compiler.scope.enter_curr_scope();
+  function.enter_scope();
   for stmt in stmts {
     compile_stmt(compiler, function, stmt);
   }
+  function.exit_scope();
   compiler.scope.exit_scope();
 }
 
@@ -57,14 +68,33 @@ pub fn compile_while(
   cond: Expr,
   do_stmt: Vec<Stmt>,
 ) {
-  function.instruction(Instruction::Block(BlockType::Empty));
-  function.instruction(Instruction::Loop(BlockType::Empty));
+  function.instruction(Inst::Block);
+  function.instruction(Inst::Loop);
   compile_expr(compiler, function, cond);
-  function.instruction(Instruction::BrIf(1));
+  function.instruction(Inst::Eqz(ValType::I32));
+  function.instruction(Inst::BrIf(1));
+  compiler.labels.extend([Label::Break, Label::Continue]);
   compile_stmts(compiler, function, do_stmt);
-  function.instruction(Instruction::Br(0));
-  function.instruction(Instruction::End);
-  function.instruction(Instruction::End);
+  compiler.labels.truncate(compiler.labels.len() - 2);
+  function.instruction(Inst::Br(0));
+  function.instruction(Inst::End);
+  function.instruction(Inst::End);
+}
+
+pub fn compile_loop_control(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  label: Label,
+  span: Span,
+) {
+  match compiler.label_depth(label) {
+    Some(depth) => {
+      function.instruction(Inst::Br(depth));
+    }
+    None => compiler
+      .handler
+      .throw(CompilerErrorKind::ScopeNotInLoop, span),
+  }
 }
 
 pub fn compile_if(
@@ -74,16 +104,54 @@ pub fn compile_if(
   then_stmt: Vec<Stmt>,
   else_stmt: Option<Vec<Stmt>>,
 ) {
+  let narrowed = narrowed_ident(&cond);
   compile_expr(compiler, function, cond);
-  function.instruction(Instruction::If(BlockType::Empty));
-  compile_stmts(compiler, function, then_stmt);
+  function.instruction(Inst::If);
+  compiler.labels.push(Label::Other);
+  match &narrowed {
+    Some((ident, _, true)) => compile_narrowed(compiler, function, ident, then_stmt),
+    _ => compile_stmts(compiler, function, then_stmt),
+  }
 
   if let Some(stmt) = else_stmt {
-    function.instruction(Instruction::Else);
-    compile_stmts(compiler, function, stmt);
+    function.instruction(Inst::Else);
+    match &narrowed {
+      Some((ident, _, false)) => compile_narrowed(compiler, function, ident, stmt),
+      _ => compile_stmts(compiler, function, stmt),
+    }
   }
 
-  function.instruction(Instruction::End);
+  compiler.labels.pop();
+  function.instruction(Inst::End);
+}
+
+/// Compiles a branch where the option `ident` holds a value, binding the value
+/// to a new local like `check_narrowed`.
+pub fn compile_narrowed(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  ident: &str,
+  stmts: Vec<Stmt>,
+) {
+  let outer = compiler.get_sym(ident);
+  compiler.scope.enter_curr_scope();
+  function.enter_scope();
+  if let Ok(IndexedSymbol(idx, sym)) = outer {
+    if let Type::Option(inner) = sym.types {
+      function.instruction(if sym.global {
+        Inst::GlobalGet(idx)
+      } else {
+        Inst::LocalGet(idx)
+      });
+      compile_unwrap(compiler, function, &inner, DUMMY);
+      let local = function.local(ident, ident_type_to_val_type(*inner));
+      function.instruction(Inst::LocalSet(local));
+      compiler.scope.get_sym_mut(ident).unwrap().0 = local;
+    }
+  }
+  compile_stmts(compiler, function, stmts);
+  function.exit_scope();
+  compiler.scope.exit_scope();
 }
 
 pub fn compile_val_decl(
@@ -92,10 +160,14 @@ pub fn compile_val_decl(
   ident: IdentTyped,
   val: Expr,
 ) {
-  let types = compile_expr(compiler, function, val);
-  let sym = compiler.get_sym(&ident.ident).unwrap();
-  function.local(sym.0, ident_type_to_val_type(types));
-  function.instruction(Instruction::LocalSet(sym.0));
+  let slot = compiler.get_sym(&ident.ident).map(|sym| sym.1.types);
+  let types = match &slot {
+    Ok(slot) => compile_value(compiler, function, val, slot),
+    Err(_) => compile_expr(compiler, function, val),
+  };
+  let idx = function.local(&ident.ident, ident_type_to_val_type(types));
+  compiler.scope.get_sym_mut(&ident.ident).unwrap().0 = idx;
+  function.instruction(Inst::LocalSet(idx));
 }
 
 pub fn compile_var_decl(
@@ -104,50 +176,70 @@ pub fn compile_var_decl(
   ident: IdentTyped,
   val: Expr,
 ) {
-  let types = compile_expr(compiler, function, val);
-  let sym = compiler.get_sym(&ident.ident).unwrap();
-  function.local(sym.0, ident_type_to_val_type(types));
-  function.instruction(Instruction::LocalSet(sym.0));
+  let slot = compiler.get_sym(&ident.ident).map(|sym| sym.1.types);
+  let types = match &slot {
+    Ok(slot) => compile_value(compiler, function, val, slot),
+    Err(_) => compile_expr(compiler, function, val),
+  };
+  let idx = function.local(&ident.ident, ident_type_to_val_type(types));
+  compiler.scope.get_sym_mut(&ident.ident).unwrap().0 = idx;
+  function.instruction(Inst::LocalSet(idx));
+}
+
+pub fn compile_tuple_decl(
+  compiler: &mut Compiler,
+  function: &mut Function,
+  idents: Vec<IdentTyped>,
+  val: Expr,
+) {
+  let types = match compile_expr(compiler, function, val) {
+    Type::Tuple(types) => types,
+    _ => return,
+  };
+  let mut locals = Vec::new();
+  for (ident, types) in idents.iter().zip(types) {
+    let idx = function.local(&ident.ident, ident_type_to_val_type(types));
+    compiler.scope.get_sym_mut(&ident.ident).unwrap().0 = idx;
+    locals.push(idx);
+  }
+  // The last element is on top of the stack.
+  for idx in locals.into_iter().rev() {
+    function.instruction(Inst::LocalSet(idx));
+  }
 }
 
 pub fn compile_block(compiler: &mut Compiler, function: &mut Function, stmts: Vec<Stmt>) {
   compiler.scope.enter_curr_scope();
-  function.instruction(Instruction::Loop(BlockType::Empty));
+  function.enter_scope();
+  function.instruction(Inst::Loop);
+  compiler.labels.push(Label::Other);
   for stmt in stmts {
     compile_stmt(compiler, function, stmt)
   }
-  function.instruction(Instruction::End);
+  compiler.labels.pop();
+  function.instruction(Inst::End);
+  function.exit_scope();
   compiler.scope.exit_scope();
 }
 
-pub fn compile_return(compiler: &mut Compiler, function: &mut Function, expr: Option<Expr>) {
-  if let Some(expr) = expr {
-    compile_expr(compiler, function, expr);
-  }
-  function.instruction(Instruction::Return);
-}
-
-pub fn compile_assign(
+pub fn compile_return(
   compiler: &mut Compiler,
   function: &mut Function,
-  rhs: Expr,
-  ident: String,
+  expr: Option<Expr>,
   span: Span,
 ) {
-  let sym = match compiler.get_sym(&ident) {
-    Ok(sym) => sym.clone(),
-    Err(err) => {
-      compiler.handler.throw(err, span);
-      IndexedSymbol(0, Symbol::default())
+  match (expr, compiler.ret_type.clone()) {
+    (Some(expr), Some(ret_type)) => {
+      compile_value(compiler, function, expr, &ret_type);
     }
-  };
-  compile_expr(compiler, function, rhs);
-
-  if sym.1.global {
-    function.instruction(Instruction::GlobalSet(sym.0));
-  } else {
-    function.instruction(Instruction::LocalSet(sym.0));
+    (Some(expr), None) => {
+      compile_expr(compiler, function, expr);
+    }
+    // A bare `return` from a function returning `none!E` succeeds.
+    (None, Some(Type::Result(ok, err))) => compile_ok(compiler, function, &ok, &err, span),
+    (None, _) => {}
   }
+  function.instruction(Inst::Return);
 }
 
 pub fn compile_expr_stmt(compiler: &mut Compiler, function: &mut Function, expr: Expr) {
@@ -155,10 +247,7 @@ pub fn compile_expr_stmt(compiler: &mut Compiler, function: &mut Function, expr:
 }
 
 pub fn compile_tip(compiler: &mut Compiler, function: &mut Function, tip: Tip, span: Span) {
-  match tip.ident.as_str() {
-    "wasm_bytes" => compile_tip_wasm_bytes(compiler, function, tip, span),
-    _ => compiler
-      .handler
-      .throw(CompilerErrorKind::Unimplemented, span),
+  if let Some(handler) = compiler.tips.get(&tip.ident) {
+    handler.compile(compiler, function, &tip, span);
   }
 }
@@ -1,5 +1,13 @@
+use whistle_common::CompilerErrorKind;
+use whistle_common::CompilerHandler;
+use whistle_common::Span;
 use whistle_common::Tip;
 
+use wasm_encoder::ValType;
+use wasmparser::Payload;
+use wast::parser::ParseBuffer;
+use wast::Wat;
+
 use crate::{Compiler, Function};
 
 pub fn compile_tip_wasm_bytes(
@@ -19,3 +27,97 @@ pub fn compile_tip_wasm_bytes(
     .collect::<Vec<u8>>();
   function.raw(data);
 }
+
+pub fn compile_tip_wasm(compiler: &mut Compiler, function: &mut Function, tip: Tip, span: Span) {
+  // The instructions are wrapped in a function declaring the same params and
+  // locals as the one being compiled, so `$ident` resolves to the Whistle local.
+  let visible = function.locals.visible();
+  let mut text = String::from("(module (func");
+  for idx in 0..function.locals.len() {
+    let kind = if (idx as usize) < function.locals.params.len() {
+      "param"
+    } else {
+      "local"
+    };
+    let name = match visible.iter().find(|(_, other)| *other == idx) {
+      Some((ident, _)) if is_wat_ident(ident) => format!("${} ", ident),
+      _ => String::new(),
+    };
+    let val_type = match val_type_to_wat(function.locals.val_type(idx).unwrap()) {
+      Ok(val_type) => val_type,
+      Err(err) => return compiler.handler.throw(err, span),
+    };
+    text.push_str(&format!(" ({} {}{})", kind, name, val_type));
+  }
+  text.push('\n');
+  let prefix = text.len();
+  text.push_str(&tip.value);
+  text.push_str("\n))");
+
+  match encode_wat(&text) {
+    Ok(bytes) => match function_body(&bytes) {
+      Some(body) => {
+        function.inline(body);
+      }
+      None => compiler.handler.throw(
+        CompilerErrorKind::MalformedInstruction {
+          message: String::from("could not encode instructions"),
+        },
+        span,
+      ),
+    },
+    Err(err) => {
+      let span = match err.span().offset().checked_sub(prefix) {
+        Some(offset) if offset <= tip.value.len() => {
+          let start = tip.value_span.start + tip.value[..offset].chars().count();
+          Span { start, end: start }
+        }
+        _ => span,
+      };
+      compiler.handler.throw(
+        CompilerErrorKind::MalformedInstruction {
+          message: err.message(),
+        },
+        span,
+      )
+    }
+  }
+}
+
+fn encode_wat(text: &str) -> Result<Vec<u8>, wast::Error> {
+  let buf = ParseBuffer::new(text)?;
+  let mut wat = wast::parser::parse::<Wat>(&buf)?;
+  wat.encode()
+}
+
+fn function_body(bytes: &[u8]) -> Option<Vec<u8>> {
+  for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+    if let Ok(Payload::CodeSectionEntry(body)) = payload {
+      let start = body.get_operators_reader().ok()?.original_position();
+      // Drop the trailing `end` of the wrapper function.
+      let end = body.range().end - 1;
+      return bytes.get(start..end).map(|body| body.to_vec());
+    }
+  }
+  None
+}
+
+fn is_wat_ident(ident: &str) -> bool {
+  !ident.is_empty()
+    && ident
+      .chars()
+      .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+fn val_type_to_wat(val_type: ValType) -> Result<&'static str, CompilerErrorKind> {
+  match val_type {
+    ValType::I32 => Ok("i32"),
+    ValType::I64 => Ok("i64"),
+    ValType::F32 => Ok("f32"),
+    ValType::F64 => Ok("f64"),
+    ValType::V128 => Ok("v128"),
+    _ => Err(CompilerErrorKind::MalformedInstruction {
+      message: format!("locals of type {:?} can't be used in #(wasm)", val_type),
+    }),
+  }
+}
@@ -1,16 +1,25 @@
+use crate::BinOp;
+use crate::Inst;
+use crate::LocalAllocator;
+use crate::OptionRepr;
 use whistle_common::CompilerErrorKind;
 
-use wasm_encoder::Instruction;
 use wasm_encoder::ValType;
 
 use whistle_ast::Operator;
 use whistle_ast::Primitive;
 use whistle_ast::Type;
+use whistle_common::Span;
 
 pub fn ident_type_to_val_type(ident_type: Type) -> ValType {
   match ident_type {
     Type::Primitive(prim) => prim_to_val_type(prim),
-    Type::Array { .. } => ValType::I32,
+    // Arrays, structs and named types are pointers into memory.
+    Type::Array { .. } | Type::Struct(_) | Type::Ident(_) | Type::IdentType { .. } => ValType::I32,
+    // Unsupported options are reported where they are built or read.
+    Type::Option(inner) => OptionRepr::of(&inner).map_or(ValType::I64, OptionRepr::val_type),
+    // Results are packed into an `i64`, see `Word`.
+    Type::Result(..) => ValType::I64,
     _ => panic!("{:?}", ident_type),
   }
 }
@@ -33,101 +42,35 @@ pub fn prim_to_val_type(prim: Primitive) -> ValType {
   }
 }
 
-pub fn operator_to_instruction<'a>(
-  op: &Operator,
-  ident_type: &Type,
-) -> Result<Instruction<'a>, CompilerErrorKind> {
+pub fn operator_to_inst(op: &Operator, ident_type: &Type) -> Result<Inst, CompilerErrorKind> {
   if let Type::Primitive(prim) = ident_type {
-    match op {
-      Operator::Add => match prim {
-        Primitive::I32 => Ok(Instruction::I32Add),
-        Primitive::U32 => Ok(Instruction::I32Add),
-        Primitive::I64 => Ok(Instruction::I64Add),
-        Primitive::U64 => Ok(Instruction::I64Add),
-        Primitive::F32 => Ok(Instruction::F32Add),
-        Primitive::F64 => Ok(Instruction::F64Add),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::Sub => match prim {
-        Primitive::I32 => Ok(Instruction::I32Sub),
-        Primitive::U32 => Ok(Instruction::I32Sub),
-        Primitive::I64 => Ok(Instruction::I64Sub),
-        Primitive::U64 => Ok(Instruction::I64Sub),
-        Primitive::F32 => Ok(Instruction::F32Sub),
-        Primitive::F64 => Ok(Instruction::F64Sub),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::Mul => match prim {
-        Primitive::I32 => Ok(Instruction::I32Mul),
-        Primitive::U32 => Ok(Instruction::I32Mul),
-        Primitive::I64 => Ok(Instruction::I64Mul),
-        Primitive::U64 => Ok(Instruction::I64Mul),
-        Primitive::F32 => Ok(Instruction::F32Mul),
-        Primitive::F64 => Ok(Instruction::F64Mul),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::Div => match prim {
-        Primitive::I32 => Ok(Instruction::I32DivS),
-        Primitive::U32 => Ok(Instruction::I32DivU),
-        Primitive::I64 => Ok(Instruction::I64DivS),
-        Primitive::U64 => Ok(Instruction::I64DivU),
-        Primitive::F32 => Ok(Instruction::F32Div),
-        Primitive::F64 => Ok(Instruction::F64Div),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::Mod => match prim {
-        Primitive::I32 => Ok(Instruction::I32RemS),
-        Primitive::U32 => Ok(Instruction::I32RemU),
-        Primitive::I64 => Ok(Instruction::I64RemS),
-        Primitive::U64 => Ok(Instruction::I64RemU),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::Eq => match prim {
-        Primitive::I32 => Ok(Instruction::I32Eq),
-        Primitive::U32 => Ok(Instruction::I32Eq),
-        Primitive::I64 => Ok(Instruction::I64Eq),
-        Primitive::U64 => Ok(Instruction::I64Eq),
-        Primitive::F32 => Ok(Instruction::F32Eq),
-        Primitive::F64 => Ok(Instruction::F64Eq),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::NotEq => match prim {
-        Primitive::I32 => Ok(Instruction::I32Ne),
-        Primitive::U32 => Ok(Instruction::I32Ne),
-        Primitive::I64 => Ok(Instruction::I64Ne),
-        Primitive::U64 => Ok(Instruction::I64Ne),
-        Primitive::F32 => Ok(Instruction::F32Ne),
-        Primitive::F64 => Ok(Instruction::F64Ne),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::LessThan => match prim {
-        Primitive::I32 => Ok(Instruction::I32LeS),
-        Primitive::U32 => Ok(Instruction::I32LeU),
-        Primitive::I64 => Ok(Instruction::I64LeS),
-        Primitive::U64 => Ok(Instruction::I64LeU),
-        Primitive::F32 => Ok(Instruction::F32Le),
-        Primitive::F64 => Ok(Instruction::F64Le),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      Operator::GreaterThan => match prim {
-        Primitive::I32 => Ok(Instruction::I32GeS),
-        Primitive::U32 => Ok(Instruction::I32GeU),
-        Primitive::I64 => Ok(Instruction::I64GeS),
-        Primitive::U64 => Ok(Instruction::I64GeU),
-        Primitive::F32 => Ok(Instruction::F32Ge),
-        Primitive::F64 => Ok(Instruction::F64Ge),
-
-        _ => Err(CompilerErrorKind::UnknownOperator),
-      },
-      _ => Err(CompilerErrorKind::UnknownOperator),
+    let unsigned = matches!(prim, Primitive::U32 | Primitive::U64);
+    let val_type = match prim {
+      Primitive::I32 | Primitive::U32 => ValType::I32,
+      Primitive::I64 | Primitive::U64 => ValType::I64,
+      Primitive::F32 => ValType::F32,
+      Primitive::F64 => ValType::F64,
+      _ => return Err(CompilerErrorKind::UnknownOperator),
+    };
+    let bin_op = match op {
+      Operator::Add => BinOp::Add,
+      Operator::Sub => BinOp::Sub,
+      Operator::Mul => BinOp::Mul,
+      Operator::Div if unsigned => BinOp::DivU,
+      Operator::Div => BinOp::DivS,
+      Operator::Mod if unsigned => BinOp::RemU,
+      Operator::Mod => BinOp::RemS,
+      Operator::Eq => BinOp::Eq,
+      Operator::NotEq => BinOp::Ne,
+      Operator::LessThan if unsigned => BinOp::LeU,
+      Operator::LessThan => BinOp::LeS,
+      Operator::GreaterThan if unsigned => BinOp::GeU,
+      Operator::GreaterThan => BinOp::GeS,
+      _ => return Err(CompilerErrorKind::UnknownOperator),
+    };
+    match bin_op.instruction(val_type) {
+      Some(_) => Ok(Inst::Binary(bin_op, val_type)),
+      None => Err(CompilerErrorKind::UnknownOperator),
     }
   } else {
     Err(CompilerErrorKind::Unimplemented)
@@ -232,28 +175,33 @@ pub fn operator_to_ident_type(op: &Operator, lhs: &Type) -> Result<Type, Compile
   }
 }
 
-pub struct Function<'a> {
+#[derive(Debug, Clone)]
+pub struct Function {
   pub ident: String,
-  pub instructions: Vec<Instruction<'a>>,
+  pub instructions: Vec<Inst>,
+  pub results: Vec<ValType>,
   pub raw: Vec<u8>,
   pub is_raw: bool,
-  locals: Vec<(u32, ValType)>,
+  pub locals: LocalAllocator,
+  /// Source spans by the index of the first instruction compiled from them.
+  pub marks: Vec<(usize, Span)>,
 }
 
-impl<'a> Function<'a> {
+impl Function {
   pub fn new(ident: String) -> Self {
     Function {
       ident,
       instructions: Vec::new(),
+      results: Vec::new(),
       raw: Vec::new(),
       is_raw: false,
-      locals: Vec::new(),
+      locals: LocalAllocator::new(),
+      marks: Vec::new(),
     }
   }
 
-  pub fn instruction(&mut self, instruction: Instruction<'a>) -> &mut Self {
-    self.instructions.push(instruction.clone());
-    // println!("{:?}", instruction);
+  pub fn instruction(&mut self, inst: Inst) -> &mut Self {
+    self.instructions.push(inst);
     self
   }
 
@@ -262,24 +210,58 @@ impl<'a> Function<'a> {
     self.raw = raw;
   }
 
-  pub fn local(&mut self, idx: u32, val_type: ValType) -> &mut Self {
-    self.locals.push((idx, val_type));
-    // println!("{:?}", (idx, val_type));
+  pub fn inline(&mut self, raw: Vec<u8>) -> &mut Self {
+    self.instructions.push(Inst::Raw(raw));
     self
   }
+
+  pub fn mark(&mut self, span: Span) {
+    self.marks.push((self.instructions.len(), span));
+  }
+
+  /// The byte offset of each mark, relative to the start of the instructions.
+  pub fn offsets(&self) -> Vec<(u32, Span)> {
+    let mut offsets = Vec::new();
+    let mut marks = self.marks.iter().peekable();
+    let mut bytes = Vec::new();
+    for (idx, inst) in self.instructions.iter().enumerate() {
+      while let Some((_, span)) = marks.next_if(|(mark, _)| *mark == idx) {
+        offsets.push((bytes.len() as u32, *span));
+      }
+      inst.encode(&mut bytes);
+    }
+    offsets
+  }
+
+  pub fn param(&mut self, ident: &str, val_type: ValType) -> u32 {
+    self.locals.param(ident, val_type)
+  }
+
+  pub fn local(&mut self, ident: &str, val_type: ValType) -> u32 {
+    self.locals.alloc(ident, val_type)
+  }
+
+  pub fn enter_scope(&mut self) {
+    self.locals.enter_scope()
+  }
+
+  pub fn exit_scope(&mut self) {
+    self.locals.exit_scope()
+  }
 }
 
-impl From<Function<'_>> for wasm_encoder::Function {
+impl From<Function> for wasm_encoder::Function {
   fn from(function: Function) -> wasm_encoder::Function {
-    let locals: Vec<_> = function.locals.iter().map(|(_, l)| *l).collect();
-    let mut res = wasm_encoder::Function::new_with_locals_types(locals);
+    let mut res = wasm_encoder::Function::new_with_locals_types(function.locals.locals);
     if function.is_raw {
       res.raw::<Vec<u8>>(function.raw);
       return res;
     }
-    for instruction in function.instructions {
-      res.instruction(&instruction);
+    let mut bytes = Vec::new();
+    for inst in &function.instructions {
+      inst.encode(&mut bytes);
     }
+    res.raw(bytes);
     res
   }
 }
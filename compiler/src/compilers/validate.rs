@@ -0,0 +1,38 @@
+use crate::Compiler;
+
+use wasmparser::Payload;
+use wasmparser::Validator;
+use whistle_common::CompilerErrorKind;
+use whistle_common::CompilerHandler;
+use whistle_common::Span;
+
+pub fn validate_module(compiler: &mut Compiler, bytes: &[u8]) {
+  let err = match Validator::new().validate_all(bytes) {
+    Ok(_) => return,
+    Err(err) => err,
+  };
+
+  let mut function = None;
+  let mut span = Span { start: 0, end: 0 };
+  let mut idx = 0;
+  for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+    if let Ok(Payload::CodeSectionEntry(body)) = payload {
+      if body.range().contains(&err.offset()) {
+        if let Some((ident, fn_span)) = compiler.module.code.get(idx) {
+          function = Some(ident.clone());
+          span = *fn_span;
+        }
+        break;
+      }
+      idx += 1;
+    }
+  }
+
+  compiler.handler.throw(
+    CompilerErrorKind::InvalidModule {
+      message: err.message().to_string(),
+      function,
+    },
+    span,
+  );
+}
@@ -0,0 +1,524 @@
+use crate::Compiler;
+use crate::Function;
+
+use std::fmt;
+use wasm_encoder::BlockType;
+use wasm_encoder::Encode;
+use wasm_encoder::Instruction;
+use wasm_encoder::MemArg;
+use wasm_encoder::ValType;
+
+/// A constant of one of the wasm value types.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+  I32(i32),
+  I64(i64),
+  F32(f32),
+  F64(f64),
+}
+
+impl Value {
+  pub fn zero(val_type: ValType) -> Self {
+    match val_type {
+      ValType::I64 => Value::I64(0),
+      ValType::F32 => Value::F32(0.0),
+      ValType::F64 => Value::F64(0.0),
+      _ => Value::I32(0),
+    }
+  }
+
+  pub fn val_type(&self) -> ValType {
+    match self {
+      Value::I32(_) => ValType::I32,
+      Value::I64(_) => ValType::I64,
+      Value::F32(_) => ValType::F32,
+      Value::F64(_) => ValType::F64,
+    }
+  }
+}
+
+/// Binary operators. Floats ignore the signedness of `DivS`, `LtS` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+  Add,
+  Sub,
+  Mul,
+  DivS,
+  DivU,
+  RemS,
+  RemU,
+  And,
+  Or,
+  Xor,
+  Shl,
+  ShrS,
+  ShrU,
+  Eq,
+  Ne,
+  LtS,
+  LtU,
+  GtS,
+  GtU,
+  LeS,
+  LeU,
+  GeS,
+  GeU,
+}
+
+impl BinOp {
+  /// The wasm instruction for this operator on `val_type`, if there is one.
+  pub fn instruction(self, val_type: ValType) -> Option<Instruction<'static>> {
+    use BinOp::*;
+    Some(match (val_type, self) {
+      (ValType::I32, Add) => Instruction::I32Add,
+      (ValType::I32, Sub) => Instruction::I32Sub,
+      (ValType::I32, Mul) => Instruction::I32Mul,
+      (ValType::I32, DivS) => Instruction::I32DivS,
+      (ValType::I32, DivU) => Instruction::I32DivU,
+      (ValType::I32, RemS) => Instruction::I32RemS,
+      (ValType::I32, RemU) => Instruction::I32RemU,
+      (ValType::I32, And) => Instruction::I32And,
+      (ValType::I32, Or) => Instruction::I32Or,
+      (ValType::I32, Xor) => Instruction::I32Xor,
+      (ValType::I32, Shl) => Instruction::I32Shl,
+      (ValType::I32, ShrS) => Instruction::I32ShrS,
+      (ValType::I32, ShrU) => Instruction::I32ShrU,
+      (ValType::I32, Eq) => Instruction::I32Eq,
+      (ValType::I32, Ne) => Instruction::I32Ne,
+      (ValType::I32, LtS) => Instruction::I32LtS,
+      (ValType::I32, LtU) => Instruction::I32LtU,
+      (ValType::I32, GtS) => Instruction::I32GtS,
+      (ValType::I32, GtU) => Instruction::I32GtU,
+      (ValType::I32, LeS) => Instruction::I32LeS,
+      (ValType::I32, LeU) => Instruction::I32LeU,
+      (ValType::I32, GeS) => Instruction::I32GeS,
+      (ValType::I32, GeU) => Instruction::I32GeU,
+
+      (ValType::I64, Add) => Instruction::I64Add,
+      (ValType::I64, Sub) => Instruction::I64Sub,
+      (ValType::I64, Mul) => Instruction::I64Mul,
+      (ValType::I64, DivS) => Instruction::I64DivS,
+      (ValType::I64, DivU) => Instruction::I64DivU,
+      (ValType::I64, RemS) => Instruction::I64RemS,
+      (ValType::I64, RemU) => Instruction::I64RemU,
+      (ValType::I64, And) => Instruction::I64And,
+      (ValType::I64, Or) => Instruction::I64Or,
+      (ValType::I64, Xor) => Instruction::I64Xor,
+      (ValType::I64, Shl) => Instruction::I64Shl,
+      (ValType::I64, ShrS) => Instruction::I64ShrS,
+      (ValType::I64, ShrU) => Instruction::I64ShrU,
+      (ValType::I64, Eq) => Instruction::I64Eq,
+      (ValType::I64, Ne) => Instruction::I64Ne,
+      (ValType::I64, LtS) => Instruction::I64LtS,
+      (ValType::I64, LtU) => Instruction::I64LtU,
+      (ValType::I64, GtS) => Instruction::I64GtS,
+      (ValType::I64, GtU) => Instruction::I64GtU,
+      (ValType::I64, LeS) => Instruction::I64LeS,
+      (ValType::I64, LeU) => Instruction::I64LeU,
+      (ValType::I64, GeS) => Instruction::I64GeS,
+      (ValType::I64, GeU) => Instruction::I64GeU,
+
+      (ValType::F32, Add) => Instruction::F32Add,
+      (ValType::F32, Sub) => Instruction::F32Sub,
+      (ValType::F32, Mul) => Instruction::F32Mul,
+      (ValType::F32, DivS | DivU) => Instruction::F32Div,
+      (ValType::F32, Eq) => Instruction::F32Eq,
+      (ValType::F32, Ne) => Instruction::F32Ne,
+      (ValType::F32, LtS | LtU) => Instruction::F32Lt,
+      (ValType::F32, GtS | GtU) => Instruction::F32Gt,
+      (ValType::F32, LeS | LeU) => Instruction::F32Le,
+      (ValType::F32, GeS | GeU) => Instruction::F32Ge,
+
+      (ValType::F64, Add) => Instruction::F64Add,
+      (ValType::F64, Sub) => Instruction::F64Sub,
+      (ValType::F64, Mul) => Instruction::F64Mul,
+      (ValType::F64, DivS | DivU) => Instruction::F64Div,
+      (ValType::F64, Eq) => Instruction::F64Eq,
+      (ValType::F64, Ne) => Instruction::F64Ne,
+      (ValType::F64, LtS | LtU) => Instruction::F64Lt,
+      (ValType::F64, GtS | GtU) => Instruction::F64Gt,
+      (ValType::F64, LeS | LeU) => Instruction::F64Le,
+      (ValType::F64, GeS | GeU) => Instruction::F64Ge,
+
+      _ => return None,
+    })
+  }
+
+  /// Whether the result is a boolean rather than an operand type.
+  pub fn is_comparison(self) -> bool {
+    use BinOp::*;
+    matches!(
+      self,
+      Eq | Ne | LtS | LtU | GtS | GtU | LeS | LeU | GeS | GeU
+    )
+  }
+}
+
+/// An instruction of the mid-level IR.
+///
+/// The IR is a typed stack machine: operators carry the type of their operands
+/// instead of being spelled out per type, so passes can reason about them
+/// without matching on every wasm opcode. [`crate::lower_all`] lowers the
+/// checked AST into it and [`crate::Module::finish`] encodes it as wasm.
+#[derive(Debug, Clone)]
+pub enum Inst {
+  Const(Value),
+  LocalGet(u32),
+  LocalSet(u32),
+  LocalTee(u32),
+  GlobalGet(u32),
+  GlobalSet(u32),
+  Binary(BinOp, ValType),
+  Eqz(ValType),
+  Convert(Conversion),
+  /// Reinterprets the bits of the operand, a float as the integer of the same
+  /// width or the other way around.
+  Reinterpret(ValType),
+  Load(ValType, MemArg),
+  Load8U(MemArg),
+  Store(ValType, MemArg),
+  Call(u32),
+  Select,
+  Drop,
+  Block,
+  Loop,
+  If,
+  Else,
+  End,
+  Br(u32),
+  BrIf(u32),
+  Return,
+  Unreachable,
+  MemorySize,
+  MemoryGrow,
+  /// Pre-encoded instructions, such as inline WAT from a `#(wasm)` tip.
+  Raw(Vec<u8>),
+}
+
+impl Inst {
+  pub fn is_block_start(&self) -> bool {
+    matches!(self, Inst::Block | Inst::Loop | Inst::If)
+  }
+
+  pub fn instruction(&self) -> Option<Instruction<'static>> {
+    Some(match self {
+      Inst::Const(Value::I32(val)) => Instruction::I32Const(*val),
+      Inst::Const(Value::I64(val)) => Instruction::I64Const(*val),
+      Inst::Const(Value::F32(val)) => Instruction::F32Const(*val),
+      Inst::Const(Value::F64(val)) => Instruction::F64Const(*val),
+      Inst::LocalGet(idx) => Instruction::LocalGet(*idx),
+      Inst::LocalSet(idx) => Instruction::LocalSet(*idx),
+      Inst::LocalTee(idx) => Instruction::LocalTee(*idx),
+      Inst::GlobalGet(idx) => Instruction::GlobalGet(*idx),
+      Inst::GlobalSet(idx) => Instruction::GlobalSet(*idx),
+      Inst::Binary(op, val_type) => op
+        .instruction(*val_type)
+        .expect("binary operators are checked when lowered"),
+      Inst::Eqz(ValType::I64) => Instruction::I64Eqz,
+      Inst::Eqz(_) => Instruction::I32Eqz,
+      Inst::Convert(conversion) => conversion.instruction.clone(),
+      Inst::Reinterpret(ValType::F32) => Instruction::I32ReinterpretF32,
+      Inst::Reinterpret(ValType::F64) => Instruction::I64ReinterpretF64,
+      Inst::Reinterpret(ValType::I32) => Instruction::F32ReinterpretI32,
+      Inst::Reinterpret(ValType::I64) => Instruction::F64ReinterpretI64,
+      Inst::Reinterpret(_) => return None,
+      Inst::Load(ValType::I64, memarg) => Instruction::I64Load(*memarg),
+      Inst::Load(ValType::F32, memarg) => Instruction::F32Load(*memarg),
+      Inst::Load(ValType::F64, memarg) => Instruction::F64Load(*memarg),
+      Inst::Load(_, memarg) => Instruction::I32Load(*memarg),
+      Inst::Load8U(memarg) => Instruction::I32Load8U(*memarg),
+      Inst::Store(ValType::I64, memarg) => Instruction::I64Store(*memarg),
+      Inst::Store(ValType::F32, memarg) => Instruction::F32Store(*memarg),
+      Inst::Store(ValType::F64, memarg) => Instruction::F64Store(*memarg),
+      Inst::Store(_, memarg) => Instruction::I32Store(*memarg),
+      Inst::Call(idx) => Instruction::Call(*idx),
+      Inst::Select => Instruction::Select,
+      Inst::Drop => Instruction::Drop,
+      Inst::Block => Instruction::Block(BlockType::Empty),
+      Inst::Loop => Instruction::Loop(BlockType::Empty),
+      Inst::If => Instruction::If(BlockType::Empty),
+      Inst::Else => Instruction::Else,
+      Inst::End => Instruction::End,
+      Inst::Br(depth) => Instruction::Br(*depth),
+      Inst::BrIf(depth) => Instruction::BrIf(*depth),
+      Inst::Return => Instruction::Return,
+      Inst::Unreachable => Instruction::Unreachable,
+      Inst::MemorySize => Instruction::MemorySize(0),
+      Inst::MemoryGrow => Instruction::MemoryGrow(0),
+      Inst::Raw(_) => return None,
+    })
+  }
+
+  pub fn encode(&self, sink: &mut Vec<u8>) {
+    match self {
+      Inst::Raw(raw) => sink.extend(raw),
+      _ => {
+        if let Some(instruction) = self.instruction() {
+          instruction.encode(sink)
+        }
+      }
+    }
+  }
+}
+
+/// Converts the operand from one number type to another, reading integers as
+/// signed if set. Float to integer conversions saturate. Only made for the
+/// pairs of types wasm has an instruction for.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+  from: ValType,
+  to: ValType,
+  signed: bool,
+  instruction: Instruction<'static>,
+}
+
+impl Conversion {
+  /// `i32` to `i64`, zero extending.
+  pub const EXTEND_U: Conversion = Conversion {
+    from: ValType::I32,
+    to: ValType::I64,
+    signed: false,
+    instruction: Instruction::I64ExtendI32U,
+  };
+  /// `i64` to `i32`, keeping the low bits.
+  pub const WRAP: Conversion = Conversion {
+    from: ValType::I64,
+    to: ValType::I32,
+    signed: false,
+    instruction: Instruction::I32WrapI64,
+  };
+
+  pub fn new(from: ValType, to: ValType, signed: bool) -> Option<Self> {
+    Some(Self {
+      from,
+      to,
+      signed,
+      instruction: convert_instruction(from, to, signed)?,
+    })
+  }
+}
+
+fn convert_instruction(from: ValType, to: ValType, signed: bool) -> Option<Instruction<'static>> {
+  use ValType::*;
+  Some(match (from, to, signed) {
+    (I32, I64, true) => Instruction::I64ExtendI32S,
+    (I32, I64, false) => Instruction::I64ExtendI32U,
+    (I64, I32, _) => Instruction::I32WrapI64,
+    (I32, F32, true) => Instruction::F32ConvertI32S,
+    (I32, F32, false) => Instruction::F32ConvertI32U,
+    (I64, F32, true) => Instruction::F32ConvertI64S,
+    (I64, F32, false) => Instruction::F32ConvertI64U,
+    (I32, F64, true) => Instruction::F64ConvertI32S,
+    (I32, F64, false) => Instruction::F64ConvertI32U,
+    (I64, F64, true) => Instruction::F64ConvertI64S,
+    (I64, F64, false) => Instruction::F64ConvertI64U,
+    (F32, I32, true) => Instruction::I32TruncSatF32S,
+    (F32, I32, false) => Instruction::I32TruncSatF32U,
+    (F64, I32, true) => Instruction::I32TruncSatF64S,
+    (F64, I32, false) => Instruction::I32TruncSatF64U,
+    (F32, I64, true) => Instruction::I64TruncSatF32S,
+    (F32, I64, false) => Instruction::I64TruncSatF32U,
+    (F64, I64, true) => Instruction::I64TruncSatF64S,
+    (F64, I64, false) => Instruction::I64TruncSatF64U,
+    (F32, F64, _) => Instruction::F64PromoteF32,
+    (F64, F32, _) => Instruction::F32DemoteF64,
+    _ => return None,
+  })
+}
+
+fn type_name(val_type: ValType) -> &'static str {
+  match val_type {
+    ValType::I32 => "i32",
+    ValType::I64 => "i64",
+    ValType::F32 => "f32",
+    ValType::F64 => "f64",
+    _ => "ref",
+  }
+}
+
+fn memarg(memarg: &MemArg) -> String {
+  if memarg.offset == 0 {
+    String::new()
+  } else {
+    format!(" +{}", memarg.offset)
+  }
+}
+
+impl fmt::Display for Inst {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Inst::Const(Value::I32(val)) => write!(f, "const i32 {}", val),
+      Inst::Const(Value::I64(val)) => write!(f, "const i64 {}", val),
+      Inst::Const(Value::F32(val)) => write!(f, "const f32 {}", val),
+      Inst::Const(Value::F64(val)) => write!(f, "const f64 {}", val),
+      Inst::LocalGet(idx) => write!(f, "get %{}", idx),
+      Inst::LocalSet(idx) => write!(f, "set %{}", idx),
+      Inst::LocalTee(idx) => write!(f, "tee %{}", idx),
+      Inst::GlobalGet(idx) => write!(f, "get @{}", idx),
+      Inst::GlobalSet(idx) => write!(f, "set @{}", idx),
+      Inst::Binary(op, val_type) => {
+        let op = format!("{:?}", op).to_lowercase();
+        write!(f, "{} {}", op, type_name(*val_type))
+      }
+      Inst::Eqz(val_type) => write!(f, "eqz {}", type_name(*val_type)),
+      Inst::Convert(conversion) => write!(
+        f,
+        "convert {} {}{}",
+        type_name(conversion.from),
+        type_name(conversion.to),
+        if conversion.signed { " s" } else { "" }
+      ),
+      Inst::Reinterpret(val_type) => write!(f, "reinterpret {}", type_name(*val_type)),
+      Inst::Load(val_type, arg) => write!(f, "load {}{}", type_name(*val_type), memarg(arg)),
+      Inst::Load8U(arg) => write!(f, "load8u i32{}", memarg(arg)),
+      Inst::Store(val_type, arg) => write!(f, "store {}{}", type_name(*val_type), memarg(arg)),
+      Inst::Call(idx) => write!(f, "call #{}", idx),
+      Inst::Br(depth) => write!(f, "br {}", depth),
+      Inst::BrIf(depth) => write!(f, "br_if {}", depth),
+      Inst::MemorySize => f.write_str("memory.size"),
+      Inst::MemoryGrow => f.write_str("memory.grow"),
+      Inst::Raw(raw) => write!(f, "raw {} bytes", raw.len()),
+      _ => f.write_str(&format!("{:?}", self).to_lowercase()),
+    }
+  }
+}
+
+/// A readable dump of `function`, with locals and calls named where possible.
+pub fn print_function(function: &Function, fn_names: &[(u32, String)]) -> String {
+  let local_name = |idx: u32| {
+    function
+      .locals
+      .names
+      .iter()
+      .find(|(other, _)| *other == idx)
+      .map(|(_, ident)| ident.clone())
+      .unwrap_or(idx.to_string())
+  };
+
+  let params: Vec<String> = function
+    .locals
+    .params
+    .iter()
+    .enumerate()
+    .map(|(idx, val_type)| format!("%{}: {}", local_name(idx as u32), type_name(*val_type)))
+    .collect();
+  let results: Vec<&str> = function.results.iter().map(|val| type_name(*val)).collect();
+  let mut out = format!("fn {}({})", function.ident, params.join(", "));
+  if !results.is_empty() {
+    out += &format!(" -> {}", results.join(", "));
+  }
+  out += "\n";
+
+  let params_len = function.locals.params.len() as u32;
+  for (idx, val_type) in function.locals.locals.iter().enumerate() {
+    let idx = params_len + idx as u32;
+    out += &format!("  local %{}: {}\n", local_name(idx), type_name(*val_type));
+  }
+  if function.is_raw {
+    out += &format!("  raw {} bytes\n", function.raw.len());
+    return out;
+  }
+
+  let mut depth = 1;
+  for inst in &function.instructions {
+    if matches!(inst, Inst::End | Inst::Else) {
+      depth -= 1;
+    }
+    let line = match inst {
+      Inst::LocalGet(idx) => format!("get %{}", local_name(*idx)),
+      Inst::LocalSet(idx) => format!("set %{}", local_name(*idx)),
+      Inst::LocalTee(idx) => format!("tee %{}", local_name(*idx)),
+      Inst::Call(idx) => match fn_names.iter().find(|(other, _)| other == idx) {
+        Some((_, ident)) => format!("call {}", ident),
+        None => inst.to_string(),
+      },
+      _ => inst.to_string(),
+    };
+    // The final `end` closes the function body.
+    if depth > 0 {
+      out += &format!("{}{}\n", "  ".repeat(depth), line);
+    }
+    if inst.is_block_start() || matches!(inst, Inst::Else) {
+      depth += 1;
+    }
+  }
+  out
+}
+
+/// Dumps the IR kept by a compiler with `emit_ir` set.
+pub fn print_ir(compiler: &Compiler) -> String {
+  compiler
+    .ir
+    .iter()
+    .map(|function| print_function(function, &compiler.module.fn_names()))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::print_function;
+  use crate::BinOp;
+  use crate::Conversion;
+  use crate::Function;
+  use crate::Inst;
+  use crate::Value;
+  use wasm_encoder::Instruction;
+  use wasm_encoder::ValType;
+
+  #[test]
+  fn typed_operators() {
+    assert!(matches!(
+      BinOp::DivS.instruction(ValType::F64),
+      Some(Instruction::F64Div)
+    ));
+    assert!(matches!(
+      BinOp::GeU.instruction(ValType::I32),
+      Some(Instruction::I32GeU)
+    ));
+    assert!(BinOp::RemS.instruction(ValType::F32).is_none());
+  }
+
+  #[test]
+  fn conversions() {
+    let encode = |conversion: Conversion| {
+      let mut sink = Vec::new();
+      Inst::Convert(conversion).encode(&mut sink);
+      sink
+    };
+    assert_eq!(
+      encode(Conversion::EXTEND_U),
+      encode(Conversion::new(ValType::I32, ValType::I64, false).unwrap())
+    );
+    assert_eq!(
+      encode(Conversion::WRAP),
+      encode(Conversion::new(ValType::I64, ValType::I32, true).unwrap())
+    );
+    assert!(!encode(Conversion::new(ValType::F64, ValType::I32, true).unwrap()).is_empty());
+    assert!(Conversion::new(ValType::I32, ValType::I32, true).is_none());
+    assert!(Conversion::new(ValType::V128, ValType::I32, false).is_none());
+  }
+
+  #[test]
+  fn print() {
+    let mut function = Function::new(String::from("add"));
+    let a = function.param("a", ValType::I32);
+    let x = function.local("x", ValType::I32);
+    function.results = vec![ValType::I32];
+    function
+      .instruction(Inst::LocalGet(a))
+      .instruction(Inst::Const(Value::I32(1)))
+      .instruction(Inst::Binary(BinOp::Add, ValType::I32))
+      .instruction(Inst::LocalSet(x))
+      .instruction(Inst::Block)
+      .instruction(Inst::Call(0))
+      .instruction(Inst::End)
+      .instruction(Inst::LocalGet(x))
+      .instruction(Inst::End);
+    assert_eq!(
+      print_function(&function, &[(0, String::from("tick"))]),
+      "fn add(%a: i32) -> i32\n  local %x: i32\n  get %a\n  const i32 1\n  add i32\n  \
+       set %x\n  block\n    call tick\n  end\n  get %x\n"
+    );
+  }
+}
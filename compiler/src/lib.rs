@@ -1,13 +1,29 @@
+mod builtins;
 mod checker;
 mod compiler;
 mod compilers;
+mod ir;
+mod locals;
 mod memory;
 mod module;
+mod optimize;
 mod scope;
+mod shake;
+mod source_map;
+#[cfg(test)]
+mod test_support;
+mod tip_handler;
 
+pub use builtins::*;
 pub use checker::*;
 pub use compiler::*;
 pub use compilers::*;
+pub use ir::*;
+pub use locals::*;
 pub use memory::*;
 pub use module::*;
+pub use optimize::*;
 pub use scope::*;
+pub use shake::*;
+pub use source_map::*;
+pub use tip_handler::*;
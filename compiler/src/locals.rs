@@ -0,0 +1,173 @@
+use wasm_encoder::ValType;
+
+#[derive(Debug, Clone)]
+pub struct LocalAllocator {
+  pub params: Vec<ValType>,
+  pub locals: Vec<ValType>,
+  /// The first name given to each slot, for the `name` section.
+  pub names: Vec<(u32, String)>,
+  free: Vec<u32>,
+  frames: Vec<Vec<(String, u32)>>,
+}
+
+impl LocalAllocator {
+  pub fn new() -> Self {
+    Self {
+      params: Vec::new(),
+      locals: Vec::new(),
+      names: Vec::new(),
+      free: Vec::new(),
+      frames: vec![Vec::new()],
+    }
+  }
+
+  pub fn param(&mut self, ident: &str, val_type: ValType) -> u32 {
+    debug_assert!(self.locals.is_empty(), "parameters come before locals");
+    self.params.push(val_type);
+    let idx = self.params.len() as u32 - 1;
+    self.frames[0].push((ident.to_string(), idx));
+    self.name(ident, idx);
+    idx
+  }
+
+  pub fn alloc(&mut self, ident: &str, val_type: ValType) -> u32 {
+    let reusable = self
+      .free
+      .iter()
+      .position(|idx| self.val_type(*idx) == Some(val_type));
+
+    let idx = if let Some(pos) = reusable {
+      self.free.remove(pos)
+    } else {
+      self.locals.push(val_type);
+      (self.params.len() + self.locals.len()) as u32 - 1
+    };
+
+    if let Some(frame) = self.frames.last_mut() {
+      frame.push((ident.to_string(), idx));
+    }
+    self.name(ident, idx);
+    idx
+  }
+
+  fn name(&mut self, ident: &str, idx: u32) {
+    if !ident.is_empty() && !self.names.iter().any(|(other, _)| *other == idx) {
+      self.names.push((idx, ident.to_string()));
+    }
+  }
+
+  pub fn val_type(&self, idx: u32) -> Option<ValType> {
+    let idx = idx as usize;
+    if idx < self.params.len() {
+      self.params.get(idx).copied()
+    } else {
+      self.locals.get(idx - self.params.len()).copied()
+    }
+  }
+
+  pub fn len(&self) -> u32 {
+    (self.params.len() + self.locals.len()) as u32
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Locals currently in scope by name, innermost declaration first.
+  pub fn visible(&self) -> Vec<(String, u32)> {
+    let mut visible: Vec<(String, u32)> = Vec::new();
+    for (ident, idx) in self.frames.iter().flatten().rev() {
+      if !visible.iter().any(|(other, _)| other == ident) {
+        visible.push((ident.clone(), *idx));
+      }
+    }
+    visible
+  }
+
+  pub fn enter_scope(&mut self) {
+    self.frames.push(Vec::new());
+  }
+
+  pub fn exit_scope(&mut self) {
+    if self.frames.len() > 1 {
+      if let Some(frame) = self.frames.pop() {
+        self.free.extend(frame.into_iter().map(|(_, idx)| idx));
+      }
+    }
+  }
+}
+
+impl Default for LocalAllocator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::LocalAllocator;
+  use wasm_encoder::ValType;
+
+  #[test]
+  fn params_first() {
+    let mut locals = LocalAllocator::new();
+
+    assert_eq!(locals.param("p", ValType::I32), 0);
+    assert_eq!(locals.param("p", ValType::F64), 1);
+    assert_eq!(locals.alloc("l", ValType::I32), 2);
+    assert_eq!(locals.val_type(1), Some(ValType::F64));
+    assert_eq!(locals.val_type(2), Some(ValType::I32));
+  }
+
+  #[test]
+  fn reuse_disjoint_scopes() {
+    let mut locals = LocalAllocator::new();
+
+    locals.enter_scope();
+    assert_eq!(locals.alloc("l", ValType::I32), 0);
+    assert_eq!(locals.alloc("l", ValType::I64), 1);
+    locals.exit_scope();
+
+    locals.enter_scope();
+    assert_eq!(locals.alloc("l", ValType::I64), 1);
+    assert_eq!(locals.alloc("l", ValType::F32), 2);
+    assert_eq!(locals.alloc("l", ValType::I32), 0);
+    locals.exit_scope();
+
+    assert_eq!(
+      locals.locals,
+      vec![ValType::I32, ValType::I64, ValType::F32]
+    );
+  }
+
+  #[test]
+  fn no_reuse_nested_scopes() {
+    let mut locals = LocalAllocator::new();
+
+    assert_eq!(locals.alloc("l", ValType::I32), 0);
+    locals.enter_scope();
+    assert_eq!(locals.alloc("l", ValType::I32), 1);
+    locals.exit_scope();
+    assert_eq!(locals.alloc("l", ValType::I32), 1);
+    assert_eq!(locals.alloc("l", ValType::I32), 2);
+  }
+
+  #[test]
+  fn visible_shadowing() {
+    let mut locals = LocalAllocator::new();
+
+    locals.param("a", ValType::I32);
+    locals.alloc("b", ValType::I32);
+    locals.enter_scope();
+    locals.alloc("a", ValType::I64);
+    assert_eq!(
+      locals.visible(),
+      vec![(String::from("a"), 2), (String::from("b"), 1)]
+    );
+    locals.exit_scope();
+    assert_eq!(
+      locals.visible(),
+      vec![(String::from("b"), 1), (String::from("a"), 0)]
+    );
+  }
+}
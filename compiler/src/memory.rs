@@ -16,15 +16,22 @@ pub struct Memory {
   pub stack: u64,
   pub _heap: u64,
   pub buf: Vec<u8>,
+  /// The function being compiled, which owns the data written meanwhile.
+  pub owner: Option<u32>,
+  /// Written ranges of `buf` and their owners.
+  pub chunks: Vec<(u64, u64, Option<u32>)>,
 }
 
 impl Memory {
   pub fn new() -> Self {
     Self {
       align: 8,
-      stack: 0,
+      // Address 0 is never handed out, so a null pointer can stand for `none`.
+      stack: 8,
       _heap: 0,
-      buf: Vec::new(),
+      buf: vec![0; 8],
+      owner: None,
+      chunks: Vec::new(),
     }
   }
 
@@ -37,6 +44,11 @@ impl Memory {
     }
   }
 
+  /// Records `start..stack` as written by the current owner.
+  pub fn claim(&mut self, start: u64) {
+    self.chunks.push((start, self.stack, self.owner));
+  }
+
   pub fn step_stack(&mut self, length: u64) {
     self.stack += length
   }
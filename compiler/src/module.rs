@@ -1,57 +1,268 @@
+use crate::reachable;
+use crate::remap_function;
+use crate::Function;
+use crate::Value;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use wasm_encoder::CodeSection;
+use wasm_encoder::ConstExpr;
+use wasm_encoder::CustomSection;
 use wasm_encoder::DataSection;
 use wasm_encoder::ElementSection;
+use wasm_encoder::EntityType;
+use wasm_encoder::ExportKind;
 use wasm_encoder::ExportSection;
 use wasm_encoder::FunctionSection;
 use wasm_encoder::GlobalSection;
+use wasm_encoder::GlobalType;
 use wasm_encoder::ImportSection;
+use wasm_encoder::IndirectNameMap;
 use wasm_encoder::MemorySection;
+use wasm_encoder::NameMap;
+use wasm_encoder::NameSection;
+use wasm_encoder::StartSection;
 use wasm_encoder::TableSection;
 use wasm_encoder::TypeSection;
+use wasm_encoder::ValType;
+use whistle_common::Span;
+
+#[derive(Debug, Clone)]
+pub enum FunctionBody {
+  Import { namespace: String, name: String },
+  Code(Function),
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleFunction {
+  /// The index given by the checker. Imports are moved in front of defined
+  /// functions, and unreachable functions dropped, when the module is finished.
+  pub index: u32,
+  pub ident: String,
+  pub span: Span,
+  pub params: Vec<ValType>,
+  pub results: Vec<ValType>,
+  pub body: FunctionBody,
+  /// Rarely called, so placed after the other functions, see `#(cold)`.
+  pub cold: bool,
+}
+
+impl ModuleFunction {
+  pub fn is_import(&self) -> bool {
+    matches!(self.body, FunctionBody::Import { .. })
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleGlobal {
+  pub global_type: GlobalType,
+  pub init: Value,
+}
+
+/// Bytes placed in memory at `offset`, written while compiling `owner`.
+#[derive(Debug, Clone)]
+pub struct DataChunk {
+  pub offset: u32,
+  pub bytes: Vec<u8>,
+  pub owner: Option<u32>,
+}
 
 pub struct Module {
-  pub types: TypeSection,
-  pub imports: ImportSection,
-  pub fns: FunctionSection,
+  pub functions: Vec<ModuleFunction>,
   pub tables: TableSection,
   pub memories: MemorySection,
-  pub globals: GlobalSection,
-  pub exports: ExportSection,
+  pub globals: Vec<ModuleGlobal>,
+  pub exports: Vec<(String, ExportKind, u32)>,
+  pub start: Option<u32>,
   pub elements: ElementSection,
-  pub code: CodeSection,
-  pub data: DataSection,
+  pub data: Vec<DataChunk>,
+  /// Drop functions, globals and data not reachable from the exports, the
+  /// start function or `main`.
+  pub tree_shake: bool,
+  /// Instruction offsets and their spans, per emitted function body.
+  pub debug: Vec<Vec<(u32, Span)>>,
+  /// Names and spans of the emitted function bodies, in code section order.
+  pub code: Vec<(String, Span)>,
+  pub source_map_url: Option<String>,
 }
 
 impl Module {
   pub fn new() -> Self {
     Self {
-      types: TypeSection::new(),
-      imports: ImportSection::new(),
-      fns: FunctionSection::new(),
+      functions: Vec::new(),
       tables: TableSection::new(),
       memories: MemorySection::new(),
-      globals: GlobalSection::new(),
-      exports: ExportSection::new(),
+      globals: Vec::new(),
+      exports: Vec::new(),
+      start: None,
       elements: ElementSection::new(),
-      code: CodeSection::new(),
-      data: DataSection::new(),
+      data: Vec::new(),
+      tree_shake: true,
+      debug: Vec::new(),
+      code: Vec::new(),
+      source_map_url: None,
     }
   }
 
-  pub fn finish(&self) -> Vec<u8> {
+  /// Function names by the checker's indices.
+  pub fn fn_names(&self) -> Vec<(u32, String)> {
+    self
+      .functions
+      .iter()
+      .map(|function| (function.index, function.ident.clone()))
+      .collect()
+  }
+
+  pub fn finish(&mut self) -> Vec<u8> {
+    let (live_fns, live_globals) = if self.tree_shake {
+      reachable(self)
+    } else {
+      (
+        self
+          .functions
+          .iter()
+          .map(|function| function.index)
+          .collect(),
+        (0..self.globals.len() as u32).collect(),
+      )
+    };
+
+    let mut functions: Vec<&ModuleFunction> = self
+      .functions
+      .iter()
+      .filter(|function| live_fns.contains(&function.index))
+      .collect();
+    functions.sort_by_key(|function| (!function.is_import(), function.cold, function.index));
+    let fn_map: HashMap<u32, u32> = functions
+      .iter()
+      .enumerate()
+      .map(|(idx, function)| (function.index, idx as u32))
+      .collect();
+    let global_map: HashMap<u32, u32> = (0..self.globals.len() as u32)
+      .filter(|idx| live_globals.contains(idx))
+      .enumerate()
+      .map(|(new, old)| (old, new as u32))
+      .collect();
+
+    let mut types = TypeSection::new();
+    let mut signatures: Vec<(&[ValType], &[ValType])> = Vec::new();
+    let mut imports = ImportSection::new();
+    let mut fns = FunctionSection::new();
+    let mut code = CodeSection::new();
+    let mut fn_names = NameMap::new();
+    let mut local_names = IndirectNameMap::new();
+    self.debug.clear();
+    self.code.clear();
+    for (idx, function) in functions.iter().enumerate() {
+      let idx = idx as u32;
+      // Functions with the same signature share one type.
+      let signature = (&function.params[..], &function.results[..]);
+      let type_idx = match signatures.iter().position(|other| *other == signature) {
+        Some(type_idx) => type_idx as u32,
+        None => {
+          types.function(function.params.clone(), function.results.clone());
+          signatures.push(signature);
+          signatures.len() as u32 - 1
+        }
+      };
+      fn_names.append(idx, &function.ident);
+      match &function.body {
+        FunctionBody::Import { namespace, name } => {
+          imports.import(namespace, name, EntityType::Function(type_idx));
+        }
+        FunctionBody::Code(body) => {
+          let mut body = body.clone();
+          remap_function(&mut body, &fn_map, &global_map);
+          fns.function(type_idx);
+
+          let mut names = body.locals.names.clone();
+          names.sort_by_key(|(idx, _)| *idx);
+          let mut map = NameMap::new();
+          for (local, ident) in &names {
+            map.append(*local, ident);
+          }
+          local_names.append(idx, &map);
+
+          self.debug.push(body.offsets());
+          self.code.push((function.ident.clone(), function.span));
+          code.function(&body.into());
+        }
+      }
+    }
+
+    let mut globals = GlobalSection::new();
+    for (idx, global) in self.globals.iter().enumerate() {
+      if global_map.contains_key(&(idx as u32)) {
+        globals.global(global.global_type, &const_expr(global.init));
+      }
+    }
+
+    let mut exports = ExportSection::new();
+    for (name, kind, idx) in &self.exports {
+      let idx = match kind {
+        ExportKind::Func => fn_map.get(idx),
+        ExportKind::Global => global_map.get(idx),
+        _ => Some(idx),
+      };
+      if let Some(idx) = idx {
+        exports.export(name, *kind, *idx);
+      }
+    }
+
+    let mut data = DataSection::new();
+    for (offset, bytes) in self.live_data(&live_fns) {
+      data.active(0, &ConstExpr::i32_const(offset as i32), bytes);
+    }
+
+    let mut names = NameSection::new();
+    names.functions(&fn_names);
+    names.locals(&local_names);
+
     let mut module = wasm_encoder::Module::new();
-    module.section(&self.types);
-    module.section(&self.imports);
-    module.section(&self.fns);
+    module.section(&types);
+    module.section(&imports);
+    module.section(&fns);
     module.section(&self.tables);
     module.section(&self.memories);
-    module.section(&self.globals);
-    module.section(&self.exports);
+    module.section(&globals);
+    module.section(&exports);
+    if let Some(function_index) = self.start.and_then(|idx| fn_map.get(&idx)) {
+      module.section(&StartSection {
+        function_index: *function_index,
+      });
+    }
     module.section(&self.elements);
-    module.section(&self.code);
-    module.section(&self.data);
+    module.section(&code);
+    module.section(&data);
+    module.section(&names);
+    if let Some(url) = &self.source_map_url {
+      module.section(&CustomSection {
+        name: Cow::Borrowed("sourceMappingURL"),
+        data: Cow::Owned(encode_str(url)),
+      });
+    }
     module.finish()
   }
+
+  /// Data chunks whose owner is live, with adjacent chunks merged into one segment.
+  fn live_data(&self, live_fns: &HashSet<u32>) -> Vec<(u32, Vec<u8>)> {
+    let mut segments: Vec<(u32, Vec<u8>)> = Vec::new();
+    for chunk in &self.data {
+      if let Some(owner) = chunk.owner {
+        if !live_fns.contains(&owner) {
+          continue;
+        }
+      }
+      match segments.last_mut() {
+        Some((offset, bytes)) if *offset + bytes.len() as u32 == chunk.offset => {
+          bytes.extend(&chunk.bytes)
+        }
+        _ => segments.push((chunk.offset, chunk.bytes.clone())),
+      }
+    }
+    segments
+  }
 }
 
 impl Default for Module {
@@ -59,3 +270,18 @@ impl Default for Module {
     Module::new()
   }
 }
+
+fn const_expr(value: Value) -> ConstExpr {
+  match value {
+    Value::I32(val) => ConstExpr::i32_const(val),
+    Value::I64(val) => ConstExpr::i64_const(val),
+    Value::F32(val) => ConstExpr::f32_const(val),
+    Value::F64(val) => ConstExpr::f64_const(val),
+  }
+}
+
+fn encode_str(string: &str) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  wasm_encoder::Encode::encode(string, &mut bytes);
+  bytes
+}
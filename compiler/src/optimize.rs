@@ -0,0 +1,417 @@
+use crate::BinOp;
+use crate::Function;
+use crate::Inst;
+use crate::Value;
+
+use std::collections::HashSet;
+use wasm_encoder::ValType;
+use whistle_common::Span;
+
+/// An instruction along with the spans marked on it.
+#[derive(Debug, Clone)]
+struct Item {
+  inst: Inst,
+  marks: Vec<Span>,
+}
+
+/// Runs the passes enabled at `level` (0 to 3) over `function`.
+///
+/// - `-O1`: constant folding, constant branches, dead code after `return`/`br`
+/// - `-O2`: peephole simplification of `local.set`/`local.get` and `drop`
+/// - `-O3`: dead store elimination and compaction of unused locals
+pub fn optimize(function: &mut Function, level: u8) {
+  if level == 0 || function.is_raw {
+    return;
+  }
+
+  let mut items = into_items(function);
+  // Each pass reports whether it rewrote anything, as a rewrite that doesn't
+  // shrink the code may still enable another pass.
+  loop {
+    let mut changed = fold_constants(&mut items);
+    changed |= fold_branches(&mut items);
+    changed |= remove_dead_code(&mut items);
+    if level >= 2 {
+      changed |= peephole(&mut items);
+    }
+    if level >= 3 {
+      changed |= remove_dead_stores(&mut items, function.locals.params.len() as u32);
+    }
+    if !changed {
+      break;
+    }
+  }
+  from_items(function, items);
+
+  if level >= 3 {
+    compact_locals(function);
+  }
+}
+
+fn into_items(function: &mut Function) -> Vec<Item> {
+  let mut items: Vec<Item> = function
+    .instructions
+    .drain(..)
+    .map(|inst| Item {
+      inst,
+      marks: Vec::new(),
+    })
+    .collect();
+  for (idx, span) in function.marks.drain(..) {
+    if let Some(item) = items.get_mut(idx) {
+      item.marks.push(span);
+    }
+  }
+  items
+}
+
+fn from_items(function: &mut Function, items: Vec<Item>) {
+  for (idx, item) in items.into_iter().enumerate() {
+    for span in item.marks {
+      function.marks.push((idx, span));
+    }
+    function.instructions.push(item.inst);
+  }
+}
+
+/// Removes `count` items starting at `idx`, moving their marks onto `replacement`.
+fn splice(items: &mut Vec<Item>, idx: usize, count: usize, replacement: Vec<Inst>) {
+  let marks: Vec<Span> = items[idx..idx + count]
+    .iter()
+    .flat_map(|item| item.marks.clone())
+    .collect();
+  let mut replacement: Vec<Item> = replacement
+    .into_iter()
+    .map(|inst| Item {
+      inst,
+      marks: Vec::new(),
+    })
+    .collect();
+  match replacement.first_mut() {
+    Some(first) => first.marks = marks,
+    None => {
+      if let Some(next) = items.get_mut(idx + count) {
+        next.marks.splice(0..0, marks);
+      }
+    }
+  }
+  items.splice(idx..idx + count, replacement);
+}
+
+fn fold_i32(op: BinOp, a: i32, b: i32) -> Option<i32> {
+  Some(match op {
+    BinOp::Add => a.wrapping_add(b),
+    BinOp::Sub => a.wrapping_sub(b),
+    BinOp::Mul => a.wrapping_mul(b),
+    BinOp::And => a & b,
+    BinOp::Or => a | b,
+    BinOp::Xor => a ^ b,
+    BinOp::Shl => a.wrapping_shl(b as u32),
+    BinOp::ShrS => a.wrapping_shr(b as u32),
+    BinOp::ShrU => (a as u32).wrapping_shr(b as u32) as i32,
+    BinOp::Eq => (a == b) as i32,
+    BinOp::Ne => (a != b) as i32,
+    BinOp::LtS => (a < b) as i32,
+    BinOp::GtS => (a > b) as i32,
+    BinOp::LeS => (a <= b) as i32,
+    BinOp::GeS => (a >= b) as i32,
+    _ => return None,
+  })
+}
+
+fn fold_i64(op: BinOp, a: i64, b: i64) -> Option<Value> {
+  Some(match op {
+    BinOp::Add => Value::I64(a.wrapping_add(b)),
+    BinOp::Sub => Value::I64(a.wrapping_sub(b)),
+    BinOp::Mul => Value::I64(a.wrapping_mul(b)),
+    BinOp::And => Value::I64(a & b),
+    BinOp::Or => Value::I64(a | b),
+    BinOp::Xor => Value::I64(a ^ b),
+    BinOp::Eq => Value::I32((a == b) as i32),
+    BinOp::Ne => Value::I32((a != b) as i32),
+    BinOp::LtS => Value::I32((a < b) as i32),
+    BinOp::GtS => Value::I32((a > b) as i32),
+    _ => return None,
+  })
+}
+
+fn fold_constants(items: &mut Vec<Item>) -> bool {
+  let mut changed = false;
+  let mut idx = 0;
+  while idx < items.len() {
+    let folded = match (
+      &items[idx].inst,
+      items.get(idx + 1).map(|item| &item.inst),
+      items.get(idx + 2).map(|item| &item.inst),
+    ) {
+      (
+        Inst::Const(Value::I32(a)),
+        Some(Inst::Const(Value::I32(b))),
+        Some(Inst::Binary(op, ValType::I32)),
+      ) => fold_i32(*op, *a, *b).map(|val| (3, Value::I32(val))),
+      (
+        Inst::Const(Value::I64(a)),
+        Some(Inst::Const(Value::I64(b))),
+        Some(Inst::Binary(op, ValType::I64)),
+      ) => fold_i64(*op, *a, *b).map(|val| (3, val)),
+      (Inst::Const(Value::I32(a)), Some(Inst::Eqz(ValType::I32)), _) => {
+        Some((2, Value::I32((*a == 0) as i32)))
+      }
+      _ => None,
+    };
+
+    match folded {
+      Some((count, val)) => {
+        splice(items, idx, count, vec![Inst::Const(val)]);
+        changed = true;
+        // The result may fold with the constant before it.
+        idx = idx.saturating_sub(1);
+      }
+      None => idx += 1,
+    }
+  }
+  changed
+}
+
+fn fold_branches(items: &mut Vec<Item>) -> bool {
+  let mut changed = false;
+  let mut idx = 0;
+  while idx + 1 < items.len() {
+    match (&items[idx].inst, &items[idx + 1].inst) {
+      (Inst::Const(Value::I32(cond)), Inst::BrIf(depth)) => {
+        let replacement = if *cond == 0 {
+          vec![]
+        } else {
+          vec![Inst::Br(*depth)]
+        };
+        splice(items, idx, 2, replacement);
+        changed = true;
+      }
+      _ => idx += 1,
+    }
+  }
+  changed
+}
+
+fn remove_dead_code(items: &mut Vec<Item>) -> bool {
+  let mut changed = false;
+  let mut idx = 0;
+  while idx < items.len() {
+    let terminates = matches!(
+      items[idx].inst,
+      Inst::Return | Inst::Br(_) | Inst::Unreachable
+    );
+    idx += 1;
+    if !terminates {
+      continue;
+    }
+
+    // Everything up to the `end` or `else` of the enclosing block is unreachable.
+    let mut depth = 0;
+    let mut end = idx;
+    while end < items.len() {
+      match &items[end].inst {
+        inst if inst.is_block_start() => depth += 1,
+        Inst::End | Inst::Else if depth == 0 => break,
+        Inst::End => depth -= 1,
+        _ => {}
+      }
+      end += 1;
+    }
+    if end > idx {
+      items.drain(idx..end);
+      changed = true;
+    }
+  }
+  changed
+}
+
+fn peephole(items: &mut Vec<Item>) -> bool {
+  let mut changed = false;
+  let mut idx = 0;
+  while idx + 1 < items.len() {
+    let replacement = match (&items[idx].inst, &items[idx + 1].inst) {
+      (Inst::LocalSet(set), Inst::LocalGet(get)) if set == get => Some(vec![Inst::LocalTee(*set)]),
+      (Inst::LocalTee(local), Inst::Drop) => Some(vec![Inst::LocalSet(*local)]),
+      (Inst::Const(_) | Inst::LocalGet(_) | Inst::GlobalGet(_), Inst::Drop) => Some(vec![]),
+      // Empty blocks left behind by other passes.
+      (Inst::Block | Inst::Loop, Inst::End) => Some(vec![]),
+      _ => None,
+    };
+    match replacement {
+      Some(replacement) => {
+        splice(items, idx, 2, replacement);
+        changed = true;
+        idx = idx.saturating_sub(1);
+      }
+      None => idx += 1,
+    }
+  }
+  changed
+}
+
+fn remove_dead_stores(items: &mut [Item], params: u32) -> bool {
+  // Inline WAT may read any local.
+  if items.iter().any(|item| matches!(item.inst, Inst::Raw(_))) {
+    return false;
+  }
+  let read: HashSet<u32> = items
+    .iter()
+    .filter_map(|item| match item.inst {
+      Inst::LocalGet(idx) | Inst::LocalTee(idx) => Some(idx),
+      _ => None,
+    })
+    .collect();
+  let mut changed = false;
+  for item in items.iter_mut() {
+    if let Inst::LocalSet(idx) = item.inst {
+      if idx >= params && !read.contains(&idx) {
+        item.inst = Inst::Drop;
+        changed = true;
+      }
+    }
+  }
+  changed
+}
+
+fn compact_locals(function: &mut Function) {
+  if function
+    .instructions
+    .iter()
+    .any(|inst| matches!(inst, Inst::Raw(_)))
+  {
+    return;
+  }
+  let params = function.locals.params.len() as u32;
+  let mut used = HashSet::new();
+  for inst in &function.instructions {
+    if let Inst::LocalGet(idx) | Inst::LocalSet(idx) | Inst::LocalTee(idx) = inst {
+      used.insert(*idx);
+    }
+  }
+
+  let mut mapping = Vec::new();
+  let mut locals = Vec::new();
+  for (offset, val_type) in function.locals.locals.iter().enumerate() {
+    let idx = params + offset as u32;
+    if used.contains(&idx) {
+      mapping.push((idx, params + locals.len() as u32));
+      locals.push(*val_type);
+    }
+  }
+  let remap = |idx: u32| {
+    if idx < params {
+      return Some(idx);
+    }
+    mapping
+      .iter()
+      .find(|(old, _)| *old == idx)
+      .map(|(_, new)| *new)
+  };
+
+  for inst in function.instructions.iter_mut() {
+    if let Inst::LocalGet(idx) | Inst::LocalSet(idx) | Inst::LocalTee(idx) = inst {
+      *idx = remap(*idx).unwrap_or(*idx);
+    }
+  }
+  function.locals.names = function
+    .locals
+    .names
+    .iter()
+    .filter_map(|(idx, ident)| remap(*idx).map(|idx| (idx, ident.clone())))
+    .collect();
+  function.locals.locals = locals;
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::optimize;
+  use crate::BinOp;
+  use crate::Function;
+  use crate::Inst;
+  use crate::Value;
+  use wasm_encoder::ValType;
+
+  fn instructions(function: &Function) -> Vec<String> {
+    function
+      .instructions
+      .iter()
+      .map(|inst| inst.to_string())
+      .collect()
+  }
+
+  #[test]
+  fn fold_and_remove_dead_code() {
+    let mut function = Function::new(String::from("f"));
+    function
+      .instruction(Inst::Const(Value::I32(2)))
+      .instruction(Inst::Const(Value::I32(3)))
+      .instruction(Inst::Binary(BinOp::Mul, ValType::I32))
+      .instruction(Inst::Const(Value::I32(1)))
+      .instruction(Inst::Binary(BinOp::Add, ValType::I32))
+      .instruction(Inst::Return)
+      .instruction(Inst::Const(Value::I32(4)))
+      .instruction(Inst::Drop)
+      .instruction(Inst::End);
+    optimize(&mut function, 1);
+    assert_eq!(
+      instructions(&function),
+      vec!["const i32 7", "return", "end"]
+    );
+  }
+
+  #[test]
+  fn constant_branches() {
+    let mut function = Function::new(String::from("f"));
+    function
+      .instruction(Inst::Block)
+      .instruction(Inst::Loop)
+      .instruction(Inst::Const(Value::I32(0)))
+      .instruction(Inst::BrIf(1))
+      .instruction(Inst::Br(0))
+      .instruction(Inst::End)
+      .instruction(Inst::End)
+      .instruction(Inst::End);
+    optimize(&mut function, 1);
+    assert_eq!(
+      instructions(&function),
+      vec!["block", "loop", "br 0", "end", "end", "end"]
+    );
+  }
+
+  #[test]
+  fn peephole_and_locals() {
+    let mut function = Function::new(String::from("f"));
+    let unused = function.local("unused", ValType::I32);
+    let x = function.local("x", ValType::I32);
+    function
+      .instruction(Inst::Const(Value::I32(1)))
+      .instruction(Inst::LocalSet(unused))
+      .instruction(Inst::Const(Value::I32(2)))
+      .instruction(Inst::LocalSet(x))
+      .instruction(Inst::LocalGet(x))
+      .instruction(Inst::Return)
+      .instruction(Inst::End);
+    optimize(&mut function, 3);
+    assert_eq!(
+      instructions(&function),
+      vec!["const i32 2", "tee %0", "return", "end"]
+    );
+    assert_eq!(function.locals.locals, vec![ValType::I32]);
+    assert_eq!(function.locals.names, vec![(0, String::from("x"))]);
+  }
+
+  #[test]
+  fn dead_store_then_drop() {
+    // Dropping the stored value doesn't shrink the code, but lets the peephole
+    // pass remove the constant and the drop.
+    let mut function = Function::new(String::from("f"));
+    let unused = function.local("unused", ValType::I32);
+    function
+      .instruction(Inst::Const(Value::I32(1)))
+      .instruction(Inst::LocalSet(unused))
+      .instruction(Inst::End);
+    optimize(&mut function, 3);
+    assert_eq!(instructions(&function), vec!["end"]);
+  }
+}
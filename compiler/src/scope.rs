@@ -7,6 +7,9 @@ use whistle_ast::Type;
 pub struct Symbol {
   pub global: bool,
   pub mutable: bool,
+  /// Expanded in place by the instructions at its index in
+  /// [`crate::Checker::intrinsics`] rather than called.
+  pub intrinsic: bool,
   pub types: Type,
 }
 
@@ -15,6 +18,7 @@ impl Default for Symbol {
     Symbol {
       global: false,
       mutable: false,
+      intrinsic: false,
       types: Type::Error,
     }
   }
@@ -274,6 +278,46 @@ impl ScopeContainer {
     self.get_sym_of(self.curr, ident)
   }
 
+  pub fn get_sym_mut_of(
+    &mut self,
+    id: usize,
+    ident: &str,
+  ) -> Result<&mut IndexedSymbol, CompilerErrorKind> {
+    let next = match self
+      .get_scope(id)
+      .ok_or(CompilerErrorKind::ScopeUndefined)?
+    {
+      Scope::Global { symbols, .. } if symbols.contains_key(ident) => None,
+      Scope::Global { .. } => return Err(CompilerErrorKind::SymbolUndefined),
+      Scope::Function { symbols, .. } | Scope::Block { symbols, .. }
+        if symbols.contains_key(ident) =>
+      {
+        None
+      }
+      Scope::Function { global, .. } => Some(*global),
+      Scope::Block { parent, .. } => Some(*parent),
+    };
+
+    if let Some(next) = next {
+      return self.get_sym_mut_of(next, ident);
+    }
+
+    match self
+      .get_scope_mut(id)
+      .ok_or(CompilerErrorKind::ScopeUndefined)?
+    {
+      Scope::Global { symbols, .. }
+      | Scope::Function { symbols, .. }
+      | Scope::Block { symbols, .. } => symbols
+        .get_mut(ident)
+        .ok_or(CompilerErrorKind::SymbolUndefined),
+    }
+  }
+
+  pub fn get_sym_mut(&mut self, ident: &str) -> Result<&mut IndexedSymbol, CompilerErrorKind> {
+    self.get_sym_mut_of(self.curr, ident)
+  }
+
   pub fn set_global_sym_of(
     &mut self,
     id: usize,
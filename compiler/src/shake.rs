@@ -0,0 +1,198 @@
+use crate::Function;
+use crate::FunctionBody;
+use crate::Inst;
+use crate::Module;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use wasm_encoder::Encode;
+use wasm_encoder::ExportKind;
+use wasm_encoder::Instruction;
+use wasmparser::FunctionBody as RawBody;
+use wasmparser::Operator;
+
+/// The functions and globals reachable from the exports, the start function
+/// and `main`/`_start`, by the checker's indices.
+pub fn reachable(module: &Module) -> (HashSet<u32>, HashSet<u32>) {
+  let mut fns = HashSet::new();
+  let mut globals = HashSet::new();
+  let mut queue = Vec::new();
+
+  for (_, kind, idx) in &module.exports {
+    match kind {
+      ExportKind::Func => queue.push(*idx),
+      ExportKind::Global => {
+        globals.insert(*idx);
+      }
+      _ => {}
+    }
+  }
+  queue.extend(module.start);
+  for function in &module.functions {
+    if function.ident == "main" || function.ident == "_start" {
+      queue.push(function.index);
+    }
+  }
+
+  while let Some(idx) = queue.pop() {
+    if !fns.insert(idx) {
+      continue;
+    }
+    let function = module
+      .functions
+      .iter()
+      .find(|function| function.index == idx);
+    if let Some(FunctionBody::Code(body)) = function.map(|function| &function.body) {
+      let (calls, refs) = references(body);
+      queue.extend(calls);
+      globals.extend(refs);
+    }
+  }
+
+  (fns, globals)
+}
+
+/// The functions called and globals used by `function`.
+fn references(function: &Function) -> (Vec<u32>, Vec<u32>) {
+  let mut calls = Vec::new();
+  let mut globals = Vec::new();
+  let mut visit = |op: &Operator| match op {
+    Operator::Call { function_index }
+    | Operator::ReturnCall { function_index }
+    | Operator::RefFunc { function_index } => calls.push(*function_index),
+    Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => {
+      globals.push(*global_index)
+    }
+    _ => {}
+  };
+
+  if function.is_raw {
+    visit_raw(&inline_body(&function.raw), &mut visit);
+  }
+  for inst in &function.instructions {
+    match inst {
+      Inst::Call(idx) => visit(&Operator::Call {
+        function_index: *idx,
+      }),
+      Inst::GlobalGet(idx) | Inst::GlobalSet(idx) => {
+        visit(&Operator::GlobalGet { global_index: *idx })
+      }
+      Inst::Raw(raw) => visit_raw(&inline_body(raw), &mut visit),
+      _ => {}
+    }
+  }
+  (calls, globals)
+}
+
+/// Rewrites the function and global indices used by `function`.
+pub fn remap_function(
+  function: &mut Function,
+  fns: &HashMap<u32, u32>,
+  globals: &HashMap<u32, u32>,
+) {
+  let remap = |map: &HashMap<u32, u32>, idx: u32| map.get(&idx).copied().unwrap_or(idx);
+
+  if function.is_raw {
+    function.raw = remap_raw(&inline_body(&function.raw), fns, globals).split_off(1);
+  }
+  for inst in function.instructions.iter_mut() {
+    match inst {
+      Inst::Call(idx) => *idx = remap(fns, *idx),
+      Inst::GlobalGet(idx) | Inst::GlobalSet(idx) => *idx = remap(globals, *idx),
+      Inst::Raw(raw) => {
+        // Drop the empty locals vector `inline_body` added.
+        *raw = remap_raw(&inline_body(raw), fns, globals).split_off(1);
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Inline or `#(wasm_bytes)` instructions with an empty locals vector in
+/// front, so they can be read as a function body.
+fn inline_body(raw: &[u8]) -> Vec<u8> {
+  let mut body = vec![0];
+  body.extend(raw);
+  body
+}
+
+fn visit_raw(body: &[u8], visit: &mut impl FnMut(&Operator)) {
+  let body = RawBody::new(0, body);
+  if let Ok(mut reader) = body.get_operators_reader() {
+    while !reader.eof() {
+      match reader.read() {
+        Ok(op) => visit(&op),
+        Err(_) => break,
+      }
+    }
+  }
+}
+
+/// Copies a function body, re-encoding the operators that refer to functions
+/// or globals.
+fn remap_raw(body: &[u8], fns: &HashMap<u32, u32>, globals: &HashMap<u32, u32>) -> Vec<u8> {
+  let remap = |map: &HashMap<u32, u32>, idx: u32| map.get(&idx).copied().unwrap_or(idx);
+  let raw = RawBody::new(0, body);
+  let mut reader = match raw.get_operators_reader() {
+    Ok(reader) => reader,
+    Err(_) => return body.to_vec(),
+  };
+
+  let mut out = body[..reader.original_position()].to_vec();
+  while !reader.eof() {
+    let (op, start) = match reader.read_with_offset() {
+      Ok(op) => op,
+      Err(_) => return body.to_vec(),
+    };
+    let end = reader.original_position();
+    let instruction = match op {
+      Operator::Call { function_index } => Instruction::Call(remap(fns, function_index)),
+      Operator::ReturnCall { function_index } => {
+        Instruction::ReturnCall(remap(fns, function_index))
+      }
+      Operator::RefFunc { function_index } => Instruction::RefFunc(remap(fns, function_index)),
+      Operator::GlobalGet { global_index } => Instruction::GlobalGet(remap(globals, global_index)),
+      Operator::GlobalSet { global_index } => Instruction::GlobalSet(remap(globals, global_index)),
+      _ => {
+        out.extend(&body[start..end]);
+        continue;
+      }
+    };
+    instruction.encode(&mut out);
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::remap_function;
+  use crate::Function;
+  use crate::Inst;
+  use std::collections::HashMap;
+  use wasm_encoder::Encode;
+  use wasm_encoder::Instruction;
+
+  #[test]
+  fn remap_inline_wasm() {
+    let mut raw = Vec::new();
+    Instruction::I32Const(1).encode(&mut raw);
+    Instruction::Call(200).encode(&mut raw);
+    Instruction::GlobalSet(3).encode(&mut raw);
+
+    let mut function = Function::new(String::from("f"));
+    function.inline(raw);
+    function.instruction(Inst::Call(200));
+    remap_function(
+      &mut function,
+      &HashMap::from([(200, 1)]),
+      &HashMap::from([(3, 0)]),
+    );
+
+    let mut expected = Vec::new();
+    Instruction::I32Const(1).encode(&mut expected);
+    Instruction::Call(1).encode(&mut expected);
+    Instruction::GlobalSet(0).encode(&mut expected);
+    assert!(matches!(&function.instructions[0], Inst::Raw(raw) if *raw == expected));
+    assert!(matches!(function.instructions[1], Inst::Call(1)));
+  }
+}
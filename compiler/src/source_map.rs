@@ -0,0 +1,135 @@
+use wasmparser::Payload;
+use whistle_common::Source;
+use whistle_common::Span;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Builds a version 3 source map from code offsets in `bytes` back to lines
+/// and columns in `sources`, using the spans recorded in `Module::debug`.
+/// `file` names the file being compiled, imports keep their import path.
+pub fn source_map(
+  bytes: &[u8],
+  debug: &[Vec<(u32, Span)>],
+  sources: &[Source],
+  file: &str,
+) -> String {
+  let mut starts = Vec::new();
+  for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+    if let Ok(Payload::CodeSectionEntry(body)) = payload {
+      if let Ok(reader) = body.get_operators_reader() {
+        starts.push(reader.original_position() as u32);
+      }
+    }
+  }
+
+  let mut entries = Vec::new();
+  for (start, offsets) in starts.iter().zip(debug) {
+    for (offset, span) in offsets {
+      if let Some((idx, line, col)) = locate(sources, span.start) {
+        entries.push((start + offset, idx, line, col));
+      }
+    }
+  }
+  entries.sort();
+  entries.dedup_by_key(|(offset, ..)| *offset);
+
+  let mut mappings = String::new();
+  let mut prev = (0, 0, 0, 0);
+  for (offset, idx, line, col) in entries {
+    if !mappings.is_empty() {
+      mappings.push(',');
+    }
+    vlq(&mut mappings, offset as i64 - prev.0 as i64);
+    vlq(&mut mappings, idx as i64 - prev.1 as i64);
+    vlq(&mut mappings, line as i64 - prev.2 as i64);
+    vlq(&mut mappings, col as i64 - prev.3 as i64);
+    prev = (offset, idx, line, col);
+  }
+
+  let names: Vec<String> = sources
+    .iter()
+    .map(|source| match source.name.as_str() {
+      "" => file,
+      name => name,
+    })
+    .map(|name| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\"")))
+    .collect();
+  format!(
+    "{{\"version\":3,\"sources\":[{}],\"names\":[],\"mappings\":\"{}\"}}",
+    names.join(","),
+    mappings
+  )
+}
+
+/// The file, line and column of `idx`.
+fn locate(sources: &[Source], idx: usize) -> Option<(u32, u32, u32)> {
+  let file = Source::find(sources, idx)?;
+  let source = &sources[file];
+  let (line, col) = line_col(&source.text, idx - source.offset);
+  Some((file as u32, line, col))
+}
+
+fn line_col(source: &str, idx: usize) -> (u32, u32) {
+  let mut line = 0;
+  let mut col = 0;
+  for ch in source.chars().take(idx) {
+    if ch == '\n' {
+      line += 1;
+      col = 0;
+    } else {
+      col += 1;
+    }
+  }
+  (line, col)
+}
+
+fn vlq(out: &mut String, val: i64) {
+  let mut val = if val < 0 { (-val << 1) | 1 } else { val << 1 };
+  loop {
+    let mut digit = val & 31;
+    val >>= 5;
+    if val > 0 {
+      digit |= 32;
+    }
+    out.push(BASE64[digit as usize] as char);
+    if val == 0 {
+      break;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::line_col;
+  use super::locate;
+  use super::vlq;
+  use whistle_common::Source;
+
+  #[test]
+  fn vlq_encoding() {
+    let mut out = String::new();
+    for val in [0, 1, -1, 16, 123] {
+      vlq(&mut out, val);
+      out.push(',');
+    }
+    assert_eq!(out, "A,C,D,gB,2H,");
+  }
+
+  #[test]
+  fn line_col_chars() {
+    assert_eq!(line_col("fn a()\n  你好", 10), (1, 3));
+  }
+
+  #[test]
+  fn locate_in_sources() {
+    let source = |name: &str, text: &str, offset| Source {
+      name: name.to_string(),
+      text: text.to_string(),
+      offset,
+    };
+    let sources = [source("", "fn a()\n", 0), source("b.whi", "\n  fn b()", 8)];
+    assert_eq!(locate(&sources, 3), Some((0, 0, 3)));
+    assert_eq!(locate(&sources, 11), Some((1, 1, 2)));
+    assert_eq!(locate(&[], 3), None);
+  }
+}
@@ -0,0 +1,63 @@
+use crate::check_all;
+use crate::compile_all;
+use crate::Checker;
+use crate::Compiler;
+
+use whistle_ast::Grammar;
+use whistle_common::CompilerErrorKind;
+use whistle_common::DiagnosticHandler;
+use whistle_common::Error;
+use whistle_common::WarningKind;
+use whistle_parser::parse_all;
+use whistle_parser::Parser;
+use whistle_preprocessor::Preprocessor;
+
+/// Preprocesses and parses `src`, returning a checker that hasn't run yet.
+pub fn parse(src: &str) -> (Checker, Grammar) {
+  let mut processor = Preprocessor::new(DiagnosticHandler::new());
+  processor.process(src);
+  let tokens = processor.finalize();
+  let mut parser = Parser::new(processor, tokens);
+  let grammar = parse_all(&mut parser);
+  (Checker::new(parser), grammar)
+}
+
+/// Preprocesses, parses and checks `src`.
+pub fn check(src: &str) -> (Checker, Grammar) {
+  let (mut checker, mut grammar) = parse(src);
+  check_all(&mut checker, &mut grammar);
+  (checker, grammar)
+}
+
+/// Checks and compiles `src`, stopping after the checker if it reports errors.
+pub fn compile(src: &str) -> (Compiler, Vec<u8>) {
+  let (checker, grammar) = check(src);
+  let failed = !checker.handler.errors.is_empty();
+  let mut compiler = Compiler::new(checker);
+  if failed {
+    return (compiler, Vec::new());
+  }
+  let bytes = compile_all(&mut compiler, grammar);
+  (compiler, bytes)
+}
+
+/// The kinds of the compiler errors reported to `handler`.
+pub fn errors(handler: &DiagnosticHandler) -> Vec<CompilerErrorKind> {
+  handler
+    .errors
+    .iter()
+    .filter_map(|err| match err {
+      Error::CompilerError(err) => Some(err.kind.clone()),
+      _ => None,
+    })
+    .collect()
+}
+
+/// The kinds of the warnings reported to `handler`.
+pub fn warnings(handler: &DiagnosticHandler) -> Vec<WarningKind> {
+  handler
+    .warnings
+    .iter()
+    .map(|warning| warning.kind.clone())
+    .collect()
+}
@@ -0,0 +1,156 @@
+use crate::compile_tip_wasm;
+use crate::compile_tip_wasm_bytes;
+use crate::tip_str;
+use crate::Checker;
+use crate::Compiler;
+use crate::Function;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use whistle_common::Span;
+use whistle_common::Tip;
+use whistle_common::WarningKind;
+use whistle_common::LINTS;
+
+/// Hooks for a `#(ident)` tip, called when the checker or compiler reaches it.
+pub trait TipHandler {
+  fn check(&self, _checker: &mut Checker, _tip: &Tip, _span: Span) {}
+
+  fn compile(&self, _compiler: &mut Compiler, _function: &mut Function, _tip: &Tip, _span: Span) {}
+}
+
+#[derive(Clone, Default)]
+pub struct TipHandlers {
+  handlers: HashMap<String, Rc<dyn TipHandler>>,
+}
+
+impl TipHandlers {
+  pub fn new() -> Self {
+    Self {
+      handlers: HashMap::new(),
+    }
+  }
+
+  pub fn builtin() -> Self {
+    let mut handlers = TipHandlers::new();
+    handlers.register("wasm_bytes", WasmBytesTip);
+    handlers.register("wasm", WasmTip);
+    handlers.register("allow", AllowTip);
+    handlers.register("deprecated", DeprecatedTip);
+    handlers
+  }
+
+  pub fn register<T: TipHandler + 'static>(&mut self, ident: &str, handler: T) {
+    self.handlers.insert(ident.to_string(), Rc::new(handler));
+  }
+
+  pub fn get(&self, ident: &str) -> Option<Rc<dyn TipHandler>> {
+    self.handlers.get(ident).cloned()
+  }
+}
+
+pub struct WasmBytesTip;
+
+impl TipHandler for WasmBytesTip {
+  fn compile(&self, compiler: &mut Compiler, function: &mut Function, tip: &Tip, span: Span) {
+    compile_tip_wasm_bytes(compiler, function, tip.clone(), span)
+  }
+}
+
+pub struct WasmTip;
+
+impl TipHandler for WasmTip {
+  fn compile(&self, compiler: &mut Compiler, function: &mut Function, tip: &Tip, span: Span) {
+    compile_tip_wasm(compiler, function, tip.clone(), span)
+  }
+}
+
+/// `#(allow) lint, ...` silences warnings, see [`crate::lint_all`].
+pub struct AllowTip;
+
+impl TipHandler for AllowTip {
+  fn check(&self, checker: &mut Checker, tip: &Tip, span: Span) {
+    for lint in allowed_lints(tip) {
+      if lint != "unused" && !LINTS.contains(&lint) {
+        checker
+          .handler
+          .warn(WarningKind::UnknownLint(lint.to_string()), span);
+      }
+    }
+  }
+}
+
+/// `#(deprecated) "note"` on a function warns wherever the function is used.
+pub struct DeprecatedTip;
+
+impl TipHandler for DeprecatedTip {
+  fn check(&self, checker: &mut Checker, tip: &Tip, _span: Span) {
+    if let Some(ident) = checker.decl.clone() {
+      checker.deprecated.insert(ident, tip_str(tip));
+    }
+  }
+}
+
+/// The lint names listed by an `allow` tip, separated by commas or spaces.
+pub fn allowed_lints(tip: &Tip) -> impl Iterator<Item = &str> {
+  tip
+    .value
+    .split(|ch: char| ch == ',' || ch.is_whitespace())
+    .filter(|lint| !lint.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::check_all;
+  use crate::test_support::check;
+  use crate::test_support::parse;
+  use crate::test_support::warnings;
+  use std::cell::RefCell;
+
+  struct RecordTip(Rc<RefCell<Vec<String>>>);
+
+  impl TipHandler for RecordTip {
+    fn check(&self, _checker: &mut Checker, tip: &Tip, _span: Span) {
+      self.0.borrow_mut().push(tip.value.trim().to_string());
+    }
+  }
+
+  #[test]
+  fn registry() {
+    let handlers = TipHandlers::builtin();
+    for ident in ["wasm", "wasm_bytes", "allow", "deprecated"] {
+      assert!(handlers.get(ident).is_some());
+    }
+    assert!(handlers.get("record").is_none());
+
+    let mut handlers = TipHandlers::new();
+    handlers.register("record", RecordTip(Rc::default()));
+    assert!(handlers.get("record").is_some());
+    assert!(handlers.get("wasm").is_none());
+  }
+
+  #[test]
+  fn custom_tips() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let (mut checker, mut grammar) =
+      parse("#(record) { outer }\nexport fn main() {\n  #(record) { inner }\n}\n");
+    checker.register_tip("record", RecordTip(seen.clone()));
+    check_all(&mut checker, &mut grammar);
+    assert_eq!(checker.handler.errors, vec![]);
+    assert_eq!(checker.handler.warnings, vec![]);
+    assert_eq!(*seen.borrow(), vec!["outer", "inner"]);
+  }
+
+  #[test]
+  fn unknown_tips() {
+    let (checker, _) = check("#(nope) { outer }\nexport fn main() {\n  #(nada) { inner }\n}\n");
+    assert_eq!(
+      warnings(&checker.handler),
+      vec![
+        WarningKind::UnknownTip("nope".to_string()),
+        WarningKind::UnknownTip("nada".to_string()),
+      ]
+    );
+  }
+}
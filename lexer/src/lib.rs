@@ -16,6 +16,10 @@ pub use whistle_common::LexerErrorKind;
 mod tokenizer;
 use tokenizer::Tokenizer;
 
+/// Tips whose value is a block of code, which may start on the line after the
+/// tip.
+pub const BLOCK_TIPS: [&str; 2] = ["wasm", "wasm_bytes"];
+
 macro_rules! ok_or_term {
   ($self:ident, $token:expr) => {
     let start = $self.tokenizer.index;
@@ -425,9 +429,26 @@ impl Lexer {
       return Err(LexerErrorKind::ExpectedRightParen);
     }
 
-    self.whitespace();
+    // A tip without a value ends at the newline, unless it takes a block and
+    // the block starts on one of the next lines
+    let mut offset = 0;
+    while self
+      .tokenizer
+      .peek_offset(offset)
+      .is_some_and(|ch| ch.is_whitespace())
+    {
+      offset += 1;
+    }
+    if BLOCK_TIPS.contains(&ident.as_str()) && self.tokenizer.peek_offset(offset) == Some('{') {
+      self.whitespace();
+    } else {
+      while let Some(' ') | Some('\t') = self.tokenizer.peek() {
+        self.tokenizer.step();
+      }
+    }
 
-    let value = if self.tokenizer.eat_char('{').is_some() {
+    let (value, value_span) = if self.tokenizer.eat_char('{').is_some() {
+      let start = self.tokenizer.index;
       let mut val = String::new();
       let mut depth = 1;
 
@@ -445,14 +466,23 @@ impl Lexer {
         }
       }
 
-      val
-    } else if let Some(val) = self.tokenizer.read_while(|ch| ch != '\n') {
-      val
+      let end = self.tokenizer.index - 1;
+      (val, Span { start, end })
     } else {
-      return Err(LexerErrorKind::ExpectedNewline);
+      let start = self.tokenizer.index;
+      let val = self
+        .tokenizer
+        .read_while(|ch| ch != '\n')
+        .unwrap_or_default();
+      let end = self.tokenizer.index;
+      (val, Span { start, end })
     };
 
-    Ok(Token::Tip(Tip { ident, value }))
+    Ok(Token::Tip(Tip {
+      ident,
+      value,
+      value_span,
+    }))
   }
 
   fn punc(&mut self) -> Result<Token, LexerErrorKind> {
@@ -546,7 +576,7 @@ mod tests {
 
   #[test]
   fn keyword() {
-    let lexer = Lexer::new("import as from export extern inline fn return if else while break continue var val for in match type struct");
+    let lexer = Lexer::new("import as from export extern builtin inline fn return if else while break continue var val for in match type struct");
 
     for tok in lexer {
       assert!(tok.is_ok());
@@ -713,7 +743,8 @@ mod tests {
       Some(Ok(TokenItem {
         token: Token::Tip(Tip {
           ident: "tip".to_string(),
-          value: "tip".to_string()
+          value: "tip".to_string(),
+          value_span: Span { start: 7, end: 10 }
         }),
         span: Span { start: 0, end: 10 }
       }))
@@ -724,13 +755,73 @@ mod tests {
       Some(Ok(TokenItem {
         token: Token::Tip(Tip {
           ident: "tip".to_string(),
-          value: " tip ".to_string()
+          value: " tip ".to_string(),
+          value_span: Span { start: 51, end: 56 }
         }),
         span: Span { start: 43, end: 57 }
       }))
     );
   }
 
+  #[test]
+  fn tip_without_value() {
+    let mut lexer = Lexer::new("#(start)\nfn");
+
+    assert_eq!(
+      lexer.next(),
+      Some(Ok(TokenItem {
+        token: Token::Tip(Tip {
+          ident: "start".to_string(),
+          value: String::new(),
+          value_span: Span { start: 8, end: 8 }
+        }),
+        span: Span { start: 0, end: 8 }
+      }))
+    );
+
+    assert_eq!(
+      lexer.next(),
+      Some(Ok(TokenItem {
+        token: Token::Keyword(Keyword::Fn),
+        span: Span { start: 9, end: 11 }
+      }))
+    );
+  }
+
+  #[test]
+  fn tip_block_on_next_line() {
+    let mut lexer = Lexer::new("#(wasm)\n{ nop }");
+
+    assert_eq!(
+      lexer.next(),
+      Some(Ok(TokenItem {
+        token: Token::Tip(Tip {
+          ident: "wasm".to_string(),
+          value: " nop ".to_string(),
+          value_span: Span { start: 9, end: 14 }
+        }),
+        span: Span { start: 0, end: 15 }
+      }))
+    );
+    assert_eq!(lexer.next(), None);
+
+    let mut lexer = Lexer::new("#(allow)\n{}");
+    assert!(matches!(
+      lexer.next(),
+      Some(Ok(TokenItem {
+        token: Token::Tip(Tip { value, .. }),
+        span: Span { start: 0, end: 8 },
+      })) if value.is_empty()
+    ));
+    assert!(matches!(
+      lexer.next(),
+      Some(Ok(TokenItem {
+        token: Token::Punc(Punc::LeftBrace),
+        ..
+      }))
+    ));
+  }
+
   #[test]
   fn punc() {
     let lexer = Lexer::new(", : . [ ] { } ( )");
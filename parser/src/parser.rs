@@ -1,8 +1,10 @@
+use whistle_ast::IdentType;
 use whistle_common::DiagnosticHandler;
 use whistle_common::ParserError;
 use whistle_common::ParserErrorKind;
 use whistle_common::ParserHandler;
 
+use whistle_common::Source;
 use whistle_common::Span;
 use whistle_common::Token;
 use whistle_common::TokenItem;
@@ -13,6 +15,11 @@ pub struct Parser {
   pub handler: DiagnosticHandler,
   pub tokens: Vec<TokenItem>,
   pub index: usize,
+  /// The generic parameters in scope, whose names parse as generic types.
+  pub generics: Vec<String>,
+  /// The type `Self` and `self` stand for in an `impl` block.
+  pub self_type: Option<IdentType>,
+  pub sources: Vec<Source>,
 }
 
 #[macro_export]
@@ -43,6 +50,9 @@ impl Parser {
       handler: preprocessor.handler,
       tokens,
       index: 0,
+      generics: Vec::new(),
+      self_type: None,
+      sources: preprocessor.sources,
     }
   }
 
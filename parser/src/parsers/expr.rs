@@ -1,11 +1,14 @@
 use crate::parser::Parser;
 use crate::parsers::ident::parse_ident_val;
 use crate::parsers::literal::parse_lit;
+use crate::parsers::types::parse_ident_type;
 use whistle_common::ParserError;
 use whistle_common::ParserErrorKind;
 
 use whistle_ast::Expr;
+use whistle_ast::Literal;
 use whistle_ast::Primary;
+use whistle_ast::Primitive;
 use whistle_ast::Unary;
 
 use whistle_common::Keyword;
@@ -19,12 +22,7 @@ pub fn parse_expr(parser: &mut Parser) -> Result<Expr, ParserError> {
     return parse_cond(parser);
   }
   let start = parser.peek()?.span.start;
-  let unary = parse_unary(parser)?;
-  let end = parser.peek_offset(-1)?.span.end;
-  let lhs = Expr::Unary {
-    unary,
-    span: Span { start, end },
-  };
+  let lhs = parse_operand(parser)?;
   let expr = parse_expr_prec(parser, start, lhs, usize::MAX)?;
   Ok(expr)
 }
@@ -50,15 +48,7 @@ pub fn parse_expr_prec(
   while let Some(op) = is_greater_precedence(parser, prec) {
     parser.step();
     let start_rhs = parser.peek()?.span.start;
-    let unary = parse_unary(parser)?;
-    let end_rhs = parser.peek_offset(-1)?.span.end;
-    let mut rhs = Expr::Unary {
-      unary,
-      span: Span {
-        start: start_rhs,
-        end: end_rhs,
-      },
-    };
+    let mut rhs = parse_operand(parser)?;
     while let Some(op) = is_greater_precedence(parser, op.get_prec()) {
       rhs = parse_expr_prec(parser, start_rhs, rhs, op.get_prec())?;
     }
@@ -73,6 +63,55 @@ pub fn parse_expr_prec(
   Ok(lhs)
 }
 
+/// A unary expression followed by any number of `as T` casts and `?`
+/// propagations, which bind tighter than every binary operator.
+pub fn parse_operand(parser: &mut Parser) -> Result<Expr, ParserError> {
+  let start = parser.peek()?.span.start;
+  let mut expr = if parser.is_tok(Token::Keyword(Keyword::Err)) {
+    parse_err(parser)?
+  } else {
+    let unary = parse_unary(parser)?;
+    let end = parser.peek_offset(-1)?.span.end;
+    Expr::Unary {
+      unary,
+      span: Span { start, end },
+    }
+  };
+  loop {
+    if parser.eat_tok(Token::Keyword(Keyword::As)).is_ok() {
+      let types = parse_ident_type(parser)?;
+      let end = parser.peek_offset(-1)?.span.end;
+      expr = Expr::Cast {
+        expr: Box::new(expr),
+        types,
+        span: Span { start, end },
+      };
+    } else if parser.eat_tok(Token::Punc(Punc::QuestionMark)).is_ok() {
+      let end = parser.peek_offset(-1)?.span.end;
+      expr = Expr::Try {
+        expr: Box::new(expr),
+        span: Span { start, end },
+      };
+    } else {
+      return Ok(expr);
+    }
+  }
+}
+
+pub fn parse_err(parser: &mut Parser) -> Result<Expr, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Keyword(Keyword::Err))?;
+  parser.eat_tok(Token::Punc(Punc::LeftParen))?;
+  let expr = parse_expr(parser)?;
+  parser.eat_tok(Token::Punc(Punc::RightParen))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(Expr::Err {
+    expr: Box::new(expr),
+    meta_id: 0,
+    span: Span { start, end },
+  })
+}
+
 pub fn parse_unary(parser: &mut Parser) -> Result<Unary, ParserError> {
   let start = parser.peek()?.span.start;
   if let Token::Operator(op) = &parser.peek()?.token.clone() {
@@ -99,6 +138,7 @@ pub fn parse_unary(parser: &mut Parser) -> Result<Unary, ParserError> {
 pub fn parse_primary(parser: &mut Parser) -> Result<Primary, ParserError> {
   match &parser.peek()?.token.clone() {
     Token::Literal(lit) => parse_lit(parser, lit.to_owned()),
+    Token::Keyword(Keyword::Primitive(Primitive::None)) => parse_lit(parser, Literal::None),
     Token::Punc(Punc::LeftParen) => parse_grouping(parser),
     Token::Punc(Punc::LeftBracket) => parse_array(parser),
     Token::Ident(ident) => parse_ident_val(parser, ident.clone()),
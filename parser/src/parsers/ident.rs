@@ -4,7 +4,9 @@ use crate::parse_ident_type;
 use crate::parse_params;
 use crate::parser::Parser;
 use whistle_common::ParserError;
+use whistle_common::ParserErrorKind;
 
+use whistle_ast::Expr;
 use whistle_ast::IdentExternFn;
 use whistle_ast::IdentImport;
 use whistle_ast::IdentType;
@@ -31,6 +33,7 @@ pub fn parse_ident_typed(parser: &mut Parser) -> Result<IdentTyped, ParserError>
     return Ok(IdentTyped {
       ident,
       type_ident,
+      default: None,
       span: Some(Span { start, end }),
     });
   };
@@ -38,6 +41,7 @@ pub fn parse_ident_typed(parser: &mut Parser) -> Result<IdentTyped, ParserError>
   Ok(IdentTyped {
     ident,
     type_ident: IdentType::Default,
+    default: None,
     span: Some(Span { start, end }),
   })
 }
@@ -93,6 +97,7 @@ pub fn parse_ident_val(parser: &mut Parser, ident: String) -> Result<Primary, Pa
     prim.push(match parser.peek()?.token {
       Token::Punc(Punc::Dot) => parse_selector(parser)?,
       Token::Punc(Punc::LeftParen) => parse_arguments(parser)?,
+      Token::Punc(Punc::LeftBracket) => parse_index(parser)?,
       _ => break,
     })
   }
@@ -115,18 +120,58 @@ pub fn parse_selector(parser: &mut Parser) -> Result<IdentVal, ParserError> {
   })
 }
 
+pub fn parse_index(parser: &mut Parser) -> Result<IdentVal, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Punc(Punc::LeftBracket))?;
+  let expr = Box::new(parse_expr(parser)?);
+  parser.eat_tok(Token::Punc(Punc::RightBracket))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(IdentVal::Index {
+    expr,
+    span: Span { start, end },
+  })
+}
+
 pub fn parse_arguments(parser: &mut Parser) -> Result<IdentVal, ParserError> {
   let start = parser.peek()?.span.start;
   parser.eat_tok(Token::Punc(Punc::LeftParen))?;
-  let args = parser.eat_repeat(
-    parse_expr,
+  let all = parser.eat_repeat(
+    parse_argument,
     Some(Token::Punc(Punc::Comma)),
     Token::Punc(Punc::RightParen),
   )?;
   parser.eat_tok(Token::Punc(Punc::RightParen))?;
   let end = parser.peek_offset(-1)?.span.end;
+  let mut args = Vec::new();
+  let mut named = Vec::new();
+  for (ident, expr) in all {
+    match ident {
+      Some(ident) => named.push((ident, expr)),
+      None if named.is_empty() => args.push(expr),
+      None => {
+        return Err(ParserError::new(
+          ParserErrorKind::ExpectedNamedArgument,
+          expr.span(),
+        ))
+      }
+    }
+  }
   Ok(IdentVal::Arguments {
     args,
+    named,
+    meta_id: 0,
     span: Span { start, end },
   })
 }
+
+/// An argument, optionally passed by name as `name: expr`.
+pub fn parse_argument(parser: &mut Parser) -> Result<(Option<String>, Expr), ParserError> {
+  if parser.is_type(Token::Ident(String::new()))
+    && parser.peek_offset(1)?.token == Token::Punc(Punc::Colon)
+  {
+    let ident = parse_ident(parser)?;
+    parser.step();
+    return Ok((Some(ident), parse_expr(parser)?));
+  }
+  Ok((None, parse_expr(parser)?))
+}
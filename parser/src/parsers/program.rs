@@ -6,14 +6,17 @@ use crate::parse_ident_import;
 use crate::parse_ident_type;
 use crate::parse_ident_typed;
 use crate::parse_stmts;
+use crate::parse_type_val;
 use crate::parser::Parser;
 use whistle_common::ParserError;
 use whistle_common::ParserErrorKind;
 
+use whistle_ast::IdentGeneric;
 use whistle_ast::IdentType;
 use whistle_ast::IdentTyped;
 use whistle_ast::Literal;
 use whistle_ast::ProgramStmt;
+use whistle_ast::Stmt;
 
 use whistle_common::Keyword;
 use whistle_common::Operator;
@@ -21,6 +24,7 @@ use whistle_common::Primitive;
 use whistle_common::Punc;
 use whistle_common::Span;
 use whistle_common::Token;
+use whistle_common::TokenItem;
 
 pub fn parse_program(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
   match parser.peek()?.token {
@@ -28,10 +32,14 @@ pub fn parse_program(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
     | Token::Keyword(Keyword::Export)
     | Token::Keyword(Keyword::Inline) => parse_fn_decl(parser),
     Token::Keyword(Keyword::Extern) => parse_extern_decl(parser),
+    Token::Keyword(Keyword::Builtin) => parse_builtin_decl(parser),
     Token::Keyword(Keyword::Import) => parse_import(parser),
     Token::Keyword(Keyword::Val) => parse_val_decl(parser),
     Token::Keyword(Keyword::Var) => parse_var_decl(parser),
     Token::Keyword(Keyword::Struct) => parse_struct_decl(parser),
+    Token::Keyword(Keyword::Interface) => parse_interface_decl(parser),
+    Token::Keyword(Keyword::Impl) => parse_impl_decl(parser),
+    Token::Tip(_) => parse_program_tip(parser),
     // Token::Keyword(Keyword::Type) => parse_type_decl(parser),
     // _ => Ok(ProgramStmt::Stmt(parse_stmt(parser)?)),
     _ => Err(ParserError::new(
@@ -41,10 +49,56 @@ pub fn parse_program(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
   }
 }
 
+pub fn parse_program_tip(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
+  let mut offset = 0;
+  while let Ok(TokenItem {
+    token: Token::Tip(_),
+    ..
+  }) = parser.peek_offset(offset)
+  {
+    offset += 1;
+  }
+
+  let attachable = matches!(
+    parser.peek_offset(offset)?.token,
+    Token::Keyword(Keyword::Fn)
+      | Token::Keyword(Keyword::Export)
+      | Token::Keyword(Keyword::Inline)
+      | Token::Keyword(Keyword::Extern)
+      | Token::Keyword(Keyword::Struct)
+  );
+
+  if !attachable {
+    let span = parser.peek()?.span;
+    let tip = eat_type!(parser, Token::Tip)?;
+    return Ok(ProgramStmt::Tip { tip, span });
+  }
+
+  let mut attached = Vec::new();
+  while let Ok(TokenItem {
+    token: Token::Tip(tip),
+    ..
+  }) = parser.peek()
+  {
+    attached.push(tip.clone());
+    parser.step();
+  }
+
+  let span = parser.peek()?.span;
+  let mut program = parse_program(parser)?;
+  match &mut program {
+    ProgramStmt::FunctionDecl { tips, .. }
+    | ProgramStmt::StructDecl { tips, .. }
+    | ProgramStmt::Extern { tips, .. } => *tips = attached,
+    _ => return Err(ParserError::new(ParserErrorKind::ExpectedProgramStmt, span)),
+  }
+  Ok(program)
+}
+
 pub fn parse_params(parser: &mut Parser) -> Result<Vec<IdentTyped>, ParserError> {
   parser.eat_tok(Token::Punc(Punc::LeftParen))?;
   let idents = parser.eat_repeat(
-    parse_ident_typed,
+    parse_param,
     Some(Token::Punc(Punc::Comma)),
     Token::Punc(Punc::RightParen),
   )?;
@@ -52,6 +106,28 @@ pub fn parse_params(parser: &mut Parser) -> Result<Vec<IdentTyped>, ParserError>
   Ok(idents)
 }
 
+/// A parameter, with an optional default value as `ident: T = expr`. The
+/// receiver of a method is written `self`, and has the type `Self`.
+pub fn parse_param(parser: &mut Parser) -> Result<IdentTyped, ParserError> {
+  if parser.is_tok(Token::Ident(String::from("self")))
+    && parser.peek_offset(1)?.token != Token::Punc(Punc::Colon)
+  {
+    let span = parser.peek()?.span;
+    let type_ident = parse_type_val(parser, String::from("Self"))?;
+    return Ok(IdentTyped {
+      ident: String::from("self"),
+      type_ident,
+      default: None,
+      span: Some(span),
+    });
+  }
+  let mut param = parse_ident_typed(parser)?;
+  if parser.eat_tok(Token::Operator(Operator::Assign)).is_ok() {
+    param.default = Some(parse_expr(parser)?);
+  }
+  Ok(param)
+}
+
 // pub fn parse_type_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
 //   let export = parser.eat_tok(Token::Keyword(Keyword::Export)).is_ok();
 //   parser.eat_tok(Token::Keyword(Keyword::Type))?;
@@ -85,6 +161,7 @@ pub fn parse_struct_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError
   parser.eat_tok(Token::Punc(Punc::RightBrace))?;
   let end = parser.peek_offset(-1)?.span.end;
   Ok(ProgramStmt::StructDecl {
+    tips: Vec::new(),
     export,
     ident,
     params,
@@ -98,6 +175,43 @@ pub fn parse_fn_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
   let inline = parser.eat_tok(Token::Keyword(Keyword::Inline)).is_ok();
   parser.eat_tok(Token::Keyword(Keyword::Fn))?;
   let ident = parse_ident(parser)?;
+  let generics = if parser.eat_tok(Token::Operator(Operator::LessThan)).is_ok() {
+    let generics = parser.eat_repeat(
+      parse_ident_generic,
+      Some(Token::Punc(Punc::Comma)),
+      Token::Operator(Operator::GreaterThan),
+    )?;
+    parser.eat_tok(Token::Operator(Operator::GreaterThan))?;
+    generics
+  } else {
+    Vec::new()
+  };
+  let outer = parser.generics.len();
+  parser
+    .generics
+    .extend(generics.iter().map(|generic| generic.ident.clone()));
+  let rest = parse_fn_rest(parser);
+  parser.generics.truncate(outer);
+  let (params, ret_type, stmt) = rest?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(ProgramStmt::FunctionDecl {
+    tips: Vec::new(),
+    inline,
+    export,
+    ident,
+    generics,
+    params,
+    ret_type,
+    stmt,
+    span: Span { start, end },
+  })
+}
+
+/// The parameters, return type and body of a function, in which its generic
+/// parameters are in scope.
+fn parse_fn_rest(
+  parser: &mut Parser,
+) -> Result<(Vec<IdentTyped>, IdentType, Vec<Stmt>), ParserError> {
   let params = parse_params(parser)?;
   let ret_type = if parser.eat_tok(Token::Punc(Punc::Colon)).is_ok() {
     parse_ident_type(parser)?
@@ -109,14 +223,76 @@ pub fn parse_fn_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
     }
   };
   let stmt = parse_stmts(parser)?;
+  Ok((params, ret_type, stmt))
+}
+
+/// A generic parameter with its bounds, `T: Show + Eq`.
+pub fn parse_ident_generic(parser: &mut Parser) -> Result<IdentGeneric, ParserError> {
+  let start = parser.peek()?.span.start;
+  let ident = parse_ident(parser)?;
+  let mut bounds = Vec::new();
+  if parser.eat_tok(Token::Punc(Punc::Colon)).is_ok() {
+    bounds.push(parse_ident(parser)?);
+    while parser.eat_tok(Token::Operator(Operator::Add)).is_ok() {
+      bounds.push(parse_ident(parser)?);
+    }
+  }
   let end = parser.peek_offset(-1)?.span.end;
-  Ok(ProgramStmt::FunctionDecl {
-    inline,
-    export,
+  Ok(IdentGeneric {
     ident,
-    params,
-    ret_type,
-    stmt,
+    bounds,
+    span: Span { start, end },
+  })
+}
+
+/// `interface ident { fn method(self): T }`, in which `Self` is the type
+/// implementing the interface.
+pub fn parse_interface_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Keyword(Keyword::Interface))?;
+  let ident = parse_ident(parser)?;
+  parser.eat_tok(Token::Punc(Punc::LeftBrace))?;
+  parser.generics.push(String::from("Self"));
+  let methods = parser.eat_repeat(parse_ident_extern, None, Token::Punc(Punc::RightBrace));
+  parser.generics.pop();
+  let methods = methods?;
+  parser.eat_tok(Token::Punc(Punc::RightBrace))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(ProgramStmt::InterfaceDecl {
+    ident,
+    methods,
+    span: Span { start, end },
+  })
+}
+
+/// `impl interface for types { fn method(self): T { .. } }`.
+pub fn parse_impl_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Keyword(Keyword::Impl))?;
+  let ident_start = parser.peek()?.span.start;
+  let ident = parse_ident(parser)?;
+  let (interface, types) = match parser.eat_tok(Token::Keyword(Keyword::For)) {
+    Ok(_) => (Some(ident), parse_ident_type(parser)?),
+    Err(_) => {
+      let end = parser.peek_offset(-1)?.span.end;
+      let span = Some(Span {
+        start: ident_start,
+        end,
+      });
+      (None, IdentType::Ident { ident, span })
+    }
+  };
+  parser.eat_tok(Token::Punc(Punc::LeftBrace))?;
+  let outer = parser.self_type.replace(types.clone());
+  let fns = parser.eat_repeat(parse_fn_decl, None, Token::Punc(Punc::RightBrace));
+  parser.self_type = outer;
+  let fns = fns?;
+  parser.eat_tok(Token::Punc(Punc::RightBrace))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(ProgramStmt::ImplDecl {
+    interface,
+    types,
+    fns,
     span: Span { start, end },
   })
 }
@@ -136,6 +312,27 @@ pub fn parse_extern_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError
   Ok(ProgramStmt::Extern {
     idents,
     namespace,
+    tips: Vec::new(),
+    span: Span { start, end },
+  })
+}
+
+pub fn parse_builtin_decl(parser: &mut Parser) -> Result<ProgramStmt, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Keyword(Keyword::Builtin))?;
+  parser.eat_tok(Token::Punc(Punc::Snabel))?;
+  let module = parse_ident(parser)?;
+  parser.eat_tok(Token::Punc(Punc::LeftBrace))?;
+  let idents = parser.eat_repeat(
+    parse_ident_import,
+    Some(Token::Punc(Punc::Comma)),
+    Token::Punc(Punc::RightBrace),
+  )?;
+  parser.eat_tok(Token::Punc(Punc::RightBrace))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(ProgramStmt::Builtin {
+    module,
+    idents,
     span: Span { start, end },
   })
 }
@@ -6,10 +6,8 @@ use crate::parser::Parser;
 use whistle_common::ParserError;
 
 use whistle_ast::Expr;
-use whistle_ast::Primary;
 use whistle_ast::Stmt;
 
-use whistle_ast::Unary;
 use whistle_common::Keyword;
 use whistle_common::Operator;
 use whistle_common::Punc;
@@ -110,6 +108,9 @@ pub fn parse_return_stmt(parser: &mut Parser) -> Result<Stmt, ParserError> {
 pub fn parse_var_decl(parser: &mut Parser) -> Result<Stmt, ParserError> {
   let start = parser.peek()?.span.start;
   parser.eat_tok(Token::Keyword(Keyword::Var))?;
+  if parser.peek()?.token == Token::Punc(Punc::LeftParen) {
+    return parse_tuple_decl(parser, true, start);
+  }
   let ident_typed = parse_ident_typed(parser)?;
   parser.eat_tok(Token::Operator(Operator::Assign))?;
   let val = parse_expr(parser)?;
@@ -124,6 +125,9 @@ pub fn parse_var_decl(parser: &mut Parser) -> Result<Stmt, ParserError> {
 pub fn parse_val_decl(parser: &mut Parser) -> Result<Stmt, ParserError> {
   let start = parser.peek()?.span.start;
   parser.eat_tok(Token::Keyword(Keyword::Val))?;
+  if parser.peek()?.token == Token::Punc(Punc::LeftParen) {
+    return parse_tuple_decl(parser, false, start);
+  }
   let ident_typed = parse_ident_typed(parser)?;
   parser.eat_tok(Token::Operator(Operator::Assign))?;
   let val = parse_expr(parser)?;
@@ -135,6 +139,29 @@ pub fn parse_val_decl(parser: &mut Parser) -> Result<Stmt, ParserError> {
   })
 }
 
+pub fn parse_tuple_decl(
+  parser: &mut Parser,
+  mutable: bool,
+  start: usize,
+) -> Result<Stmt, ParserError> {
+  parser.eat_tok(Token::Punc(Punc::LeftParen))?;
+  let idents = parser.eat_repeat(
+    parse_ident_typed,
+    Some(Token::Punc(Punc::Comma)),
+    Token::Punc(Punc::RightParen),
+  )?;
+  parser.eat_tok(Token::Punc(Punc::RightParen))?;
+  parser.eat_tok(Token::Operator(Operator::Assign))?;
+  let val = parse_expr(parser)?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(Stmt::TupleDecl {
+    mutable,
+    idents,
+    val,
+    span: Span { start, end },
+  })
+}
+
 pub fn parse_tip(parser: &mut Parser) -> Result<Stmt, ParserError> {
   let span = parser.peek()?.span;
   let tip = eat_type!(parser, Token::Tip)?;
@@ -155,24 +182,18 @@ pub fn parse_expr_stmt(parser: &mut Parser) -> Result<Stmt, ParserError> {
   let start = parser.peek()?.span.start;
   let expr = parse_expr(parser)?;
   let end = parser.peek_offset(-1)?.span.end;
-  if let Expr::Binary { op, lhs, rhs, span } = expr.clone() {
-    if op.clone() == Operator::Assign {
-      if let Expr::Unary {
-        unary:
-          Unary::Primary {
-            prim: Primary::IdentVal { ident, .. },
-            ..
-          },
-        ..
-      } = (*lhs).clone()
-      {
-        return Ok(Stmt::Assign {
-          ident,
-          rhs: *rhs,
-          span,
-        });
-      }
-    }
+  if let Expr::Binary {
+    op: Operator::Assign,
+    lhs,
+    rhs,
+    span,
+  } = expr
+  {
+    return Ok(Stmt::Assign {
+      lhs: *lhs,
+      rhs: *rhs,
+      span,
+    });
   }
   Ok(Stmt::Expr {
     expr,
@@ -1,3 +1,4 @@
+use crate::parse_expr;
 use crate::parser::Parser;
 use whistle_common::ParserError;
 use whistle_common::ParserErrorKind;
@@ -13,25 +14,61 @@ use whistle_common::Token;
 
 pub fn parse_ident_type(parser: &mut Parser) -> Result<IdentType, ParserError> {
   let start = parser.peek()?.span.start;
-  let ident_type = match &parser.peek()?.token.clone() {
+  let mut ident_type = match &parser.peek()?.token.clone() {
     Token::Keyword(Keyword::Primitive(prim)) => parse_type_prim(parser, prim.clone()),
     Token::Ident(ident) => parse_type_val(parser, ident.clone()),
+    Token::Punc(Punc::LeftParen) => parse_type_tuple(parser),
     _ => Err(ParserError::new(
       ParserErrorKind::ExpectedType,
       parser.peek()?.span,
     )),
   }?;
 
-  if parser.eat_tok(Token::Punc(Punc::LeftBracket)).is_ok()
-    && parser.eat_tok(Token::Punc(Punc::RightBracket)).is_ok()
-  {
-    let end = parser.peek_offset(-1)?.span.end;
-    Ok(IdentType::Array {
-      ident: Box::new(ident_type),
-      span: Some(Span { start, end }),
-    })
-  } else {
-    Ok(ident_type)
+  loop {
+    ident_type = if parser.eat_tok(Token::Punc(Punc::LeftBracket)).is_ok() {
+      let size = if parser.is_tok(Token::Punc(Punc::RightBracket)) {
+        None
+      } else {
+        Some(Box::new(parse_expr(parser)?))
+      };
+      parser.eat_tok(Token::Punc(Punc::RightBracket))?;
+      let end = parser.peek_offset(-1)?.span.end;
+      IdentType::Array {
+        ident: Box::new(ident_type),
+        size,
+        span: Some(Span { start, end }),
+      }
+    } else if parser.eat_tok(Token::Punc(Punc::QuestionMark)).is_ok() {
+      let end = parser.peek_offset(-1)?.span.end;
+      IdentType::Option {
+        ident: Box::new(ident_type),
+        span: Some(Span { start, end }),
+      }
+    } else if parser.eat_tok(Token::Operator(Operator::Coalesce)).is_ok() {
+      // `T??` lexes as the `??` operator
+      let end = parser.peek_offset(-1)?.span.end;
+      let inner = IdentType::Option {
+        ident: Box::new(ident_type),
+        span: Some(Span {
+          start,
+          end: end - 1,
+        }),
+      };
+      IdentType::Option {
+        ident: Box::new(inner),
+        span: Some(Span { start, end }),
+      }
+    } else if parser.eat_tok(Token::Operator(Operator::LogNot)).is_ok() {
+      let err = parse_ident_type(parser)?;
+      let end = parser.peek_offset(-1)?.span.end;
+      return Ok(IdentType::Result {
+        ok: Box::new(ident_type),
+        err: Box::new(err),
+        span: Some(Span { start, end }),
+      });
+    } else {
+      return Ok(ident_type);
+    };
   }
 }
 
@@ -41,23 +78,51 @@ pub fn parse_type_prim(parser: &mut Parser, prim: Primitive) -> Result<IdentType
   Ok(IdentType::Primitive { prim, span })
 }
 
+pub fn parse_type_tuple(parser: &mut Parser) -> Result<IdentType, ParserError> {
+  let start = parser.peek()?.span.start;
+  parser.eat_tok(Token::Punc(Punc::LeftParen))?;
+  let types = parser.eat_repeat(
+    parse_ident_type,
+    Some(Token::Punc(Punc::Comma)),
+    Token::Punc(Punc::RightParen),
+  )?;
+  parser.eat_tok(Token::Punc(Punc::RightParen))?;
+  let end = parser.peek_offset(-1)?.span.end;
+  Ok(IdentType::Tuple {
+    types,
+    span: Some(Span { start, end }),
+  })
+}
+
 pub fn parse_type_val(parser: &mut Parser, ident: String) -> Result<IdentType, ParserError> {
   let start = parser.peek()?.span.start;
   parser.step();
   if parser.eat_tok(Token::Operator(Operator::LessThan)).is_ok() {
-    let prim = parse_type_arguments(parser)?;
+    let mut prim = parse_type_arguments(parser)?;
     let end = parser.peek_offset(-1)?.span.end;
-    return Ok(IdentType::IdentType {
-      ident,
-      prim,
-      span: Some(Span { start, end }),
-    });
+    let span = Some(Span { start, end });
+    if ident == "Result" && prim.len() == 2 {
+      let err = prim.pop().unwrap();
+      let ok = prim.pop().unwrap();
+      return Ok(IdentType::Result {
+        ok: Box::new(ok),
+        err: Box::new(err),
+        span,
+      });
+    }
+    return Ok(IdentType::IdentType { ident, prim, span });
   }
   let end = parser.peek_offset(-1)?.span.end;
-  Ok(IdentType::Ident {
-    ident,
-    span: Some(Span { start, end }),
-  })
+  let span = Some(Span { start, end });
+  if ident == "Self" {
+    if let Some(self_type) = &parser.self_type {
+      return Ok(self_type.clone());
+    }
+  }
+  if parser.generics.contains(&ident) {
+    return Ok(IdentType::Generic { var: ident, span });
+  }
+  Ok(IdentType::Ident { ident, span })
 }
 
 pub fn parse_type_arguments(parser: &mut Parser) -> Result<Vec<IdentType>, ParserError> {
@@ -2,16 +2,22 @@ use whistle_common::DiagnosticHandler;
 use whistle_common::Keyword;
 use whistle_common::LexerHandler;
 use whistle_common::Literal;
+use whistle_common::Source;
 use whistle_common::Span;
 use whistle_common::Token;
 use whistle_common::TokenItem;
 use whistle_lexer::Lexer;
 use whistle_lexer::LexerErrorKind;
 
+mod std_lib;
+pub use std_lib::*;
+
 #[derive(Clone)]
 pub struct Preprocessor {
   pub token_list: Vec<Vec<TokenItem>>,
   pub handler: DiagnosticHandler,
+  pub std_imported: Vec<String>,
+  pub sources: Vec<Source>,
 }
 
 impl Preprocessor {
@@ -19,25 +25,42 @@ impl Preprocessor {
     Self {
       token_list: Vec::new(),
       handler,
+      std_imported: Vec::new(),
+      sources: Vec::new(),
     }
   }
 
   pub fn process(&mut self, src: &str) {
+    self.process_file("", src)
+  }
+
+  /// Processes `src`, imported as `name`, placing its spans after the files
+  /// processed before it.
+  pub fn process_file(&mut self, name: &str, src: &str) {
+    let offset = self
+      .sources
+      .last()
+      .map_or(0, |source| source.offset + source.text.chars().count() + 1);
+    self.sources.push(Source {
+      name: name.to_string(),
+      text: src.to_string(),
+      offset,
+    });
     let mut lexer = Lexer::new(src);
     let mut tokens: Vec<TokenItem> = Vec::new();
 
-    let mut imports: Vec<String> = Vec::new();
+    let mut imports: Vec<(String, Span)> = Vec::new();
     loop {
       let item = match lexer.next() {
-        Some(Ok(v)) => v,
-        Some(Err(err)) => return self.handler.throw(err.kind, err.span),
+        Some(Ok(v)) => shift(v, offset),
+        Some(Err(err)) => return self.handler.throw(err.kind, shift_span(err.span, offset)),
         None => break,
       };
 
       if item.token == Token::Keyword(Keyword::Import) {
         let import = match lexer.next() {
-          Some(Ok(v)) => v,
-          Some(Err(err)) => return self.handler.throw(err.kind, err.span),
+          Some(Ok(v)) => shift(v, offset),
+          Some(Err(err)) => return self.handler.throw(err.kind, shift_span(err.span, offset)),
           None => return self.handler.throw(LexerErrorKind::Eof, item.span),
         };
 
@@ -57,19 +80,32 @@ impl Preprocessor {
           }
         };
 
-        imports.push(import_file);
+        imports.push((import_file, import.span));
       } else {
         tokens.push(item);
       }
     }
-    for mut file_name in imports {
+    for (mut file_name, span) in imports {
+      if let Some(module) = file_name.strip_prefix("@std/") {
+        if self.std_imported.iter().any(|imported| imported == module) {
+          continue;
+        }
+        match std_module(module) {
+          Some(src) => {
+            self.std_imported.push(module.to_string());
+            self.process_file(&file_name, src);
+            continue;
+          }
+          None => return self.handler.throw(LexerErrorKind::UnknownStdModule, span),
+        }
+      }
       if file_name.starts_with("@") {
         file_name.remove(0);
         file_name =
           "https://raw.githubusercontent.com/whistle-lang/std/main/".to_owned() + &file_name;
       }
       let file_data = if url::Url::parse(&file_name).is_ok() {
-        match reqwest::blocking::get(file_name) {
+        match reqwest::blocking::get(&file_name) {
           Ok(v) => match v.text() {
             Ok(v) => v,
             Err(_) => {
@@ -85,7 +121,7 @@ impl Preprocessor {
           }
         }
       } else {
-        match std::fs::read_to_string(file_name) {
+        match std::fs::read_to_string(&file_name) {
           Ok(v) => v,
           Err(_) => {
             return self
@@ -94,7 +130,7 @@ impl Preprocessor {
           }
         }
       };
-      self.process(&file_data);
+      self.process_file(&file_name, &file_data);
     }
 
     self.token_list.push(tokens);
@@ -109,3 +145,98 @@ impl Preprocessor {
     })
   }
 }
+
+fn shift_span(span: Span, offset: usize) -> Span {
+  Span {
+    start: span.start + offset,
+    end: span.end + offset,
+  }
+}
+
+fn shift(mut item: TokenItem, offset: usize) -> TokenItem {
+  item.span = shift_span(item.span, offset);
+  if let Token::Tip(tip) = &mut item.token {
+    tip.value_span = shift_span(tip.value_span, offset);
+  }
+  item
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::std_module;
+  use crate::Preprocessor;
+  use crate::STD_MODULES;
+  use whistle_common::DiagnosticHandler;
+  use whistle_common::Error;
+  use whistle_common::Keyword;
+  use whistle_common::Token;
+  use whistle_lexer::LexerErrorKind;
+
+  fn process(src: &str) -> Preprocessor {
+    let mut processor = Preprocessor::new(DiagnosticHandler::new());
+    processor.process(src);
+    processor
+  }
+
+  fn defines(processor: &Preprocessor, ident: &str) -> usize {
+    let tokens = processor.finalize();
+    tokens
+      .windows(2)
+      .filter(|pair| {
+        pair[0].token == Token::Keyword(Keyword::Fn)
+          && pair[1].token == Token::Ident(ident.to_string())
+      })
+      .count()
+  }
+
+  #[test]
+  fn std_sources() {
+    for module in STD_MODULES {
+      let processor = process(std_module(module).unwrap());
+      assert!(
+        processor.handler.errors.is_empty(),
+        "@std/{}: {:?}",
+        module,
+        processor.handler.errors
+      );
+    }
+  }
+
+  #[test]
+  fn std_imports() {
+    let processor = process("import \"@std/io\"\nimport \"@std/io\"");
+    assert!(processor.handler.errors.is_empty());
+    assert_eq!(processor.std_imported, vec!["io", "wasi", "mem"]);
+    assert_eq!(defines(&processor, "io_println"), 1);
+    assert_eq!(defines(&processor, "std_strlen"), 1);
+
+    // Each file's spans start after the files processed before it.
+    let names: Vec<&str> = processor
+      .sources
+      .iter()
+      .map(|source| source.name.as_str())
+      .collect();
+    assert_eq!(names, vec!["", "@std/io", "@std/wasi", "@std/mem"]);
+    let io = &processor.sources[1];
+    assert_eq!(io.offset, processor.sources[0].text.chars().count() + 1);
+    let tokens = processor.finalize();
+    let println = tokens
+      .iter()
+      .find(|item| item.token == Token::Ident(String::from("io_println")))
+      .unwrap();
+    let start = println.span.start - io.offset;
+    assert_eq!(
+      io.text.chars().skip(start).take(10).collect::<String>(),
+      "io_println"
+    );
+  }
+
+  #[test]
+  fn unknown_std_module() {
+    let processor = process("import \"@std/nope\"");
+    assert!(matches!(
+      processor.handler.errors.as_slice(),
+      [Error::LexerError(err)] if err.kind == LexerErrorKind::UnknownStdModule
+    ));
+  }
+}
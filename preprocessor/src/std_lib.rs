@@ -0,0 +1,19 @@
+/// The std modules bundled with the compiler.
+pub const STD_MODULES: [&str; 8] = [
+  "clock", "env", "fs", "io", "mem", "process", "random", "wasi",
+];
+
+/// Source of a std module bundled with the compiler, imported as `@std/<name>`.
+pub fn std_module(name: &str) -> Option<&'static str> {
+  match name {
+    "clock" => Some(include_str!("../std/clock.whi")),
+    "env" => Some(include_str!("../std/env.whi")),
+    "fs" => Some(include_str!("../std/fs.whi")),
+    "io" => Some(include_str!("../std/io.whi")),
+    "mem" => Some(include_str!("../std/mem.whi")),
+    "process" => Some(include_str!("../std/process.whi")),
+    "random" => Some(include_str!("../std/random.whi")),
+    "wasi" => Some(include_str!("../std/wasi.whi")),
+    _ => None,
+  }
+}